@@ -16,7 +16,13 @@ async fn main() -> anyhow::Result<()> {
             Err(err)
         }
     })?;
-    chapter_tracker::links_handling::refresh_links_directory(&mut pool.acquire().await?, &path)
-        .await?;
+    let report = chapter_tracker::links_handling::refresh_links_directory(
+        &mut pool.acquire().await?,
+        path,
+        &Default::default(),
+        Default::default(),
+    )
+    .await?;
+    println!("{}", report);
     Ok(())
 }
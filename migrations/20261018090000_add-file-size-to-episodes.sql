@@ -0,0 +1,3 @@
+-- Add migration script here
+
+ALTER TABLE episodes ADD file_size INTEGER;
@@ -0,0 +1,28 @@
+-- Add migration script here
+
+CREATE TABLE IF NOT EXISTS read_events (id integer primary key autoincrement, episode integer, timestamp datetime, note text, source text);
+CREATE INDEX IF NOT EXISTS read_events_episode ON read_events(episode);
+
+INSERT INTO read_events(episode, timestamp, source)
+SELECT id, date_of_read, 'backfill' FROM episodes WHERE date_of_read IS NOT NULL;
+
+-- episodes.date_of_read is kept as the time of the last read. Marking an episode as unread sets it
+-- to NULL without deleting the history.
+CREATE TRIGGER IF NOT EXISTS read_events_after_insert AFTER INSERT ON read_events
+BEGIN
+    UPDATE episodes
+    SET date_of_read = max(coalesce(date_of_read, NEW.timestamp), NEW.timestamp)
+    WHERE id = NEW.episode;
+END;
+
+CREATE TRIGGER IF NOT EXISTS read_events_after_delete AFTER DELETE ON read_events
+BEGIN
+    UPDATE episodes
+    SET date_of_read = (SELECT max(timestamp) FROM read_events WHERE episode = OLD.episode)
+    WHERE id = OLD.episode AND date_of_read IS NOT NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS episodes_after_delete AFTER DELETE ON episodes
+BEGIN
+    DELETE FROM read_events WHERE episode = OLD.id;
+END;
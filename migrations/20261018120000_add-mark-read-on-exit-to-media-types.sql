@@ -0,0 +1,4 @@
+-- Add migration script here
+
+ALTER TABLE media_types ADD mark_read_on_exit BOOLEAN;
+UPDATE media_types SET mark_read_on_exit = false WHERE mark_read_on_exit IS NULL;
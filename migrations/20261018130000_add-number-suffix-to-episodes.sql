@@ -0,0 +1,6 @@
+-- Add migration script here
+
+-- Chapter numbers can now have a fractional part, which is stored in the number column (making it
+-- a REAL for these episodes), and a letter suffix, which is stored in the new number_suffix column.
+ALTER TABLE episodes ADD number_suffix TEXT;
+UPDATE episodes SET number_suffix = '' WHERE number_suffix IS NULL;
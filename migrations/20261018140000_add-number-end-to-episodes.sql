@@ -0,0 +1,5 @@
+-- Add migration script here
+
+-- The last chapter of episodes that cover a range of chapters, as text (e.g. "12" or "12.5a").
+-- NULL for episodes of a single chapter.
+ALTER TABLE episodes ADD number_end TEXT;
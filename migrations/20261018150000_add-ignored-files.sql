@@ -0,0 +1,5 @@
+-- Add migration script here
+
+-- Files that discovery should not register as episodes, like redundant duplicates that were merged
+-- into another episode but kept on disk.
+CREATE TABLE IF NOT EXISTS ignored_files (file text primary key);
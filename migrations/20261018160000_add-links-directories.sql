@@ -0,0 +1,23 @@
+-- Add migration script here
+
+-- Named links directories. Empty filter tables mean no filtering - except for media types, where it
+-- means all the media types that maintain symlinks. max_unread_per_series NULL means no limit.
+CREATE TABLE IF NOT EXISTS links_directories (id integer primary key autoincrement, name text unique, dir text, max_unread_per_series integer);
+CREATE TABLE IF NOT EXISTS links_directory_media_types (links_directory integer, media_type integer);
+CREATE TABLE IF NOT EXISTS links_directory_serieses (links_directory integer, series integer);
+
+CREATE TRIGGER IF NOT EXISTS links_directories_after_delete AFTER DELETE ON links_directories
+BEGIN
+    DELETE FROM links_directory_media_types WHERE links_directory = OLD.id;
+    DELETE FROM links_directory_serieses WHERE links_directory = OLD.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS media_types_after_delete_links_directories AFTER DELETE ON media_types
+BEGIN
+    DELETE FROM links_directory_media_types WHERE media_type = OLD.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS serieses_after_delete_links_directories AFTER DELETE ON serieses
+BEGIN
+    DELETE FROM links_directory_serieses WHERE series = OLD.id;
+END;
@@ -0,0 +1,5 @@
+-- Add migration script here
+
+-- An empty template means the link is named after the episode, with the chapter number padded.
+ALTER TABLE media_types ADD link_template TEXT;
+UPDATE media_types SET link_template = '' WHERE link_template IS NULL;
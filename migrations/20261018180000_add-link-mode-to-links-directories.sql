@@ -0,0 +1,4 @@
+-- Add migration script here
+
+ALTER TABLE links_directories ADD link_mode TEXT;
+UPDATE links_directories SET link_mode = 'symlink' WHERE link_mode IS NULL;
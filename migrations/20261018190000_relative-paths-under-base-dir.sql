@@ -0,0 +1,36 @@
+-- Add migration script here
+
+CREATE TEMP TABLE series_base_dirs AS
+SELECT serieses.id AS series, rtrim(coalesce(media_types.base_dir, ''), '/') AS base_dir
+FROM serieses
+INNER JOIN media_types ON media_types.id = serieses.media_type;
+
+DELETE FROM series_base_dirs WHERE base_dir = '';
+
+UPDATE episodes
+SET file = substr(file, length((SELECT base_dir FROM series_base_dirs WHERE series_base_dirs.series = episodes.series)) + 2)
+WHERE EXISTS (
+    SELECT 1 FROM series_base_dirs
+    WHERE series_base_dirs.series = episodes.series
+    AND substr(episodes.file, 1, length(series_base_dirs.base_dir) + 1) = series_base_dirs.base_dir || '/'
+    AND length(episodes.file) > length(series_base_dirs.base_dir) + 1
+);
+
+UPDATE directories
+SET dir = '.'
+WHERE EXISTS (
+    SELECT 1 FROM series_base_dirs
+    WHERE series_base_dirs.series = directories.series
+    AND rtrim(directories.dir, '/') = series_base_dirs.base_dir
+);
+
+UPDATE directories
+SET dir = rtrim(substr(dir, length((SELECT base_dir FROM series_base_dirs WHERE series_base_dirs.series = directories.series)) + 2), '/')
+WHERE EXISTS (
+    SELECT 1 FROM series_base_dirs
+    WHERE series_base_dirs.series = directories.series
+    AND substr(directories.dir, 1, length(series_base_dirs.base_dir) + 1) = series_base_dirs.base_dir || '/'
+    AND length(rtrim(directories.dir, '/')) > length(series_base_dirs.base_dir) + 1
+);
+
+DROP TABLE series_base_dirs;
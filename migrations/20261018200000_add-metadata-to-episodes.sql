@@ -0,0 +1,11 @@
+-- Add migration script here
+
+ALTER TABLE episodes ADD title TEXT;
+ALTER TABLE episodes ADD page_count INTEGER;
+-- In seconds
+ALTER TABLE episodes ADD duration REAL;
+ALTER TABLE episodes ADD release_date TEXT;
+ALTER TABLE episodes ADD date_of_metadata_extraction DATETIME;
+
+ALTER TABLE media_types ADD extract_metadata BOOLEAN;
+UPDATE media_types SET extract_metadata = false WHERE extract_metadata IS NULL;
@@ -0,0 +1,12 @@
+-- Add migration script here
+
+-- The runs of the serieses' download commands. exit_code is NULL when the command could not be
+-- started or was killed for exceeding its timeout. output has the command's stdout and stderr,
+-- interleaved, and is truncated to its last part when too long.
+CREATE TABLE IF NOT EXISTS download_runs (id integer primary key autoincrement, series integer, command text, date_started datetime, date_finished datetime, exit_code integer, timed_out boolean, output text);
+CREATE INDEX IF NOT EXISTS download_runs_series ON download_runs(series);
+
+CREATE TRIGGER IF NOT EXISTS serieses_after_delete_download_runs AFTER DELETE ON serieses
+BEGIN
+    DELETE FROM download_runs WHERE series = OLD.id;
+END;
@@ -0,0 +1,8 @@
+-- Add migration script here
+
+-- A series without its own download_command runs its media type's download_command_template, with
+-- its source and episodes filling the placeholders.
+ALTER TABLE media_types ADD download_command_template TEXT;
+UPDATE media_types SET download_command_template = '' WHERE download_command_template IS NULL;
+
+ALTER TABLE serieses ADD source TEXT;
@@ -0,0 +1,11 @@
+-- Add migration script here
+
+-- Jobs the GUI runs periodically. interval_minutes 0 means the job is not scheduled. last_result
+-- describes how the last run went, and last_succeeded says whether it was a failure.
+CREATE TABLE IF NOT EXISTS scheduled_jobs (id integer primary key autoincrement, job text unique, interval_minutes integer, last_run datetime, last_result text, last_succeeded boolean);
+
+INSERT OR IGNORE INTO scheduled_jobs(job, interval_minutes) VALUES
+    ('scan', 0),
+    ('clean_dangling', 0),
+    ('refresh_links', 0),
+    ('download', 0);
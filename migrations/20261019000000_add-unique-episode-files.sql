@@ -0,0 +1,31 @@
+-- Add migration script here
+
+-- Scans that ran at the same time could register the same file twice. Keep the oldest episode of
+-- each file, with the read events of its duplicates, and then make sure it can't happen again.
+UPDATE episodes
+SET date_of_read = (
+    SELECT max(duplicates.date_of_read) FROM episodes AS duplicates
+    WHERE duplicates.series = episodes.series AND duplicates.file = episodes.file
+)
+WHERE NOT EXISTS (
+    SELECT 1 FROM episodes AS older
+    WHERE older.series = episodes.series AND older.file = episodes.file AND older.id < episodes.id
+);
+
+UPDATE read_events
+SET episode = (
+    SELECT min(oldest.id) FROM episodes AS duplicate
+    INNER JOIN episodes AS oldest ON oldest.series = duplicate.series AND oldest.file = duplicate.file
+    WHERE duplicate.id = read_events.episode
+)
+WHERE episode IN (SELECT id FROM episodes);
+
+DELETE FROM episodes
+WHERE EXISTS (
+    SELECT 1 FROM episodes AS older
+    WHERE older.series = episodes.series AND older.file = episodes.file AND older.id < episodes.id
+);
+
+-- Stored paths are relative to the media type's base directory, so the same path may belong to
+-- serieses of different media types.
+CREATE UNIQUE INDEX IF NOT EXISTS episodes_series_file ON episodes(series, file);
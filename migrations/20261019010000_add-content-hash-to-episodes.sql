@@ -0,0 +1,5 @@
+-- Add migration script here
+
+-- The SHA-256 of the episode's file, from when it was last hashed. Kept so that a moved file can be
+-- recognized by its content after the original is gone.
+ALTER TABLE episodes ADD content_hash BLOB;
@@ -9,6 +9,8 @@ use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
 #[derive(typed_builder::TypedBuilder)]
 pub struct DbActor {
     pool: SqlitePool,
+    #[builder(setter(skip), default)]
+    scan_lock: std::rc::Rc<tokio::sync::Mutex<()>>,
 }
 
 impl Actor for DbActor {
@@ -40,51 +42,46 @@ impl Default for DbActor {
         })
         .join()
         .unwrap();
-        Self { pool }
+        Self {
+            pool,
+            scan_lock: Default::default(),
+        }
     }
 }
 
-impl Handler<crate::msgs::DiscoverFiles> for DbActor {
-    type Result = ResponseActFuture<Self, anyhow::Result<Vec<crate::files_discovery::FoundFile>>>;
+impl Handler<crate::msgs::ScanFiles> for DbActor {
+    type Result = ResponseActFuture<Self, anyhow::Result<Vec<crate::files_discovery::NewEpisodes>>>;
 
-    fn handle(
-        &mut self,
-        _msg: crate::msgs::DiscoverFiles,
-        _ctx: &mut Self::Context,
-    ) -> Self::Result {
+    fn handle(&mut self, msg: crate::msgs::ScanFiles, _ctx: &mut Self::Context) -> Self::Result {
+        let pool = self.pool.clone();
+        let scan_lock = self.scan_lock.clone();
         Box::pin(
-            self.pool
-                .acquire()
-                .then(|con| async move { crate::files_discovery::run_files_discovery(con?).await })
-                .into_actor(self),
+            async move {
+                let _scan_guard = scan_lock.lock().await;
+                let new_files =
+                    crate::files_discovery::run_files_discovery(pool.acquire().await?, msg.series)
+                        .await?;
+                crate::files_discovery::register_files(&mut pool.acquire().await?, new_files).await
+            }
+            .into_actor(self),
         )
     }
 }
 
-impl Handler<crate::msgs::FindAndRemoveDanglingFiles> for DbActor {
-    type Result = ResponseActFuture<Self, anyhow::Result<()>>;
+impl Handler<crate::msgs::FindDanglingFiles> for DbActor {
+    type Result =
+        ResponseActFuture<Self, anyhow::Result<Vec<crate::files_discovery::DanglingFile>>>;
 
     fn handle(
         &mut self,
-        _msg: crate::msgs::FindAndRemoveDanglingFiles,
+        _msg: crate::msgs::FindDanglingFiles,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
         Box::pin(
             self.pool
                 .acquire()
                 .then(|con| async move {
-                    let mut con = con?;
-                    let dangling_file_ids =
-                        crate::files_discovery::run_dangling_files_scan(&mut con).await?;
-                    let mut tx = con.begin().await?;
-                    for dangling_file_id in dangling_file_ids.iter() {
-                        sqlx::query("DELETE FROM episodes WHERE id == ?")
-                            .bind(dangling_file_id)
-                            .execute(tx.acquire().await?)
-                            .await?;
-                    }
-                    tx.commit().await?;
-                    Ok(())
+                    crate::files_discovery::run_dangling_files_scan(&mut con?).await
                 })
                 .into_actor(self),
         )
@@ -0,0 +1,184 @@
+use actix::prelude::*;
+use futures::stream::StreamExt;
+use hashbrown::HashSet;
+
+use crate::downloads::{DownloadCommand, DownloadProgress, DownloadRun, DownloadState};
+use crate::files_discovery::NewEpisodes;
+
+/// Runs the download commands of serieses, and scans their directories once they finish.
+#[derive(Default)]
+pub struct DownloadRunner {
+    running: HashSet<i64>,
+}
+
+impl Actor for DownloadRunner {
+    type Context = Context<Self>;
+}
+
+impl Supervised for DownloadRunner {}
+
+impl SystemService for DownloadRunner {}
+
+/// Run the command and log its run. The connection is only taken for logging, so that long
+/// downloads don't hold connections from the pool.
+async fn run_and_log(
+    download_command: &DownloadCommand,
+    timeout: std::time::Duration,
+) -> anyhow::Result<DownloadRun> {
+    let download_run = download_command.run(timeout).await;
+    log::info!(
+        "Download command of {:?} {}",
+        download_command.series_name,
+        download_run.status()
+    );
+    let mut con = crate::util::db::request_connection().await?;
+    crate::downloads::log_download_run(&mut con, &download_run).await?;
+    Ok(download_run)
+}
+
+async fn scan_and_register(series: Option<i64>) -> anyhow::Result<Vec<NewEpisodes>> {
+    crate::actors::DbActor::from_registry()
+        .send(crate::msgs::ScanFiles { series })
+        .await?
+}
+
+impl Handler<crate::msgs::RunDownloadCommand> for DownloadRunner {
+    type Result = ResponseActFuture<Self, anyhow::Result<(DownloadRun, Vec<NewEpisodes>)>>;
+
+    fn handle(
+        &mut self,
+        msg: crate::msgs::RunDownloadCommand,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let crate::msgs::RunDownloadCommand { series, timeout } = msg;
+        if !self.running.insert(series) {
+            return Box::pin(actix::fut::ready(Err(anyhow::anyhow!(
+                "The download command of series {} is already running",
+                series
+            ))));
+        }
+        Box::pin(
+            async move {
+                let download_command = {
+                    let mut con = crate::util::db::request_connection().await?;
+                    crate::downloads::series_download_command(&mut con, series).await?
+                };
+                let download_run = run_and_log(&download_command, timeout).await?;
+                let new_episodes = scan_and_register(Some(series)).await?;
+                Ok((download_run, new_episodes))
+            }
+            .into_actor(self)
+            .map(move |result, actor, _| {
+                actor.running.remove(&series);
+                result
+            }),
+        )
+    }
+}
+
+impl Handler<crate::msgs::RunAllDownloadCommands> for DownloadRunner {
+    type Result = ResponseActFuture<Self, anyhow::Result<Vec<NewEpisodes>>>;
+
+    fn handle(
+        &mut self,
+        msg: crate::msgs::RunAllDownloadCommands,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let crate::msgs::RunAllDownloadCommands {
+            media_type,
+            concurrency,
+            timeout,
+            progress,
+        } = msg;
+        Box::pin(
+            async move {
+                let mut con = crate::util::db::request_connection().await?;
+                crate::downloads::all_download_commands(&mut con, media_type).await
+            }
+            .into_actor(self)
+            .then(move |download_commands, actor, _| {
+                let download_commands = match download_commands {
+                    Ok(download_commands) => download_commands,
+                    Err(err) => {
+                        return futures::future::Either::Left(actix::fut::ready(Err(err)));
+                    }
+                };
+                let report = move |series: i64, series_name: &str, state| {
+                    // The receiver is allowed to stop listening.
+                    let _ = progress.send(DownloadProgress {
+                        series,
+                        series_name: series_name.to_owned(),
+                        state,
+                    });
+                };
+                let mut to_run = Vec::new();
+                for download_command in download_commands {
+                    match download_command {
+                        Ok(download_command) if actor.running.insert(download_command.series) => {
+                            report(
+                                download_command.series,
+                                &download_command.series_name,
+                                DownloadState::Queued,
+                            );
+                            to_run.push(download_command);
+                        }
+                        Ok(download_command) => {
+                            report(
+                                download_command.series,
+                                &download_command.series_name,
+                                DownloadState::Failed("already running".to_owned()),
+                            );
+                        }
+                        Err(err) => {
+                            report(
+                                err.series,
+                                &err.series_name,
+                                DownloadState::Failed(err.error.to_string()),
+                            );
+                        }
+                    }
+                }
+                let series_ids = to_run
+                    .iter()
+                    .map(|download_command| download_command.series)
+                    .collect::<Vec<_>>();
+                futures::future::Either::Right(
+                    async move {
+                        futures::stream::iter(to_run)
+                            .map(|download_command| {
+                                let report = &report;
+                                async move {
+                                    report(
+                                        download_command.series,
+                                        &download_command.series_name,
+                                        DownloadState::Running,
+                                    );
+                                    let state = match run_and_log(&download_command, timeout).await
+                                    {
+                                        Ok(download_run) => DownloadState::Finished(download_run),
+                                        Err(err) => DownloadState::Failed(err.to_string()),
+                                    };
+                                    report(
+                                        download_command.series,
+                                        &download_command.series_name,
+                                        state,
+                                    );
+                                }
+                            })
+                            .buffer_unordered(concurrency.max(1))
+                            .collect::<Vec<()>>()
+                            .await;
+                        scan_and_register(None).await
+                    }
+                    .into_actor(actor)
+                    .map(move |result, actor, _| {
+                        for series in series_ids {
+                            actor.running.remove(&series);
+                        }
+                        result
+                    }),
+                )
+            }),
+        )
+    }
+}
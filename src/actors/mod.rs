@@ -1,3 +1,5 @@
 mod db_actor;
+mod download_runner;
 
 pub use db_actor::DbActor;
+pub use download_runner::DownloadRunner;
@@ -0,0 +1,388 @@
+use core::cmp::Ordering;
+use core::fmt;
+use core::str::FromStr;
+
+use sqlx::sqlite::{Sqlite, SqliteRow};
+use sqlx::Row;
+
+/// A chapter number that may have a fractional part (`12.5`) and/or a letter suffix (`12a`).
+///
+/// In the database the whole and fractional parts are stored together in the `number` column
+/// (which makes it a `REAL` when there is a fraction) and the suffix in `number_suffix`, so
+/// `ORDER BY number, number_suffix` gives the same order as `Ord`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChapterNumber {
+    whole: i64,
+    /// The digits after the decimal point, without trailing zeros.
+    fraction: String,
+    suffix: String,
+}
+
+impl ChapterNumber {
+    /// Parse the chapter number at the start of `text`, ignoring whatever comes after it.
+    ///
+    /// The suffix is a single letter, and only if it is not followed by another letter or digit -
+    /// so that `12a.cbz` has a suffix but `12 end` and `12v2` don't.
+    pub fn parse_prefix(text: &str) -> Option<Self> {
+        Some(Self::parse_prefix_with_length(text)?.0)
+    }
+
+    fn parse_prefix_with_length(text: &str) -> Option<(Self, usize)> {
+        let whole_len = text
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len());
+        if whole_len == 0 {
+            return None;
+        }
+        let whole = text[..whole_len].parse().ok()?;
+        let mut rest = &text[whole_len..];
+
+        let mut fraction = "";
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            let fraction_len = after_dot
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(after_dot.len());
+            if 0 < fraction_len {
+                fraction = &after_dot[..fraction_len];
+                rest = &after_dot[fraction_len..];
+            }
+        }
+
+        let mut chars = rest.chars();
+        let suffix = match (chars.next(), chars.next()) {
+            (Some(c), next)
+                if c.is_ascii_alphabetic() && !next.is_some_and(|n| n.is_ascii_alphanumeric()) =>
+            {
+                rest = &rest[1..];
+                c.to_ascii_lowercase().to_string()
+            }
+            _ => String::new(),
+        };
+
+        let chapter_number = Self {
+            whole,
+            fraction: fraction.trim_end_matches('0').to_owned(),
+            suffix,
+        };
+        Some((chapter_number, text.len() - rest.len()))
+    }
+
+    /// The number without its fraction and suffix.
+    pub fn whole(&self) -> i64 {
+        self.whole
+    }
+
+    pub fn is_whole(&self) -> bool {
+        self.fraction.is_empty() && self.suffix.is_empty()
+    }
+
+    /// The value for the `number` column.
+    pub fn stored_number(&self) -> StoredNumber {
+        if self.fraction.is_empty() {
+            StoredNumber::Whole(self.whole)
+        } else {
+            StoredNumber::Fractional(
+                format!("{}.{}", self.whole, self.fraction)
+                    .parse()
+                    .expect("whole and fraction are digits"),
+            )
+        }
+    }
+
+    /// The value for the `number_suffix` column.
+    pub fn suffix(&self) -> &str {
+        &self.suffix
+    }
+
+    /// The whole part, zero-padded to `width` digits, followed by the rest of the number.
+    pub fn padded(&self, width: usize) -> String {
+        let mut result = format!("{:0width$}", self.whole, width = width);
+        if !self.fraction.is_empty() {
+            result.push('.');
+            result.push_str(&self.fraction);
+        }
+        result.push_str(&self.suffix);
+        result
+    }
+}
+
+/// Parse a chapter or a range of chapters, like `10` or `10-12`. The end is `None` for a single
+/// chapter.
+pub fn parse_range(text: &str) -> anyhow::Result<(ChapterNumber, Option<ChapterNumber>)> {
+    let (start, end) = if let Some((start, end)) = text.split_once('-') {
+        (start.parse::<ChapterNumber>()?, Some(end.parse()?))
+    } else {
+        (text.parse()?, None)
+    };
+    Ok((start.clone(), normalize_range_end(&start, end)?))
+}
+
+/// Drop the end of a single-chapter range, and reject ranges that end before they start.
+pub fn normalize_range_end(
+    start: &ChapterNumber,
+    end: Option<ChapterNumber>,
+) -> anyhow::Result<Option<ChapterNumber>> {
+    match end {
+        Some(end) if end < *start => Err(anyhow::anyhow!(
+            "Chapter range {}-{} ends before it starts",
+            start,
+            end
+        )),
+        Some(end) if end == *start => Ok(None),
+        end => Ok(end),
+    }
+}
+
+/// The inverse of [`parse_range`].
+pub fn format_range(start: &ChapterNumber, end: Option<&ChapterNumber>) -> String {
+    if let Some(end) = end {
+        format!("{}-{}", start, end)
+    } else {
+        start.to_string()
+    }
+}
+
+impl From<i64> for ChapterNumber {
+    fn from(whole: i64) -> Self {
+        Self {
+            whole,
+            fraction: String::new(),
+            suffix: String::new(),
+        }
+    }
+}
+
+impl FromStr for ChapterNumber {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        match Self::parse_prefix_with_length(text) {
+            Some((chapter_number, length)) if length == text.len() => Ok(chapter_number),
+            _ => Err(anyhow::anyhow!("{:?} is not a chapter number", text)),
+        }
+    }
+}
+
+impl fmt::Display for ChapterNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.padded(0))
+    }
+}
+
+impl PartialOrd for ChapterNumber {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ChapterNumber {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Comparing the fraction digits as strings works because they have no trailing zeros.
+        self.whole
+            .cmp(&other.whole)
+            .then_with(|| self.fraction.cmp(&other.fraction))
+            .then_with(|| self.suffix.cmp(&other.suffix))
+    }
+}
+
+impl<'r> sqlx::FromRow<'r, SqliteRow> for ChapterNumber {
+    fn from_row(row: &'r SqliteRow) -> sqlx::Result<Self> {
+        let number: StoredNumber = row.try_get("number")?;
+        let suffix: Option<String> = row.try_get("number_suffix")?;
+        let mut chapter_number = match number {
+            StoredNumber::Whole(whole) => Self::from(whole),
+            StoredNumber::Fractional(number) => Self::parse_prefix(&number.to_string())
+                .ok_or_else(|| {
+                    sqlx::Error::Decode(format!("Bad chapter number {}", number).into())
+                })?,
+        };
+        chapter_number.suffix = suffix.unwrap_or_default();
+        Ok(chapter_number)
+    }
+}
+
+// The end of a chapter range is stored as text in a single column, unlike the start which is split
+// into `number` and `number_suffix` for sorting.
+impl sqlx::Type<Sqlite> for ChapterNumber {
+    fn type_info() -> sqlx::sqlite::SqliteTypeInfo {
+        <String as sqlx::Type<Sqlite>>::type_info()
+    }
+}
+
+impl<'q> sqlx::Encode<'q, Sqlite> for ChapterNumber {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        sqlx::Encode::<Sqlite>::encode(self.to_string(), buf)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Sqlite> for ChapterNumber {
+    fn decode(value: sqlx::sqlite::SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(<&str as sqlx::Decode<Sqlite>>::decode(value)?.parse()?)
+    }
+}
+
+/// The numeric part of a chapter number, as stored in the `number` column.
+#[derive(Debug, Clone, Copy)]
+pub enum StoredNumber {
+    Whole(i64),
+    Fractional(f64),
+}
+
+impl sqlx::Type<Sqlite> for StoredNumber {
+    fn type_info() -> sqlx::sqlite::SqliteTypeInfo {
+        <f64 as sqlx::Type<Sqlite>>::type_info()
+    }
+
+    fn compatible(ty: &sqlx::sqlite::SqliteTypeInfo) -> bool {
+        <f64 as sqlx::Type<Sqlite>>::compatible(ty) || <i64 as sqlx::Type<Sqlite>>::compatible(ty)
+    }
+}
+
+impl<'q> sqlx::Encode<'q, Sqlite> for StoredNumber {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        match self {
+            StoredNumber::Whole(number) => sqlx::Encode::<Sqlite>::encode_by_ref(number, buf),
+            StoredNumber::Fractional(number) => sqlx::Encode::<Sqlite>::encode_by_ref(number, buf),
+        }
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Sqlite> for StoredNumber {
+    fn decode(value: sqlx::sqlite::SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let number = <f64 as sqlx::Decode<Sqlite>>::decode(value)?;
+        Ok(if number.fract() == 0.0 {
+            StoredNumber::Whole(number as i64)
+        } else {
+            StoredNumber::Fractional(number)
+        })
+    }
+}
+
+impl serde::Serialize for ChapterNumber {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.is_whole() {
+            serializer.serialize_i64(self.whole)
+        } else {
+            serializer.collect_str(self)
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for ChapterNumber {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = ChapterNumber;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a chapter number")
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<Self::Value, E> {
+                Ok(ChapterNumber::from(value))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<Self::Value, E> {
+                Ok(ChapterNumber::from(value as i64))
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, value: f64) -> Result<Self::Value, E> {
+                self.visit_str(&value.to_string())
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(text: &str) -> ChapterNumber {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(chapter("12"), ChapterNumber::from(12));
+        assert_eq!(chapter(" 12 ").to_string(), "12");
+        assert_eq!(chapter("12.5").to_string(), "12.5");
+        assert_eq!(chapter("12.50").to_string(), "12.5");
+        assert_eq!(chapter("12.0"), ChapterNumber::from(12));
+        assert_eq!(chapter("12A").to_string(), "12a");
+        assert_eq!(chapter("12.5b").to_string(), "12.5b");
+        assert!("".parse::<ChapterNumber>().is_err());
+        assert!("a12".parse::<ChapterNumber>().is_err());
+        assert!("12ab".parse::<ChapterNumber>().is_err());
+    }
+
+    #[test]
+    fn test_parse_prefix() {
+        assert_eq!(ChapterNumber::parse_prefix("12a.cbz"), Some(chapter("12a")));
+        assert_eq!(ChapterNumber::parse_prefix("12 end"), Some(chapter("12")));
+        assert_eq!(ChapterNumber::parse_prefix("12v2"), Some(chapter("12")));
+        assert_eq!(ChapterNumber::parse_prefix("12.cbz"), Some(chapter("12")));
+        assert_eq!(ChapterNumber::parse_prefix("end"), None);
+    }
+
+    #[test]
+    fn test_ordering() {
+        let sorted = [
+            "9", "10", "10a", "10b", "10.1", "10.15", "10.5", "10.5a", "11",
+        ];
+        let chapters = sorted.iter().map(|text| chapter(text)).collect::<Vec<_>>();
+        for pair in chapters.windows(2) {
+            assert!(
+                pair[0] < pair[1],
+                "{} should be before {}",
+                pair[0],
+                pair[1]
+            );
+        }
+        assert_eq!(chapter("10.50").cmp(&chapter("10.5")), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_padded() {
+        assert_eq!(chapter("7").padded(3), "007");
+        assert_eq!(chapter("7.5a").padded(3), "007.5a");
+        assert_eq!(chapter("1234").padded(3), "1234");
+    }
+
+    #[test]
+    fn test_stored_number() {
+        assert!(matches!(
+            chapter("12a").stored_number(),
+            StoredNumber::Whole(12)
+        ));
+        assert!(
+            matches!(chapter("12.5").stored_number(), StoredNumber::Fractional(number) if number == 12.5)
+        );
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let (start, end) = parse_range("10-12").unwrap();
+        assert_eq!((start, end), (chapter("10"), Some(chapter("12"))));
+        let (start, end) = parse_range("10-10").unwrap();
+        assert_eq!((start, end), (chapter("10"), None));
+        assert!(parse_range("12-10").is_err());
+        assert_eq!(
+            format_range(&chapter("10.5"), Some(&chapter("11a"))),
+            "10.5-11a"
+        );
+    }
+}
@@ -0,0 +1,454 @@
+use std::path::Path;
+
+use actix::prelude::*;
+use futures::TryStreamExt;
+use sqlx::prelude::*;
+use tokio::fs;
+
+use crate::models;
+use crate::util::db;
+
+#[derive(structopt::StructOpt, Debug)]
+pub enum CliCommand {
+    /// Scan the directories for new files and register them as episodes
+    Scan,
+    /// List episodes whose files no longer exist, and optionally remove them
+    CleanDangling {
+        /// Remove the listed episodes
+        #[structopt(long)]
+        delete: bool,
+        /// Remove the episodes even if their media type's base directory is missing
+        #[structopt(long, requires = "delete")]
+        force: bool,
+    },
+    /// Point an episode to a new file
+    Relink {
+        /// Id of the episode to relink
+        episode: i64,
+        /// The new file of the episode
+        file: String,
+    },
+    /// Refresh the links directory given by --linksdir and the links directories configured in the
+    /// database
+    RefreshLinks,
+    /// List all the serieses with their read stats
+    ListSeries,
+    /// List the unread episodes
+    ListUnread {
+        /// Only list episodes of the series with this id
+        #[structopt(long)]
+        series: Option<i64>,
+    },
+    /// Mark episodes as read
+    MarkRead {
+        /// Ids of the episodes to mark
+        #[structopt(required = true)]
+        episodes: Vec<i64>,
+    },
+    /// Report missing chapters, duplicate chapters and out-of-order volumes
+    Analyze {
+        /// Only analyze the series with this id
+        #[structopt(long)]
+        series: Option<i64>,
+    },
+    /// List episodes that were registered more than once, either for the same chapter or with
+    /// files of the same content
+    ListDuplicates,
+    /// Merge duplicate episodes into one, moving their reads to it
+    MergeDuplicates {
+        /// Id of the episode to keep
+        keep: i64,
+        /// Ids of the episodes to merge into it
+        #[structopt(required = true)]
+        redundant: Vec<i64>,
+        /// What to do with the files of the merged episodes
+        #[structopt(long, default_value = "keep", possible_values = &["keep", "trash", "delete"])]
+        files: String,
+    },
+    /// Read titles, page counts, durations and release dates from the files of the media types
+    /// that extract metadata
+    ExtractMetadata {
+        /// Only extract the episodes of the series with this id
+        #[structopt(long)]
+        series: Option<i64>,
+        /// Also extract episodes whose metadata was already extracted
+        #[structopt(long)]
+        all: bool,
+    },
+    /// Move the base directory of a media type, rewriting the paths of its episodes and
+    /// directories. Refuses if some of the episode files are not in the new location
+    Relocate {
+        /// Name of the media type
+        media_type: String,
+        /// The new base directory
+        new_base_dir: String,
+        /// Relocate even if some episode files are missing from the new base directory
+        #[structopt(long)]
+        force: bool,
+    },
+    /// Run the download commands of all the serieses, then scan for new files
+    DownloadAll {
+        /// Only run the download commands of serieses of the media type with this name
+        #[structopt(long)]
+        media_type: Option<String>,
+        /// How many download commands to run at once
+        #[structopt(long, default_value = "4")]
+        concurrency: usize,
+        /// Kill download commands that run for longer than this many seconds
+        #[structopt(long, default_value = "1800")]
+        timeout: u64,
+    },
+    /// Export the library
+    Export {
+        /// Either json for the whole library or csv for just the episodes
+        #[structopt(long, default_value = "json", possible_values = &["json", "csv"])]
+        format: String,
+        /// Write to this file instead of to the standard output
+        #[structopt(long)]
+        output: Option<String>,
+    },
+    /// Import an exported library, merging it into the current one
+    Import {
+        /// The exported file
+        file: String,
+        /// Either json for the whole library or csv for just the episodes
+        #[structopt(long, default_value = "json", possible_values = &["json", "csv"])]
+        format: String,
+        /// Replace the settings of existing media types, serieses and directories
+        #[structopt(long)]
+        overwrite_settings: bool,
+    },
+}
+
+pub fn run_cli_command(cli_args: &crate::CliArgs, command: &CliCommand) -> anyhow::Result<()> {
+    actix::System::new().block_on(async move {
+        let mut con = db::request_connection().await?;
+        let should_refresh_links = match command {
+            CliCommand::Scan => {
+                let new_episodes = crate::actors::DbActor::from_registry()
+                    .send(crate::msgs::ScanFiles { series: None })
+                    .await??;
+                println!(
+                    "Registered {} new files",
+                    new_episodes
+                        .iter()
+                        .map(|new_episodes| new_episodes.episodes.len())
+                        .sum::<usize>()
+                );
+                for new_episodes in new_episodes {
+                    println!("{}", new_episodes.describe());
+                }
+                true
+            }
+            CliCommand::CleanDangling { delete, force } => {
+                let dangling_files = crate::actors::DbActor::from_registry()
+                    .send(crate::msgs::FindDanglingFiles)
+                    .await??;
+                for dangling_file in dangling_files.iter() {
+                    println!(
+                        "{}\t{}\t{}\t{}\t{}",
+                        dangling_file.episode.id,
+                        dangling_file.series_name,
+                        dangling_file.episode.name,
+                        dangling_file.episode.file,
+                        dangling_file.status(),
+                    );
+                }
+                if *delete {
+                    if !force {
+                        crate::files_discovery::verify_safe_to_remove_dangling_files(
+                            &dangling_files,
+                        )?;
+                    }
+                    crate::files_discovery::remove_episodes(
+                        &mut con,
+                        dangling_files
+                            .iter()
+                            .map(|dangling_file| dangling_file.episode.id),
+                    )
+                    .await?;
+                }
+                *delete
+            }
+            CliCommand::Relink { episode, file } => {
+                crate::files_discovery::relink_episode(&mut con, *episode, file).await?;
+                true
+            }
+            CliCommand::RefreshLinks => true,
+            CliCommand::ListSeries => {
+                let rows: Vec<(i64, String, String, i32, i32)> = sqlx::query_as(
+                    r#"
+                    SELECT serieses.id
+                        , media_types.name
+                        , serieses.name
+                        , SUM(episodes.id IS NOT NULL AND date_of_read IS NULL) AS num_unread
+                        , COUNT(episodes.id) AS num_episodes
+                    FROM serieses
+                    INNER JOIN media_types ON serieses.media_type = media_types.id
+                    LEFT JOIN episodes ON serieses.id = episodes.series
+                    GROUP BY serieses.id
+                    ORDER BY serieses.name
+                    "#,
+                )
+                .fetch(con.acquire().await?)
+                .try_collect()
+                .await?;
+                for (id, media_type, name, num_unread, num_episodes) in rows {
+                    println!("{id}\t{media_type}\t{name}\t{num_unread}/{num_episodes}");
+                }
+                false
+            }
+            CliCommand::ListUnread { series } => {
+                let library_roots = crate::library_root::LibraryRoots::load(&mut con).await?;
+                let episodes: Vec<models::Episode> = sqlx::query_as(
+                    r#"
+                    SELECT * FROM episodes
+                    WHERE date_of_read IS NULL
+                    AND (? IS NULL OR series = ?)
+                    ORDER BY series, volume, number, number_suffix
+                    "#,
+                )
+                .bind(series)
+                .bind(series)
+                .fetch(con.acquire().await?)
+                .try_collect()
+                .await?;
+                for episode in episodes {
+                    println!(
+                        "{}\t{}\t{}",
+                        episode.id,
+                        episode.name,
+                        library_roots.resolve(episode.series, &episode.file)
+                    );
+                }
+                false
+            }
+            CliCommand::MarkRead { episodes } => {
+                let mut tx = con.begin().await?;
+                for episode_id in episodes {
+                    crate::read_events::mark_read(tx.acquire().await?, *episode_id, "cli").await?;
+                }
+                tx.commit().await?;
+                true
+            }
+            CliCommand::Analyze { series } => {
+                let serieses = if let Some(series_id) = series {
+                    let (series_name,): (String,) =
+                        sqlx::query_as("SELECT name FROM serieses WHERE id = ?")
+                            .bind(series_id)
+                            .fetch_optional(con.acquire().await?)
+                            .await?
+                            .ok_or_else(|| anyhow::anyhow!("No series with id {}", series_id))?;
+                    let analysis =
+                        crate::series_analysis::analyze_series(&mut con, *series_id).await?;
+                    vec![(*series_id, series_name, analysis)]
+                } else {
+                    crate::series_analysis::analyze_all_serieses(&mut con).await?
+                };
+                for (series_id, series_name, analysis) in serieses {
+                    println!("{}\t{}", series_id, series_name);
+                    if analysis.is_empty() {
+                        println!("\tNo problems found");
+                    }
+                    for problem in analysis.problem_descriptions() {
+                        println!("\t{}", problem);
+                    }
+                }
+                false
+            }
+            CliCommand::ListDuplicates => {
+                for group in crate::duplicates::find_duplicates(&mut con).await? {
+                    println!("{}:", group.reason.description());
+                    for duplicate in group.episodes {
+                        println!(
+                            "\t{}\t{}\t{}\t{}\t{}",
+                            duplicate.episode.id,
+                            duplicate.series_name,
+                            duplicate.episode.name,
+                            duplicate.episode.file,
+                            if duplicate.episode.date_of_read.is_some() {
+                                "read"
+                            } else {
+                                "unread"
+                            },
+                        );
+                    }
+                }
+                false
+            }
+            CliCommand::MergeDuplicates {
+                keep,
+                redundant,
+                files,
+            } => {
+                for (file, reason) in
+                    crate::duplicates::merge_duplicates(&mut con, *keep, redundant, files.parse()?)
+                        .await?
+                {
+                    eprintln!("{:?} is now ignored - {}", file, reason);
+                }
+                true
+            }
+            CliCommand::ExtractMetadata { series, all } => {
+                let num_extracted =
+                    crate::metadata::extract_pending_metadata(&mut con, *series, *all).await?;
+                println!("Extracted the metadata of {} episodes", num_extracted);
+                false
+            }
+            CliCommand::Relocate {
+                media_type,
+                new_base_dir,
+                force,
+            } => {
+                let (media_type_id,): (i64,) =
+                    sqlx::query_as("SELECT id FROM media_types WHERE name = ?")
+                        .bind(media_type)
+                        .fetch_optional(con.acquire().await?)
+                        .await?
+                        .ok_or_else(|| anyhow::anyhow!("No media type named {:?}", media_type))?;
+                let check =
+                    crate::library_root::check_relocation(&mut con, media_type_id, new_base_dir)
+                        .await?;
+                for missing in check.missing.iter() {
+                    eprintln!("Missing: {}", missing);
+                }
+                println!(
+                    "{} episode files found, {} missing",
+                    check.num_found,
+                    check.missing.len()
+                );
+                if !check.is_complete() && !force {
+                    anyhow::bail!(
+                        "Not relocating {:?} - some files are missing. Use --force to relocate anyway",
+                        media_type
+                    );
+                }
+                crate::library_root::relocate_base_dir(&mut con, media_type_id, new_base_dir)
+                    .await?;
+                true
+            }
+            CliCommand::DownloadAll {
+                media_type,
+                concurrency,
+                timeout,
+            } => {
+                let media_type_id = if let Some(media_type) = media_type {
+                    let (media_type_id,): (i64,) =
+                        sqlx::query_as("SELECT id FROM media_types WHERE name = ?")
+                            .bind(media_type)
+                            .fetch_optional(con.acquire().await?)
+                            .await?
+                            .ok_or_else(|| {
+                                anyhow::anyhow!("No media type named {:?}", media_type)
+                            })?;
+                    Some(media_type_id)
+                } else {
+                    None
+                };
+                let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+                let run_all = crate::actors::DownloadRunner::from_registry().send(
+                    crate::msgs::RunAllDownloadCommands {
+                        media_type: media_type_id,
+                        concurrency: *concurrency,
+                        timeout: std::time::Duration::from_secs(*timeout),
+                        progress: progress_tx,
+                    },
+                );
+                let print_progress = async {
+                    let mut num_succeeded = 0;
+                    let mut num_failed = 0;
+                    while let Some(progress) = progress_rx.recv().await {
+                        match &progress.state {
+                            crate::downloads::DownloadState::Queued => continue,
+                            crate::downloads::DownloadState::Running => {}
+                            crate::downloads::DownloadState::Finished(download_run)
+                                if download_run.succeeded() =>
+                            {
+                                num_succeeded += 1;
+                            }
+                            crate::downloads::DownloadState::Finished(_)
+                            | crate::downloads::DownloadState::Failed(_) => {
+                                num_failed += 1;
+                            }
+                        }
+                        println!("{}\t{}", progress.series_name, progress.state.describe());
+                    }
+                    (num_succeeded, num_failed)
+                };
+                let (result, (num_succeeded, num_failed)) =
+                    futures::future::join(run_all, print_progress).await;
+                for new_episodes in result?? {
+                    println!("{}", new_episodes.describe());
+                }
+                println!(
+                    "{} download commands succeeded, {} failed",
+                    num_succeeded, num_failed
+                );
+                true
+            }
+            CliCommand::Export { format, output } => {
+                let writer: Box<dyn std::io::Write> = if let Some(output) = output {
+                    Box::new(std::fs::File::create(output)?)
+                } else {
+                    Box::new(std::io::stdout())
+                };
+                match format.as_str() {
+                    "json" => {
+                        let library = crate::export_import::export_library(&mut con).await?;
+                        serde_json::to_writer_pretty(writer, &library)?;
+                    }
+                    "csv" => {
+                        crate::export_import::export_episodes_csv(&mut con, writer).await?;
+                    }
+                    _ => unreachable!("format is checked by structopt"),
+                }
+                false
+            }
+            CliCommand::Import {
+                file,
+                format,
+                overwrite_settings,
+            } => {
+                let reader = std::io::BufReader::new(std::fs::File::open(file)?);
+                let summary = match format.as_str() {
+                    "json" => {
+                        let library = serde_json::from_reader(reader)?;
+                        crate::export_import::import_library(&mut con, library, *overwrite_settings)
+                            .await?
+                    }
+                    "csv" => crate::export_import::import_episodes_csv(&mut con, reader).await?,
+                    _ => unreachable!("format is checked by structopt"),
+                };
+                println!("{}", summary);
+                true
+            }
+        };
+        if should_refresh_links {
+            if let Some(links_dir) = &cli_args.linksdir {
+                let links_dir = Path::new(links_dir);
+                fs::create_dir_all(links_dir).await?;
+                let report = crate::links_handling::refresh_links_directory(
+                    &mut con,
+                    links_dir,
+                    &Default::default(),
+                    cli_args.linksmode,
+                )
+                .await?;
+                if report.has_problems() {
+                    eprintln!("{}: {}", links_dir.display(), report);
+                }
+            }
+            for (links_directory, report) in
+                crate::links_handling::refresh_configured_links_directories(&mut con).await?
+            {
+                if report.has_problems() {
+                    eprintln!(
+                        "{} ({}): {}",
+                        links_directory.name, links_directory.dir, report
+                    );
+                }
+            }
+        }
+        Ok(())
+    })
+}
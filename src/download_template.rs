@@ -0,0 +1,217 @@
+//! Templates for the download commands of the serieses of a media type, like
+//! `gallery-dl --chapter-range {next_chapter}- {source}`. A series that has its own download command
+//! runs it instead.
+//!
+//! The placeholders are:
+//!
+//! * `{series}` - the name of the series.
+//! * `{source}` - the series' source, e.g. the URL to download it from.
+//! * `{last_chapter}` - the latest chapter of the series' episodes.
+//! * `{next_chapter}` - the chapter after the latest one, or `1` if the series has no episodes.
+//! * `{last_volume}` - the latest volume of the series' episodes.
+//! * `{dir}` - the directory of the series' first directory.
+//!
+//! `{last_chapter}`, `{next_chapter}` and `{last_volume}` can be zero-padded with a width, e.g.
+//! `{next_chapter:03}`. Use `{{` and `}}` for literal braces.
+//!
+//! Values are quoted for the shell when needed, so placeholders should not be quoted in the
+//! template. A template that uses a placeholder the series has no value for cannot be rendered.
+
+use core::str::FromStr;
+
+use crate::chapter_number::ChapterNumber;
+use crate::link_template::{parse_template_parts, TemplatePart, TemplatePlaceholder};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadTemplate {
+    parts: Vec<TemplatePart<Placeholder>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Placeholder {
+    Series,
+    Source,
+    LastChapter,
+    NextChapter,
+    LastVolume,
+    Dir,
+}
+
+impl TemplatePlaceholder for Placeholder {
+    fn can_pad(&self) -> bool {
+        matches!(
+            self,
+            Placeholder::LastChapter | Placeholder::NextChapter | Placeholder::LastVolume
+        )
+    }
+}
+
+impl Placeholder {
+    fn name(&self) -> &'static str {
+        match self {
+            Placeholder::Series => "series",
+            Placeholder::Source => "source",
+            Placeholder::LastChapter => "last_chapter",
+            Placeholder::NextChapter => "next_chapter",
+            Placeholder::LastVolume => "last_volume",
+            Placeholder::Dir => "dir",
+        }
+    }
+}
+
+impl FromStr for Placeholder {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Ok(match text {
+            "series" => Placeholder::Series,
+            "source" => Placeholder::Source,
+            "last_chapter" => Placeholder::LastChapter,
+            "next_chapter" => Placeholder::NextChapter,
+            "last_volume" => Placeholder::LastVolume,
+            "dir" => Placeholder::Dir,
+            _ => anyhow::bail!("Unknown placeholder {{{}}}", text),
+        })
+    }
+}
+
+/// The values for the placeholders of a single series.
+pub struct DownloadTemplateValues<'a> {
+    pub series: &'a str,
+    pub source: Option<&'a str>,
+    pub last_chapter: Option<&'a ChapterNumber>,
+    pub last_volume: Option<i64>,
+    pub dir: Option<&'a str>,
+}
+
+impl FromStr for DownloadTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let parts = parse_template_parts(text)?;
+        if parts.is_empty() {
+            anyhow::bail!("Download command template cannot be empty");
+        }
+        Ok(Self { parts })
+    }
+}
+
+impl DownloadTemplate {
+    /// The shell command to run.
+    pub fn render(&self, values: &DownloadTemplateValues) -> anyhow::Result<String> {
+        let mut result = String::new();
+        for part in self.parts.iter() {
+            match part {
+                TemplatePart::Literal(literal) => result.push_str(literal),
+                TemplatePart::Placeholder(placeholder, width) => {
+                    let width = width.unwrap_or(0);
+                    let value = match placeholder {
+                        Placeholder::Series => Some(values.series.to_owned()),
+                        Placeholder::Source => values
+                            .source
+                            .filter(|source| !source.is_empty())
+                            .map(|source| source.to_owned()),
+                        Placeholder::LastChapter => values
+                            .last_chapter
+                            .map(|last_chapter| last_chapter.padded(width)),
+                        Placeholder::NextChapter => Some(format!(
+                            "{:0width$}",
+                            values
+                                .last_chapter
+                                .map(|last_chapter| last_chapter.whole() + 1)
+                                .unwrap_or(1),
+                            width = width
+                        )),
+                        Placeholder::LastVolume => values
+                            .last_volume
+                            .map(|volume| format!("{:0width$}", volume, width = width)),
+                        Placeholder::Dir => values.dir.map(|dir| dir.to_owned()),
+                    };
+                    let value = value.ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "{:?} has no value for {{{}}}",
+                            values.series,
+                            placeholder.name()
+                        )
+                    })?;
+                    result.push_str(&shell_quote(&value));
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Quote the value as a single POSIX shell word, unless it is already one.
+fn shell_quote(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.,/:=@%+".contains(c))
+    {
+        value.to_owned()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, values: &DownloadTemplateValues) -> anyhow::Result<String> {
+        template.parse::<DownloadTemplate>()?.render(values)
+    }
+
+    #[test]
+    fn test_render() {
+        let last_chapter = "41.5".parse().unwrap();
+        let values = DownloadTemplateValues {
+            series: "Series",
+            source: Some("https://example.com/series?id=1&lang=en"),
+            last_chapter: Some(&last_chapter),
+            last_volume: Some(4),
+            dir: Some("/data/manga/Series"),
+        };
+        assert_eq!(
+            render(
+                "dl {{{series}}} --from {next_chapter:03} --after {last_chapter:03} --volume {last_volume} -d {dir} {source}",
+                &values
+            )
+            .unwrap(),
+            "dl {Series} --from 042 --after 041.5 --volume 4 -d /data/manga/Series 'https://example.com/series?id=1&lang=en'"
+        );
+    }
+
+    #[test]
+    fn test_missing_values() {
+        let values = DownloadTemplateValues {
+            series: "Series",
+            source: Some(""),
+            last_chapter: None,
+            last_volume: None,
+            dir: None,
+        };
+        assert_eq!(render("dl {next_chapter}", &values).unwrap(), "dl 1");
+        assert!(render("dl {source}", &values).is_err());
+        assert!(render("dl {last_chapter}", &values).is_err());
+        assert!(render("dl {last_volume}", &values).is_err());
+        assert!(render("dl {dir}", &values).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("".parse::<DownloadTemplate>().is_err());
+        assert!("dl {series:03}".parse::<DownloadTemplate>().is_err());
+        assert!("dl {volume}".parse::<DownloadTemplate>().is_err());
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("simple-value_1.2"), "simple-value_1.2");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("two words"), "'two words'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("$(rm -rf ~)"), "'$(rm -rf ~)'");
+    }
+}
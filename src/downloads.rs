@@ -0,0 +1,408 @@
+//! Running the download commands of serieses and keeping a log of their runs.
+
+use std::time::Duration;
+
+use futures::stream::StreamExt;
+use sqlx::prelude::*;
+use sqlx::types::chrono::{NaiveDateTime, Utc};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio_stream::wrappers::LinesStream;
+
+use crate::chapter_number::ChapterNumber;
+use crate::download_template::{DownloadTemplate, DownloadTemplateValues};
+
+/// How long a download command may run before it gets killed.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// How many download commands a batch runs at once, unless told otherwise.
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Only the end of longer outputs is kept.
+const MAX_OUTPUT_LEN: usize = 64 * 1024;
+
+/// Older runs are deleted from the log.
+const RUNS_KEPT_PER_SERIES: i64 = 10;
+
+/// A series' download command, as it will be run.
+#[derive(Debug, Clone)]
+pub struct DownloadCommand {
+    pub series: i64,
+    pub series_name: String,
+    pub command: String,
+    pub dir: Option<String>,
+}
+
+#[derive(PartialEq, Debug, Clone, sqlx::FromRow)]
+pub struct DownloadRun {
+    pub series: i64,
+    pub command: String,
+    pub date_started: NaiveDateTime,
+    pub date_finished: NaiveDateTime,
+    pub exit_code: Option<i64>,
+    pub timed_out: bool,
+    pub output: String,
+}
+
+impl DownloadRun {
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    pub fn status(&self) -> String {
+        if self.timed_out {
+            "timed out".to_owned()
+        } else {
+            match self.exit_code {
+                Some(0) => "succeeded".to_owned(),
+                Some(exit_code) => format!("failed with exit code {}", exit_code),
+                None => "failed".to_owned(),
+            }
+        }
+    }
+}
+
+/// Reported for each series while running a batch of download commands.
+#[derive(Debug, Clone)]
+pub struct DownloadProgress {
+    pub series: i64,
+    pub series_name: String,
+    pub state: DownloadState,
+}
+
+#[derive(Debug, Clone)]
+pub enum DownloadState {
+    Queued,
+    Running,
+    Finished(DownloadRun),
+    /// The command could not be run, or its run could not be logged.
+    Failed(String),
+}
+
+impl DownloadState {
+    pub fn describe(&self) -> String {
+        match self {
+            DownloadState::Queued => "queued".to_owned(),
+            DownloadState::Running => "running".to_owned(),
+            DownloadState::Finished(download_run) => download_run.status(),
+            DownloadState::Failed(err) => err.clone(),
+        }
+    }
+}
+
+/// A series that should have a download command, but its media type's template could not be
+/// rendered for it.
+#[derive(Debug)]
+pub struct DownloadCommandError {
+    pub series: i64,
+    pub series_name: String,
+    pub error: anyhow::Error,
+}
+
+pub async fn series_download_command(
+    con: &mut sqlx::SqliteConnection,
+    series_id: i64,
+) -> anyhow::Result<DownloadCommand> {
+    match load_download_commands(con, Some(series_id), None)
+        .await?
+        .pop()
+    {
+        Some(Ok(download_command)) => Ok(download_command),
+        Some(Err(err)) => Err(err.error),
+        None => Err(anyhow::anyhow!(
+            "Series {} has no download command",
+            series_id
+        )),
+    }
+}
+
+/// The download commands of all the serieses, or just the serieses of one media type.
+pub async fn all_download_commands(
+    con: &mut sqlx::SqliteConnection,
+    media_type: Option<i64>,
+) -> anyhow::Result<Vec<Result<DownloadCommand, DownloadCommandError>>> {
+    load_download_commands(con, None, media_type).await
+}
+
+#[derive(sqlx::FromRow)]
+struct DownloadSettings {
+    series: i64,
+    series_name: String,
+    download_command: Option<String>,
+    download_command_dir: Option<String>,
+    source: Option<String>,
+    download_command_template: String,
+    base_dir: String,
+    first_dir: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct EpisodeNumbers {
+    #[sqlx(flatten)]
+    number: ChapterNumber,
+    number_end: Option<ChapterNumber>,
+    volume: Option<i64>,
+}
+
+/// A series runs its own download command if it has one. Otherwise, if it has a source, it runs its
+/// media type's download command template.
+async fn load_download_commands(
+    con: &mut sqlx::SqliteConnection,
+    series: Option<i64>,
+    media_type: Option<i64>,
+) -> anyhow::Result<Vec<Result<DownloadCommand, DownloadCommandError>>> {
+    let serieses: Vec<DownloadSettings> = sqlx::query_as(
+        r#"
+        SELECT serieses.id AS series
+            , serieses.name AS series_name
+            , serieses.download_command
+            , serieses.download_command_dir
+            , serieses.source
+            , media_types.download_command_template
+            , media_types.base_dir
+            , (
+                SELECT directories.dir FROM directories
+                WHERE directories.series = serieses.id
+                ORDER BY directories.id
+                LIMIT 1
+            ) AS first_dir
+        FROM serieses
+        INNER JOIN media_types ON media_types.id = serieses.media_type
+        WHERE (
+                (serieses.download_command IS NOT NULL AND serieses.download_command != '')
+                OR (serieses.source IS NOT NULL AND serieses.source != '' AND media_types.download_command_template != '')
+            )
+            AND (?1 IS NULL OR serieses.id = ?1)
+            AND (?2 IS NULL OR serieses.media_type = ?2)
+        ORDER BY serieses.name
+        "#,
+    )
+    .bind(series)
+    .bind(media_type)
+    .fetch_all(con.acquire().await?)
+    .await?;
+
+    let mut result = Vec::with_capacity(serieses.len());
+    for settings in serieses {
+        let command = match settings.download_command.as_deref() {
+            Some(download_command) if !download_command.is_empty() => {
+                Ok(download_command.to_owned())
+            }
+            _ => render_download_template(con, &settings).await,
+        };
+        result.push(match command {
+            Ok(command) => Ok(DownloadCommand {
+                series: settings.series,
+                series_name: settings.series_name,
+                command,
+                dir: settings.download_command_dir,
+            }),
+            Err(error) => Err(DownloadCommandError {
+                series: settings.series,
+                series_name: settings.series_name,
+                error,
+            }),
+        });
+    }
+    Ok(result)
+}
+
+async fn render_download_template(
+    con: &mut sqlx::SqliteConnection,
+    settings: &DownloadSettings,
+) -> anyhow::Result<String> {
+    let template: DownloadTemplate = settings.download_command_template.parse()?;
+    let episodes: Vec<EpisodeNumbers> = sqlx::query_as(
+        "SELECT number, number_suffix, number_end, volume FROM episodes WHERE series = ?",
+    )
+    .bind(settings.series)
+    .fetch_all(con.acquire().await?)
+    .await?;
+    let last_chapter = episodes
+        .iter()
+        .map(|episode| episode.number_end.as_ref().unwrap_or(&episode.number))
+        .max();
+    let last_volume = episodes.iter().filter_map(|episode| episode.volume).max();
+    let dir = settings
+        .first_dir
+        .as_ref()
+        .map(|dir| crate::library_root::resolve(&settings.base_dir, dir));
+    template.render(&DownloadTemplateValues {
+        series: &settings.series_name,
+        source: settings.source.as_deref(),
+        last_chapter,
+        last_volume,
+        dir: dir.as_deref(),
+    })
+}
+
+impl DownloadCommand {
+    /// Run the command in its download directory.
+    ///
+    /// Failures of the command itself are not errors - they are reported in the returned run.
+    pub async fn run(&self, timeout: Duration) -> DownloadRun {
+        let date_started = Utc::now().naive_utc();
+        let mut output = String::new();
+        let (exit_code, timed_out) = match run_command(
+            &self.command,
+            self.dir.as_deref().filter(|dir| !dir.is_empty()),
+            timeout,
+            &mut output,
+        )
+        .await
+        {
+            Ok(exit_code) => (exit_code, false),
+            Err(RunCommandError::TimedOut) => (None, true),
+            Err(RunCommandError::Io(err)) => {
+                append_output_line(&mut output, &format!("Failed to run the command: {}", err));
+                (None, false)
+            }
+        };
+        DownloadRun {
+            series: self.series,
+            command: self.command.clone(),
+            date_started,
+            date_finished: Utc::now().naive_utc(),
+            exit_code,
+            timed_out,
+            output,
+        }
+    }
+}
+
+/// Add the run to the series' download log, dropping its oldest runs.
+pub async fn log_download_run(
+    con: &mut sqlx::SqliteConnection,
+    download_run: &DownloadRun,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO download_runs(series, command, date_started, date_finished, exit_code, timed_out, output)
+        VALUES(?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(download_run.series)
+    .bind(&download_run.command)
+    .bind(download_run.date_started)
+    .bind(download_run.date_finished)
+    .bind(download_run.exit_code)
+    .bind(download_run.timed_out)
+    .bind(&download_run.output)
+    .execute(con.acquire().await?)
+    .await?;
+    sqlx::query(
+        r#"
+        DELETE FROM download_runs
+        WHERE series = ?1 AND id NOT IN (
+            SELECT id FROM download_runs WHERE series = ?1 ORDER BY id DESC LIMIT ?2
+        )
+        "#,
+    )
+    .bind(download_run.series)
+    .bind(RUNS_KEPT_PER_SERIES)
+    .execute(con.acquire().await?)
+    .await?;
+    Ok(())
+}
+
+/// The last logged run of the series' download command.
+pub async fn latest_download_run(
+    con: &mut sqlx::SqliteConnection,
+    series_id: i64,
+) -> anyhow::Result<Option<DownloadRun>> {
+    Ok(sqlx::query_as(
+        r#"
+        SELECT series, command, date_started, date_finished, exit_code, timed_out, output
+        FROM download_runs
+        WHERE series = ?
+        ORDER BY id DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(series_id)
+    .fetch_optional(con.acquire().await?)
+    .await?)
+}
+
+enum RunCommandError {
+    TimedOut,
+    Io(std::io::Error),
+}
+
+/// Run the shell command, collecting its stdout and stderr lines into `output` in the order they
+/// arrive, and return its exit code.
+async fn run_command(
+    command: &str,
+    dir: Option<&str>,
+    timeout: Duration,
+    output: &mut String,
+) -> Result<Option<i64>, RunCommandError> {
+    let mut cmd = if cfg!(target_os = "windows") {
+        let mut cmd = std::process::Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = std::process::Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    // In its own process group, so that the timeout can kill whatever the shell started.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+    let mut cmd = tokio::process::Command::from(cmd);
+    cmd.arg(command);
+    if let Some(dir) = dir {
+        cmd.current_dir(dir);
+    }
+    cmd.stdin(std::process::Stdio::null());
+    cmd.stdout(std::process::Stdio::piped());
+    cmd.stderr(std::process::Stdio::piped());
+    // Dropping the child when the timeout is reached kills it.
+    cmd.kill_on_drop(true);
+
+    let mut child = cmd.spawn().map_err(RunCommandError::Io)?;
+    #[cfg(unix)]
+    let pid = child.id();
+    let stdout = LinesStream::new(BufReader::new(child.stdout.take().unwrap()).lines());
+    let stderr = LinesStream::new(BufReader::new(child.stderr.take().unwrap()).lines());
+    let mut lines = futures::stream::select(stdout, stderr);
+
+    let run = async {
+        while let Some(line) = lines.next().await {
+            match line {
+                Ok(line) => append_output_line(output, &line),
+                Err(err) => log::warn!("Cannot read output of {:?}: {}", command, err),
+            }
+        }
+        child.wait().await
+    };
+    match tokio::time::timeout(timeout, run).await {
+        Ok(Ok(status)) => Ok(status.code().map(i64::from)),
+        Ok(Err(err)) => Err(RunCommandError::Io(err)),
+        Err(_) => {
+            #[cfg(unix)]
+            if let Some(pid) = pid {
+                // SAFETY: killpg has no memory safety requirements.
+                if unsafe { libc::killpg(pid as libc::pid_t, libc::SIGKILL) } != 0 {
+                    log::warn!(
+                        "Cannot kill the processes of {:?}: {}",
+                        command,
+                        std::io::Error::last_os_error()
+                    );
+                }
+            }
+            Err(RunCommandError::TimedOut)
+        }
+    }
+}
+
+fn append_output_line(output: &mut String, line: &str) {
+    output.push_str(line);
+    output.push('\n');
+    if output.len() > 2 * MAX_OUTPUT_LEN {
+        let mut cut = output.len() - MAX_OUTPUT_LEN;
+        while !output.is_char_boundary(cut) {
+            cut += 1;
+        }
+        output.replace_range(..cut, "…");
+    }
+}
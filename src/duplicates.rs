@@ -0,0 +1,362 @@
+//! Finding episodes that were registered more than once - either as the same chapter of the same
+//! series, or as different files with the same content - and merging them into a single episode.
+
+use std::str::FromStr;
+
+use futures::TryStreamExt;
+use hashbrown::{HashMap, HashSet};
+use sha2::Digest;
+use sqlx::prelude::*;
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+
+use crate::chapter_number::ChapterNumber;
+use crate::models;
+use crate::util::db::FromRowWithExtra;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DuplicateReason {
+    /// The episodes have the same series, volume and number.
+    SameChapter,
+    /// The files of the episodes have the same content.
+    SameContent,
+}
+
+impl DuplicateReason {
+    pub fn description(&self) -> &'static str {
+        match self {
+            DuplicateReason::SameChapter => "same chapter",
+            DuplicateReason::SameContent => "same content",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DuplicateGroup {
+    pub reason: DuplicateReason,
+    pub episodes: Vec<DuplicateEpisode>,
+}
+
+#[derive(Debug)]
+pub struct DuplicateEpisode {
+    pub episode: models::Episode,
+    pub series_name: String,
+    pub num_reads: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct DuplicateEpisodeExtra {
+    series_name: String,
+    num_reads: i64,
+    base_dir: String,
+}
+
+pub async fn find_duplicates(
+    con: &mut sqlx::SqliteConnection,
+) -> anyhow::Result<Vec<DuplicateGroup>> {
+    let episodes: Vec<FromRowWithExtra<models::Episode, DuplicateEpisodeExtra>> = sqlx::query_as(
+        r#"
+        SELECT episodes.*
+            , serieses.name AS series_name
+            , (SELECT COUNT(*) FROM read_events WHERE read_events.episode = episodes.id) AS num_reads
+            , media_types.base_dir
+        FROM episodes
+        INNER JOIN serieses ON serieses.id = episodes.series
+        INNER JOIN media_types ON media_types.id = serieses.media_type
+        ORDER BY serieses.name, episodes.volume, episodes.number, episodes.number_suffix, episodes.id
+        "#,
+    )
+    .fetch(con.acquire().await?)
+    .map_ok(
+        |mut episode: FromRowWithExtra<models::Episode, DuplicateEpisodeExtra>| {
+            episode.data.file =
+                crate::library_root::resolve(&episode.extra.base_dir, &episode.data.file);
+            episode
+        },
+    )
+    .try_collect()
+    .await?;
+
+    // Groups are lists of indices into `episodes`, so that they can be sorted by their first
+    // episode and so that an episode can be in both kinds of groups.
+    let mut groups = Vec::<(DuplicateReason, Vec<usize>)>::new();
+
+    let mut by_chapter = HashMap::<(i64, Option<i64>, &ChapterNumber), Vec<usize>>::new();
+    for (index, episode) in episodes.iter().enumerate() {
+        let episode = &episode.data;
+        by_chapter
+            .entry((episode.series, episode.volume, &episode.number))
+            .or_default()
+            .push(index);
+    }
+    groups.extend(
+        by_chapter
+            .into_values()
+            .filter(|indices| 1 < indices.len())
+            .map(|indices| (DuplicateReason::SameChapter, indices)),
+    );
+
+    // Only files that have the same size as another file can have the same content, so there is
+    // no need to hash the others.
+    let mut by_size = HashMap::<i64, Vec<usize>>::new();
+    for (index, episode) in episodes.iter().enumerate() {
+        let file_size = if let Some(file_size) = episode.data.file_size {
+            file_size
+        } else if let Ok(metadata) = fs::metadata(&episode.data.file).await {
+            metadata.len() as i64
+        } else {
+            continue;
+        };
+        by_size.entry(file_size).or_default().push(index);
+    }
+    // Only episodes of the same series can be merged, even if their files have the same content.
+    let mut by_hash = HashMap::<(i64, Vec<u8>), Vec<usize>>::new();
+    for indices in by_size.into_values().filter(|indices| 1 < indices.len()) {
+        for index in indices {
+            let episode = &episodes[index].data;
+            match hash_file(&episode.file).await {
+                Ok(hash) => {
+                    // Kept for recognizing the file if it is moved.
+                    sqlx::query("UPDATE episodes SET content_hash = ? WHERE id = ?")
+                        .bind(&hash)
+                        .bind(episode.id)
+                        .execute(con.acquire().await?)
+                        .await?;
+                    by_hash
+                        .entry((episode.series, hash))
+                        .or_default()
+                        .push(index);
+                }
+                Err(err) => log::warn!("Cannot hash {:?}: {}", episode.file, err),
+            }
+        }
+    }
+    groups.extend(
+        by_hash
+            .into_values()
+            .filter(|indices| {
+                // If they are all the same chapter, they are already in a same chapter group.
+                let chapters: HashSet<_> = indices
+                    .iter()
+                    .map(|&index| {
+                        let episode = &episodes[index].data;
+                        (episode.series, episode.volume, &episode.number)
+                    })
+                    .collect();
+                1 < chapters.len()
+            })
+            .map(|indices| (DuplicateReason::SameContent, indices)),
+    );
+
+    groups.sort_by_key(|(_, indices)| indices[0]);
+    let mut episodes: Vec<Option<DuplicateEpisode>> = episodes
+        .into_iter()
+        .map(|FromRowWithExtra { data, extra }| {
+            Some(DuplicateEpisode {
+                episode: data,
+                series_name: extra.series_name,
+                num_reads: extra.num_reads,
+            })
+        })
+        .collect();
+    Ok(groups
+        .into_iter()
+        .map(|(reason, indices)| DuplicateGroup {
+            reason,
+            episodes: indices
+                .into_iter()
+                .filter_map(|index| episodes[index].take())
+                .collect(),
+        })
+        // An episode that is in two groups only appears in the first one, which may leave the
+        // second one with a single episode.
+        .filter(|group| 1 < group.episodes.len())
+        .collect())
+}
+
+/// The episode, with its file resolved.
+async fn load_episode(
+    con: &mut crate::SqlitePoolConnection,
+    episode_id: i64,
+) -> anyhow::Result<models::Episode> {
+    let mut episode: models::Episode = sqlx::query_as("SELECT * FROM episodes WHERE id = ?")
+        .bind(episode_id)
+        .fetch_optional(con.acquire().await?)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("No episode with id={}", episode_id))?;
+    let base_dir = crate::library_root::series_base_dir(con, episode.series).await?;
+    episode.file = crate::library_root::resolve(&base_dir, &episode.file);
+    Ok(episode)
+}
+
+/// The SHA-256 of the file's content.
+pub async fn hash_file(path: &str) -> anyhow::Result<Vec<u8>> {
+    let mut file = fs::File::open(path).await?;
+    let mut hasher = sha2::Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let num_read = file.read(&mut buffer).await?;
+        if num_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..num_read]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// What to do with the files of the redundant episodes when merging duplicates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedundantFileAction {
+    /// Leave the files on disk, but don't register them again when scanning.
+    Keep,
+    /// Move the files to the trash.
+    Trash,
+    /// Delete the files permanently.
+    Delete,
+}
+
+impl FromStr for RedundantFileAction {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Ok(match text {
+            "keep" => RedundantFileAction::Keep,
+            "trash" => RedundantFileAction::Trash,
+            "delete" => RedundantFileAction::Delete,
+            _ => anyhow::bail!("Unknown redundant file action {:?}", text),
+        })
+    }
+}
+
+/// Merge the redundant episodes into the kept one: their reads are moved to it, it is considered
+/// read if any of them was, and they are removed. Their files are then handled according to
+/// `file_action`.
+///
+/// All the episodes must be of the same series, and each redundant episode must be the same
+/// chapter as the kept one or have the same content. Nothing is changed if any of them isn't.
+///
+/// Files that cannot be trashed or deleted are ignored instead, so that scans won't register them
+/// again, and are returned with the reason.
+pub async fn merge_duplicates(
+    con: &mut crate::SqlitePoolConnection,
+    keep_id: i64,
+    redundant_ids: &[i64],
+    file_action: RedundantFileAction,
+) -> anyhow::Result<Vec<(String, String)>> {
+    if redundant_ids.contains(&keep_id) {
+        anyhow::bail!("Cannot merge episode {} into itself", keep_id);
+    }
+    let keep = load_episode(con, keep_id).await?;
+    let mut keep_hash = keep.content_hash.clone();
+    for &redundant_id in redundant_ids {
+        let redundant = load_episode(con, redundant_id).await?;
+        if redundant.series != keep.series {
+            anyhow::bail!(
+                "Cannot merge episode {} into episode {} of another series",
+                redundant_id,
+                keep_id
+            );
+        }
+        if (redundant.volume, &redundant.number) == (keep.volume, &keep.number) {
+            continue;
+        }
+        let hash_episode_file = |episode: &models::Episode| {
+            let file = episode.file.clone();
+            async move {
+                hash_file(&file)
+                    .await
+                    .map_err(|err| anyhow::anyhow!("Cannot hash {:?}: {}", file, err))
+            }
+        };
+        if keep_hash.is_none() {
+            keep_hash = Some(hash_episode_file(&keep).await?);
+        }
+        let redundant_hash = match &redundant.content_hash {
+            Some(hash) => hash.clone(),
+            None => hash_episode_file(&redundant).await?,
+        };
+        if keep_hash.as_ref() != Some(&redundant_hash) {
+            anyhow::bail!(
+                "Cannot merge episode {} into episode {} - not the same chapter or content",
+                redundant_id,
+                keep_id
+            );
+        }
+    }
+
+    let mut tx = con.begin().await?;
+    let mut redundant_files = Vec::new();
+    for &redundant_id in redundant_ids {
+        let (base_dir, file): (String, String) = sqlx::query_as(
+            r#"
+            SELECT media_types.base_dir, episodes.file
+            FROM episodes
+            INNER JOIN serieses ON serieses.id = episodes.series
+            INNER JOIN media_types ON media_types.id = serieses.media_type
+            WHERE episodes.id = ?
+            "#,
+        )
+        .bind(redundant_id)
+        .fetch_optional(tx.acquire().await?)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("No episode with id={}", redundant_id))?;
+        // Ignored files are not tied to a media type, so they are kept as full paths
+        let file = crate::library_root::resolve(&base_dir, &file);
+        let query_result = sqlx::query(
+            r#"
+            UPDATE episodes
+            SET date_of_read = (SELECT max(date_of_read) FROM episodes WHERE id IN (?, ?))
+            WHERE id = ?
+            "#,
+        )
+        .bind(keep_id)
+        .bind(redundant_id)
+        .bind(keep_id)
+        .execute(tx.acquire().await?)
+        .await?;
+        if query_result.rows_affected() == 0 {
+            anyhow::bail!("No episode with id={}", keep_id);
+        }
+        sqlx::query("UPDATE read_events SET episode = ? WHERE episode = ?")
+            .bind(keep_id)
+            .bind(redundant_id)
+            .execute(tx.acquire().await?)
+            .await?;
+        sqlx::query("DELETE FROM episodes WHERE id = ?")
+            .bind(redundant_id)
+            .execute(tx.acquire().await?)
+            .await?;
+        if file_action == RedundantFileAction::Keep {
+            sqlx::query("INSERT OR IGNORE INTO ignored_files(file) VALUES(?)")
+                .bind(&file)
+                .execute(tx.acquire().await?)
+                .await?;
+        }
+        redundant_files.push(file);
+    }
+    tx.commit().await?;
+
+    let mut failures = Vec::new();
+    for file in redundant_files {
+        let result = match file_action {
+            RedundantFileAction::Keep => Ok(()),
+            RedundantFileAction::Trash => {
+                use gio::prelude::FileExt;
+                gio::File::for_path(&file)
+                    .trash(gio::Cancellable::NONE)
+                    .map_err(|err| format!("cannot trash: {}", err))
+            }
+            RedundantFileAction::Delete => fs::remove_file(&file)
+                .await
+                .map_err(|err| format!("cannot delete: {}", err)),
+        };
+        if let Err(reason) = result {
+            sqlx::query("INSERT OR IGNORE INTO ignored_files(file) VALUES(?)")
+                .bind(&file)
+                .execute(con.acquire().await?)
+                .await?;
+            failures.push((file, reason));
+        }
+    }
+    Ok(failures)
+}
@@ -0,0 +1,838 @@
+//! Export and import of the library, for moving it between machines, sharing pattern setups and
+//! diffing libraries.
+//!
+//! The JSON format refers to everything by name rather than by database id:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "media_types": [
+//!     {
+//!       "name": "Manga",
+//!       "base_dir": "/home/me/manga",
+//!       "file_types": "cbz",
+//!       "adjacent_file_types": "",
+//!       "program": "mcomix",
+//!       "maintain_symlinks": false,
+//!       "mark_read_on_exit": false,
+//!       "link_template": "",
+//!       "extract_metadata": false,
+//!       "download_command_template": "",
+//!       "serieses": [
+//!         {
+//!           "name": "Some Manga",
+//!           "download_command_dir": null,
+//!           "download_command": null,
+//!           "source": null,
+//!           "directories": [
+//!             { "pattern": "c(?P<c>\\d+)", "dir": "/home/me/manga/some", "volume": null, "recursive": false }
+//!           ],
+//!           "episodes": [
+//!             {
+//!               "volume": null,
+//!               "number": 1,
+//!               "number_end": null,
+//!               "name": "Some Manga c1",
+//!               "file": "/home/me/manga/some/c1.cbz",
+//!               "file_size": 1234,
+//!               "date_of_read": "2023-08-16 13:32:26",
+//!               "reads": [
+//!                 { "timestamp": "2023-08-16 13:32:26", "note": null, "source": "gui" }
+//!               ]
+//!             }
+//!           ]
+//!         }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Episode numbers are integers for whole chapters, and strings for chapters with a fraction or a
+//! suffix (`"12.5"`, `"12a"`). `number_end` is the last chapter of episodes that cover a range of
+//! chapters, and `null` otherwise.
+//!
+//! Timestamps are in the `YYYY-MM-DD HH:MM:SS` format. `date_of_read` is `null` for unread
+//! episodes, even if they have reads.
+//!
+//! The CSV format only covers episodes, with the columns `media_type`, `series`, `volume`,
+//! `number`, `number_end`, `name`, `file`, `file_size`, `date_of_read` and `num_reads`.
+//! `num_reads` is informative, and is ignored on import.
+//!
+//! Paths are always exported in full. On import, paths inside the base directory of their media
+//! type are stored relative to it, like the paths of scanned files.
+//!
+//! Importing merges into the existing library:
+//!
+//! * Media types are matched by name, and serieses by media type and name. When
+//!   `overwrite_settings` is set the settings of existing ones are replaced with the imported ones.
+//! * Directories are matched by series, dir and pattern.
+//! * Episodes are matched by series, volume and number. Reads that the episode does not already
+//!   have are added, and the episode is considered read if it is read in either library.
+//! * An episode whose file is already registered in its series under a different volume or number
+//!   is skipped, and its reads are merged into the registered episode.
+
+use futures::TryStreamExt;
+use hashbrown::HashMap;
+use sqlx::prelude::*;
+use sqlx::types::chrono::NaiveDateTime;
+
+use crate::chapter_number::ChapterNumber;
+use crate::library_root::LibraryRoots;
+use crate::models;
+use crate::util::db::FromRowWithExtra;
+
+pub const FORMAT_VERSION: u32 = 1;
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Library {
+    pub version: u32,
+    pub media_types: Vec<ExportedMediaType>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ExportedMediaType {
+    pub name: String,
+    pub base_dir: String,
+    pub file_types: String,
+    #[serde(default)]
+    pub adjacent_file_types: String,
+    pub program: String,
+    #[serde(default)]
+    pub maintain_symlinks: bool,
+    #[serde(default)]
+    pub mark_read_on_exit: bool,
+    #[serde(default)]
+    pub link_template: String,
+    #[serde(default)]
+    pub extract_metadata: bool,
+    #[serde(default)]
+    pub download_command_template: String,
+    #[serde(default)]
+    pub serieses: Vec<ExportedSeries>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ExportedSeries {
+    pub name: String,
+    #[serde(default)]
+    pub download_command_dir: Option<String>,
+    #[serde(default)]
+    pub download_command: Option<String>,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub directories: Vec<ExportedDirectory>,
+    #[serde(default)]
+    pub episodes: Vec<ExportedEpisode>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ExportedDirectory {
+    pub pattern: String,
+    pub dir: String,
+    #[serde(default)]
+    pub volume: Option<i32>,
+    #[serde(default)]
+    pub recursive: bool,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ExportedEpisode {
+    #[serde(default)]
+    pub volume: Option<i64>,
+    pub number: ChapterNumber,
+    #[serde(default)]
+    pub number_end: Option<ChapterNumber>,
+    pub name: String,
+    pub file: String,
+    #[serde(default)]
+    pub file_size: Option<i64>,
+    #[serde(default)]
+    pub date_of_read: Option<String>,
+    #[serde(default)]
+    pub reads: Vec<ExportedRead>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ExportedRead {
+    pub timestamp: String,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct CsvEpisode {
+    media_type: String,
+    series: String,
+    volume: Option<i64>,
+    number: ChapterNumber,
+    number_end: Option<ChapterNumber>,
+    name: String,
+    file: String,
+    file_size: Option<i64>,
+    date_of_read: Option<String>,
+    #[serde(default)]
+    num_reads: i64,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub media_types_added: usize,
+    pub serieses_added: usize,
+    pub directories_added: usize,
+    pub episodes_added: usize,
+    /// Episodes whose file was already registered as another episode of the series.
+    pub episodes_skipped: usize,
+    pub reads_added: usize,
+}
+
+impl core::fmt::Display for ImportSummary {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Added {} media types, {} serieses, {} directories, {} episodes and {} reads",
+            self.media_types_added,
+            self.serieses_added,
+            self.directories_added,
+            self.episodes_added,
+            self.reads_added,
+        )?;
+        if 0 < self.episodes_skipped {
+            write!(
+                f,
+                " (skipped {} episodes whose files are already registered)",
+                self.episodes_skipped
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ReadEvent {
+    episode: i64,
+    timestamp: NaiveDateTime,
+    note: Option<String>,
+    source: Option<String>,
+}
+
+fn format_timestamp(timestamp: &NaiveDateTime) -> String {
+    timestamp.format(TIMESTAMP_FORMAT).to_string()
+}
+
+fn parse_timestamp(timestamp: &str) -> anyhow::Result<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)
+        .map_err(|err| anyhow::anyhow!("Bad timestamp {:?}: {}", timestamp, err))
+}
+
+pub async fn export_library(con: &mut sqlx::SqliteConnection) -> anyhow::Result<Library> {
+    let mut reads = HashMap::<i64, Vec<ExportedRead>>::new();
+    sqlx::query_as::<_, ReadEvent>(
+        "SELECT episode, timestamp, note, source FROM read_events ORDER BY timestamp",
+    )
+    .fetch(con.acquire().await?)
+    .try_for_each(|read_event| {
+        reads
+            .entry(read_event.episode)
+            .or_default()
+            .push(ExportedRead {
+                timestamp: format_timestamp(&read_event.timestamp),
+                note: read_event.note,
+                source: read_event.source,
+            });
+        futures::future::ready(Ok(()))
+    })
+    .await?;
+
+    let library_roots = LibraryRoots::load(con).await?;
+    let mut episodes = HashMap::<i64, Vec<ExportedEpisode>>::new();
+    sqlx::query_as::<_, models::Episode>(
+        "SELECT * FROM episodes ORDER BY volume, number, number_suffix",
+    )
+    .fetch(con.acquire().await?)
+    .try_for_each(|episode| {
+        episodes
+            .entry(episode.series)
+            .or_default()
+            .push(ExportedEpisode {
+                volume: episode.volume,
+                number: episode.number,
+                number_end: episode.number_end,
+                name: episode.name,
+                file: library_roots.resolve(episode.series, &episode.file),
+                file_size: episode.file_size,
+                date_of_read: episode.date_of_read.as_ref().map(format_timestamp),
+                reads: reads.remove(&episode.id).unwrap_or_default(),
+            });
+        futures::future::ready(Ok(()))
+    })
+    .await?;
+
+    let mut directories = HashMap::<i64, Vec<ExportedDirectory>>::new();
+    sqlx::query_as::<_, models::Directory>("SELECT * FROM directories ORDER BY dir")
+        .fetch(con.acquire().await?)
+        .try_for_each(|directory| {
+            directories
+                .entry(directory.series)
+                .or_default()
+                .push(ExportedDirectory {
+                    pattern: directory.pattern,
+                    dir: library_roots.resolve(directory.series, &directory.dir),
+                    volume: directory.volume,
+                    recursive: directory.recursive,
+                });
+            futures::future::ready(Ok(()))
+        })
+        .await?;
+
+    let mut serieses = HashMap::<i64, Vec<ExportedSeries>>::new();
+    sqlx::query_as::<_, models::Series>("SELECT * FROM serieses ORDER BY name")
+        .fetch(con.acquire().await?)
+        .try_for_each(|series| {
+            serieses
+                .entry(series.media_type)
+                .or_default()
+                .push(ExportedSeries {
+                    name: series.name,
+                    download_command_dir: series.download_command_dir,
+                    download_command: series.download_command,
+                    source: series.source,
+                    directories: directories.remove(&series.id).unwrap_or_default(),
+                    episodes: episodes.remove(&series.id).unwrap_or_default(),
+                });
+            futures::future::ready(Ok(()))
+        })
+        .await?;
+
+    let media_types =
+        sqlx::query_as::<_, models::MediaType>("SELECT * FROM media_types ORDER BY name")
+            .fetch(con.acquire().await?)
+            .map_ok(|media_type| ExportedMediaType {
+                serieses: serieses.remove(&media_type.id).unwrap_or_default(),
+                name: media_type.name,
+                base_dir: media_type.base_dir,
+                file_types: media_type.file_types,
+                adjacent_file_types: media_type.adjacent_file_types,
+                program: media_type.program,
+                maintain_symlinks: media_type.maintain_symlinks,
+                mark_read_on_exit: media_type.mark_read_on_exit,
+                link_template: media_type.link_template,
+                extract_metadata: media_type.extract_metadata,
+                download_command_template: media_type.download_command_template,
+            })
+            .try_collect()
+            .await?;
+
+    Ok(Library {
+        version: FORMAT_VERSION,
+        media_types,
+    })
+}
+
+pub async fn import_library(
+    con: &mut crate::SqlitePoolConnection,
+    library: Library,
+    overwrite_settings: bool,
+) -> anyhow::Result<ImportSummary> {
+    if FORMAT_VERSION < library.version {
+        anyhow::bail!(
+            "Library format version {} is newer than the supported version {}",
+            library.version,
+            FORMAT_VERSION
+        );
+    }
+    let mut summary = ImportSummary::default();
+    let mut tx = con.begin().await?;
+    for media_type in library.media_types {
+        let media_type_id = merge_media_type(
+            tx.acquire().await?,
+            &media_type,
+            overwrite_settings,
+            &mut summary,
+        )
+        .await?;
+        for series in media_type.serieses {
+            let series_id = merge_series(
+                tx.acquire().await?,
+                media_type_id,
+                &series,
+                overwrite_settings,
+                &mut summary,
+            )
+            .await?;
+            for directory in series.directories.iter() {
+                merge_directory(
+                    tx.acquire().await?,
+                    series_id,
+                    directory,
+                    overwrite_settings,
+                    &mut summary,
+                )
+                .await?;
+            }
+            for episode in series.episodes.iter() {
+                merge_episode(tx.acquire().await?, series_id, episode, &mut summary).await?;
+            }
+        }
+    }
+    tx.commit().await?;
+    Ok(summary)
+}
+
+pub async fn export_episodes_csv(
+    con: &mut sqlx::SqliteConnection,
+    writer: impl std::io::Write,
+) -> anyhow::Result<()> {
+    let rows: Vec<FromRowWithExtra<models::Episode, EpisodeNames>> = sqlx::query_as(
+        r#"
+        SELECT episodes.*
+            , media_types.name AS media_type_name
+            , serieses.name AS series_name
+            , (SELECT COUNT(*) FROM read_events WHERE read_events.episode = episodes.id) AS num_reads
+        FROM episodes
+        INNER JOIN serieses ON serieses.id = episodes.series
+        INNER JOIN media_types ON media_types.id = serieses.media_type
+        ORDER BY media_types.name, serieses.name, episodes.volume, episodes.number, episodes.number_suffix
+        "#,
+    )
+    .fetch(con.acquire().await?)
+    .try_collect()
+    .await?;
+    let library_roots = LibraryRoots::load(con).await?;
+    let mut writer = csv::Writer::from_writer(writer);
+    for FromRowWithExtra {
+        data: episode,
+        extra: names,
+    } in rows
+    {
+        writer.serialize(CsvEpisode {
+            media_type: names.media_type_name,
+            series: names.series_name,
+            volume: episode.volume,
+            number: episode.number,
+            number_end: episode.number_end,
+            name: episode.name,
+            file: library_roots.resolve(episode.series, &episode.file),
+            file_size: episode.file_size,
+            date_of_read: episode.date_of_read.as_ref().map(format_timestamp),
+            num_reads: names.num_reads,
+        })?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Import episodes from CSV. The media types must already exist - missing serieses are created
+/// without directories.
+pub async fn import_episodes_csv(
+    con: &mut crate::SqlitePoolConnection,
+    reader: impl std::io::Read,
+) -> anyhow::Result<ImportSummary> {
+    let mut summary = ImportSummary::default();
+    let mut media_type_ids = HashMap::<String, i64>::new();
+    let mut series_ids = HashMap::<(i64, String), i64>::new();
+    let mut tx = con.begin().await?;
+    for row in csv::Reader::from_reader(reader).deserialize() {
+        let row: CsvEpisode = row?;
+        let media_type_id = if let Some(&media_type_id) = media_type_ids.get(&row.media_type) {
+            media_type_id
+        } else {
+            let (media_type_id,): (i64,) =
+                sqlx::query_as("SELECT id FROM media_types WHERE name = ?")
+                    .bind(&row.media_type)
+                    .fetch_optional(tx.acquire().await?)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("No media type named {:?}", row.media_type))?;
+            media_type_ids.insert(row.media_type.clone(), media_type_id);
+            media_type_id
+        };
+        let series_key = (media_type_id, row.series);
+        let series_id = if let Some(&series_id) = series_ids.get(&series_key) {
+            series_id
+        } else {
+            let series = ExportedSeries {
+                name: series_key.1.clone(),
+                download_command_dir: None,
+                download_command: None,
+                source: None,
+                directories: Vec::new(),
+                episodes: Vec::new(),
+            };
+            let series_id = merge_series(
+                tx.acquire().await?,
+                media_type_id,
+                &series,
+                false,
+                &mut summary,
+            )
+            .await?;
+            series_ids.insert(series_key, series_id);
+            series_id
+        };
+        let episode = ExportedEpisode {
+            volume: row.volume,
+            number: row.number,
+            number_end: row.number_end,
+            name: row.name,
+            file: row.file,
+            file_size: row.file_size,
+            reads: row
+                .date_of_read
+                .iter()
+                .map(|date_of_read| ExportedRead {
+                    timestamp: date_of_read.clone(),
+                    note: None,
+                    source: Some("import".to_owned()),
+                })
+                .collect(),
+            date_of_read: row.date_of_read,
+        };
+        merge_episode(tx.acquire().await?, series_id, &episode, &mut summary).await?;
+    }
+    tx.commit().await?;
+    Ok(summary)
+}
+
+#[derive(sqlx::FromRow)]
+struct EpisodeNames {
+    media_type_name: String,
+    series_name: String,
+    num_reads: i64,
+}
+
+async fn merge_media_type(
+    con: &mut sqlx::SqliteConnection,
+    media_type: &ExportedMediaType,
+    overwrite_settings: bool,
+    summary: &mut ImportSummary,
+) -> anyhow::Result<i64> {
+    let existing: Option<(i64,)> = sqlx::query_as("SELECT id FROM media_types WHERE name = ?")
+        .bind(&media_type.name)
+        .fetch_optional(con.acquire().await?)
+        .await?;
+    if let Some((media_type_id,)) = existing {
+        if overwrite_settings {
+            crate::library_root::move_base_dir(con, media_type_id, &media_type.base_dir).await?;
+            sqlx::query(
+                r#"
+                UPDATE media_types
+                SET base_dir = ?
+                  , file_types = ?
+                  , adjacent_file_types = ?
+                  , program = ?
+                  , maintain_symlinks = ?
+                  , mark_read_on_exit = ?
+                  , link_template = ?
+                  , extract_metadata = ?
+                  , download_command_template = ?
+                WHERE id = ?
+                "#,
+            )
+            .bind(&media_type.base_dir)
+            .bind(&media_type.file_types)
+            .bind(&media_type.adjacent_file_types)
+            .bind(&media_type.program)
+            .bind(media_type.maintain_symlinks)
+            .bind(media_type.mark_read_on_exit)
+            .bind(&media_type.link_template)
+            .bind(media_type.extract_metadata)
+            .bind(&media_type.download_command_template)
+            .bind(media_type_id)
+            .execute(con.acquire().await?)
+            .await?;
+        }
+        return Ok(media_type_id);
+    }
+    let query_result = sqlx::query(
+        r#"
+        INSERT INTO media_types(name, base_dir, file_types, adjacent_file_types, program, maintain_symlinks, mark_read_on_exit, link_template, extract_metadata, download_command_template)
+        VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&media_type.name)
+    .bind(&media_type.base_dir)
+    .bind(&media_type.file_types)
+    .bind(&media_type.adjacent_file_types)
+    .bind(&media_type.program)
+    .bind(media_type.maintain_symlinks)
+    .bind(media_type.mark_read_on_exit)
+    .bind(&media_type.link_template)
+    .bind(media_type.extract_metadata)
+    .bind(&media_type.download_command_template)
+    .execute(con.acquire().await?)
+    .await?;
+    summary.media_types_added += 1;
+    Ok(query_result.last_insert_rowid())
+}
+
+async fn merge_series(
+    con: &mut sqlx::SqliteConnection,
+    media_type_id: i64,
+    series: &ExportedSeries,
+    overwrite_settings: bool,
+    summary: &mut ImportSummary,
+) -> anyhow::Result<i64> {
+    let existing: Option<(i64,)> =
+        sqlx::query_as("SELECT id FROM serieses WHERE media_type = ? AND name = ?")
+            .bind(media_type_id)
+            .bind(&series.name)
+            .fetch_optional(con.acquire().await?)
+            .await?;
+    if let Some((series_id,)) = existing {
+        if overwrite_settings {
+            sqlx::query(
+                r#"
+                UPDATE serieses
+                SET download_command_dir = ?
+                  , download_command = ?
+                  , source = ?
+                WHERE id = ?
+                "#,
+            )
+            .bind(&series.download_command_dir)
+            .bind(&series.download_command)
+            .bind(&series.source)
+            .bind(series_id)
+            .execute(con.acquire().await?)
+            .await?;
+        }
+        return Ok(series_id);
+    }
+    let query_result = sqlx::query(
+        r#"
+        INSERT INTO serieses(media_type, name, download_command_dir, download_command, source)
+        VALUES(?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(media_type_id)
+    .bind(&series.name)
+    .bind(&series.download_command_dir)
+    .bind(&series.download_command)
+    .bind(&series.source)
+    .execute(con.acquire().await?)
+    .await?;
+    summary.serieses_added += 1;
+    Ok(query_result.last_insert_rowid())
+}
+
+async fn merge_directory(
+    con: &mut sqlx::SqliteConnection,
+    series_id: i64,
+    directory: &ExportedDirectory,
+    overwrite_settings: bool,
+    summary: &mut ImportSummary,
+) -> anyhow::Result<()> {
+    let base_dir = crate::library_root::series_base_dir(con, series_id).await?;
+    let dir = crate::library_root::to_stored(&base_dir, &directory.dir);
+    let existing: Option<(i64,)> =
+        sqlx::query_as("SELECT id FROM directories WHERE series = ? AND dir = ? AND pattern = ?")
+            .bind(series_id)
+            .bind(&dir)
+            .bind(&directory.pattern)
+            .fetch_optional(con.acquire().await?)
+            .await?;
+    if let Some((directory_id,)) = existing {
+        if overwrite_settings {
+            sqlx::query("UPDATE directories SET volume = ?, recursive = ? WHERE id = ?")
+                .bind(directory.volume)
+                .bind(directory.recursive)
+                .bind(directory_id)
+                .execute(con.acquire().await?)
+                .await?;
+        }
+        return Ok(());
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO directories(series, pattern, dir, volume, recursive)
+        VALUES(?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(series_id)
+    .bind(&directory.pattern)
+    .bind(&dir)
+    .bind(directory.volume)
+    .bind(directory.recursive)
+    .execute(con.acquire().await?)
+    .await?;
+    summary.directories_added += 1;
+    Ok(())
+}
+
+async fn merge_episode(
+    con: &mut sqlx::SqliteConnection,
+    series_id: i64,
+    episode: &ExportedEpisode,
+    summary: &mut ImportSummary,
+) -> anyhow::Result<()> {
+    let imported_date_of_read = episode
+        .date_of_read
+        .as_deref()
+        .map(parse_timestamp)
+        .transpose()?;
+    let existing: Option<(i64, Option<NaiveDateTime>)> = sqlx::query_as(
+        r#"
+        SELECT id, date_of_read
+        FROM episodes
+        WHERE series = ? AND volume IS ? AND number = ? AND number_suffix = ?
+        "#,
+    )
+    .bind(series_id)
+    .bind(episode.volume)
+    .bind(episode.number.stored_number())
+    .bind(episode.number.suffix())
+    .fetch_optional(con.acquire().await?)
+    .await?;
+    let (episode_id, existing_date_of_read) = if let Some(existing) = existing {
+        existing
+    } else {
+        let base_dir = crate::library_root::series_base_dir(con, series_id).await?;
+        let file = crate::library_root::to_stored(&base_dir, &episode.file);
+        let registered: Option<(i64, Option<NaiveDateTime>)> =
+            sqlx::query_as("SELECT id, date_of_read FROM episodes WHERE series = ? AND file = ?")
+                .bind(series_id)
+                .bind(&file)
+                .fetch_optional(con.acquire().await?)
+                .await?;
+        if let Some(registered) = registered {
+            summary.episodes_skipped += 1;
+            registered
+        } else {
+            let query_result = sqlx::query(
+            r#"
+            INSERT INTO episodes(series, volume, number, number_suffix, number_end, name, file, date_of_read, file_size, date_of_discovery)
+            VALUES(?, ?, ?, ?, ?, ?, ?, NULL, ?, datetime())
+            "#,
+        )
+        .bind(series_id)
+        .bind(episode.volume)
+        .bind(episode.number.stored_number())
+        .bind(episode.number.suffix())
+        .bind(&episode.number_end)
+        .bind(&episode.name)
+        .bind(&file)
+        .bind(episode.file_size)
+        .execute(con.acquire().await?)
+        .await?;
+            summary.episodes_added += 1;
+            (query_result.last_insert_rowid(), None)
+        }
+    };
+    for read in episode.reads.iter() {
+        let timestamp = format_timestamp(&parse_timestamp(&read.timestamp)?);
+        let query_result = sqlx::query(
+            r#"
+            INSERT INTO read_events(episode, timestamp, note, source)
+            SELECT ?, ?, ?, ?
+            WHERE NOT EXISTS (SELECT 1 FROM read_events WHERE episode = ? AND timestamp = ?)
+            "#,
+        )
+        .bind(episode_id)
+        .bind(&timestamp)
+        .bind(&read.note)
+        .bind(&read.source)
+        .bind(episode_id)
+        .bind(&timestamp)
+        .execute(con.acquire().await?)
+        .await?;
+        summary.reads_added += query_result.rows_affected() as usize;
+    }
+    // Adding reads makes the episode read, so set the read state explicitly.
+    sqlx::query("UPDATE episodes SET date_of_read = ? WHERE id = ?")
+        .bind(
+            existing_date_of_read
+                .max(imported_date_of_read)
+                .as_ref()
+                .map(format_timestamp),
+        )
+        .bind(episode_id)
+        .execute(con.acquire().await?)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn empty_library() -> crate::SqlitePoolConnection {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        pool.acquire().await.unwrap()
+    }
+
+    fn library_with_episode(episode: serde_json::Value) -> Library {
+        serde_json::from_value(serde_json::json!({
+            "version": FORMAT_VERSION,
+            "media_types": [{
+                "name": "Manga",
+                "base_dir": "/manga",
+                "file_types": "cbz",
+                "program": "mcomix",
+                "serieses": [{
+                    "name": "Some Manga",
+                    "episodes": [episode],
+                }],
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_import_episode_with_registered_file() {
+        let mut con = empty_library().await;
+        let summary = import_library(
+            &mut con,
+            library_with_episode(serde_json::json!({
+                "number": 1,
+                "name": "Some Manga c1",
+                "file": "/manga/some/c1.cbz",
+            })),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(summary.episodes_added, 1);
+
+        let summary = import_library(
+            &mut con,
+            library_with_episode(serde_json::json!({
+                "volume": 1,
+                "number": 2,
+                "name": "Some Manga v1c2",
+                "file": "/manga/some/c1.cbz",
+                "date_of_read": "2023-08-16 13:32:26",
+                "reads": [{ "timestamp": "2023-08-16 13:32:26" }],
+            })),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(summary.episodes_added, 0);
+        assert_eq!(summary.episodes_skipped, 1);
+        assert_eq!(summary.reads_added, 1);
+
+        let episodes: Vec<(Option<i64>, i64, String, Option<NaiveDateTime>)> =
+            sqlx::query_as("SELECT volume, number, file, date_of_read FROM episodes")
+                .fetch_all(con.acquire().await.unwrap())
+                .await
+                .unwrap();
+        assert_eq!(
+            episodes,
+            [(
+                None,
+                1,
+                "some/c1.cbz".to_owned(),
+                Some(parse_timestamp("2023-08-16 13:32:26").unwrap())
+            )]
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::Path;
 
 use futures::future::join_all;
 use hashbrown::{HashMap, HashSet};
@@ -9,10 +9,10 @@ use tokio_stream::wrappers::ReadDirStream;
 
 use sqlx::prelude::*;
 
-use crate::links_handling::{
-    prepare_media_type_to_adjacent_types_mapping, prepare_series_to_adjacent_types_mapping,
-};
+use crate::chapter_number::{self, ChapterNumber};
+use crate::library_root::LibraryRoots;
 use crate::models;
+use crate::util::db::FromRowWithExtra;
 
 pub struct FoundFile {
     pub series: i64,
@@ -23,26 +23,27 @@ pub struct FoundFile {
 
 pub async fn run_files_discovery(
     mut con: crate::SqlitePoolConnection,
+    series: Option<i64>,
 ) -> anyhow::Result<Vec<FoundFile>> {
-    let media_type_to_adjacent_types =
-        prepare_media_type_to_adjacent_types_mapping(&mut con).await?;
-    let series_to_adjacent_types =
-        prepare_series_to_adjacent_types_mapping(&mut con, &media_type_to_adjacent_types).await?;
-    log::info!("{:?}", series_to_adjacent_types);
+    let series_to_file_types_filter = prepare_series_to_file_types_filter_mapping(&mut con).await?;
+    log::info!("{:?}", series_to_file_types_filter);
 
+    let library_roots = LibraryRoots::load(&mut con).await?;
     let mut directories = HashMap::<(String, bool), Vec<models::Directory>>::new();
-    sqlx::query_as::<_, models::Directory>("SELECT id, series, replace(pattern, '(?<', '(?P<') AS pattern, dir, volume, recursive FROM directories").fetch(con.acquire().await?).try_for_each(|directory| {
-        if let Some(entry) = directories.get_mut(&(directory.dir.clone(), directory.recursive)) {
+    sqlx::query_as::<_, models::Directory>("SELECT id, series, replace(pattern, '(?<', '(?P<') AS pattern, dir, volume, recursive FROM directories WHERE ?1 IS NULL OR series = ?1").bind(series).fetch(con.acquire().await?).try_for_each(|directory| {
+        let dir = library_roots.resolve(directory.series, &directory.dir);
+        if let Some(entry) = directories.get_mut(&(dir.clone(), directory.recursive)) {
             entry.push(directory);
         } else {
-            directories.insert((directory.dir.clone(), directory.recursive), vec![directory]);
+            directories.insert((dir, directory.recursive), vec![directory]);
         }
         futures::future::ready(Ok(()))
     }).await?;
+    let known_files = load_known_files(&mut con).await?;
     let mut result = Vec::new();
     for ((path, recursive), directories) in directories {
         log::trace!("{} has {} patterns", path, directories.len());
-        let new_files = discover_in_path(&mut con, &path, recursive).await?;
+        let new_files = discover_in_path(&path, recursive, &known_files).await?;
         if new_files.is_empty() {
             continue;
         }
@@ -60,14 +61,10 @@ pub async fn run_files_discovery(
             if let Some(index) = regex_set.matches(&new_file).iter().next() {
                 let directory = &directories[index];
 
-                if let Some(adjacent_file_types) = series_to_adjacent_types.get(&directory.series) {
-                    if let Some(extension) = std::path::Path::new(&new_file)
-                        .extension()
-                        .and_then(|ext| ext.to_str())
-                    {
-                        if adjacent_file_types.contains(extension) {
-                            continue;
-                        }
+                if let Some(file_types_filter) = series_to_file_types_filter.get(&directory.series)
+                {
+                    if !file_types_filter.accepts(&new_file) {
+                        continue;
                     }
                 }
 
@@ -94,10 +91,315 @@ pub async fn run_files_discovery(
     Ok(result)
 }
 
+/// Decides which files a media type can register as episodes, based on their extensions.
+///
+/// Extensions are matched case-insensitively. An empty `file_types` accepts files with any
+/// extension (or none), but files with one of the `adjacent_file_types` are never accepted since
+/// they accompany the episode files rather than being episodes themselves.
+#[derive(Debug, Default, Clone)]
+pub struct FileTypesFilter {
+    file_types: HashSet<String>,
+    adjacent_file_types: HashSet<String>,
+}
+
+impl FileTypesFilter {
+    pub fn new(file_types: &str, adjacent_file_types: &str) -> Self {
+        fn parse(file_types: &str) -> HashSet<String> {
+            file_types
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .map(|ft| ft.trim_start_matches('.').to_lowercase())
+                .filter(|ft| !ft.is_empty())
+                .collect()
+        }
+        Self {
+            file_types: parse(file_types),
+            adjacent_file_types: parse(adjacent_file_types),
+        }
+    }
+
+    pub fn accepts(&self, path: &str) -> bool {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+        if let Some(extension) = &extension {
+            if self.adjacent_file_types.contains(extension) {
+                return false;
+            }
+        }
+        if self.file_types.is_empty() {
+            return true;
+        }
+        extension.is_some_and(|ext| self.file_types.contains(&ext))
+    }
+}
+
+pub async fn prepare_series_to_file_types_filter_mapping(
+    con: &mut crate::SqlitePoolConnection,
+) -> anyhow::Result<HashMap<i64, FileTypesFilter>> {
+    let mut mapping = HashMap::new();
+    sqlx::query_as::<_, (i64, String, String)>(
+        r#"
+        SELECT serieses.id, media_types.file_types, media_types.adjacent_file_types
+        FROM serieses
+        INNER JOIN media_types ON serieses.media_type = media_types.id
+        "#,
+    )
+    .fetch(con.acquire().await?)
+    .try_for_each(|(series_id, file_types, adjacent_file_types)| {
+        mapping.insert(
+            series_id,
+            FileTypesFilter::new(&file_types, &adjacent_file_types),
+        );
+        futures::future::ready(Ok(()))
+    })
+    .await?;
+    Ok(mapping)
+}
+
+/// The episodes a scan registered for a single series.
+#[derive(Debug, Clone)]
+pub struct NewEpisodes {
+    pub series: i64,
+    pub series_name: String,
+    pub episodes: Vec<i64>,
+    pub first_chapter: ChapterNumber,
+    pub last_chapter: ChapterNumber,
+}
+
+impl NewEpisodes {
+    /// Add episodes of the same series that were registered by another scan.
+    pub fn merge(&mut self, other: NewEpisodes) {
+        self.episodes.extend(other.episodes);
+        if other.first_chapter < self.first_chapter {
+            self.first_chapter = other.first_chapter;
+        }
+        if self.last_chapter < other.last_chapter {
+            self.last_chapter = other.last_chapter;
+        }
+    }
+
+    /// Like `Series A: 3 new chapters (c101–c103)`.
+    pub fn describe(&self) -> String {
+        format!(
+            "{}: {} new {} ({})",
+            self.series_name,
+            self.episodes.len(),
+            if self.episodes.len() == 1 {
+                "chapter"
+            } else {
+                "chapters"
+            },
+            if self.first_chapter == self.last_chapter {
+                format!("c{}", self.first_chapter)
+            } else {
+                format!("c{}–c{}", self.first_chapter, self.last_chapter)
+            },
+        )
+    }
+}
+
+/// Register the files as new episodes, and return the new episodes of each series, ordered by
+/// series name. Files of moved episodes are relinked instead, and are not counted as new.
+pub async fn register_files(
+    con: &mut crate::SqlitePoolConnection,
+    new_files: Vec<FoundFile>,
+) -> anyhow::Result<Vec<NewEpisodes>> {
+    let new_files = relink_moved_files(con, new_files).await?;
+    let library_roots = LibraryRoots::load(con).await?;
+    let mut series_map = HashMap::<i64, String>::new();
+    sqlx::query_as::<_, (i64, String)>("SELECT id, name FROM serieses")
+        .fetch(con.acquire().await?)
+        .try_for_each(|(id, name)| {
+            series_map.insert(id, name);
+            futures::future::ready(Ok(()))
+        })
+        .await?;
+    let statement = con
+        .prepare(
+            r#"
+            INSERT OR IGNORE INTO episodes(series, volume, number, number_suffix, number_end, name, file, date_of_read, file_size, date_of_discovery)
+            VALUES(?, ?, ?, ?, ?, ?, ?, NULL, ?, datetime());
+            "#,
+        )
+        .await?;
+    let mut new_episodes = HashMap::<i64, NewEpisodes>::new();
+    for file in new_files {
+        let file_size = get_file_size(&file.path).await;
+        let query_result = statement
+            .query()
+            .bind(file.series)
+            .bind(file.file_data.volume)
+            .bind(file.file_data.chapter.stored_number())
+            .bind(file.file_data.chapter.suffix())
+            .bind(&file.file_data.chapter_end)
+            .bind({
+                let chapters = chapter_number::format_range(
+                    &file.file_data.chapter,
+                    file.file_data.chapter_end.as_ref(),
+                );
+                if let Some(volume) = file.file_data.volume {
+                    format!("{} v{:?} c{}", series_map[&file.series], volume, chapters)
+                } else {
+                    format!("{} c{}", series_map[&file.series], chapters)
+                }
+            })
+            .bind(library_roots.to_stored(file.series, &file.path))
+            .bind(file_size)
+            .execute(con.acquire().await?)
+            .await?;
+        if query_result.rows_affected() == 0 {
+            // Already registered, by a scan that ran before this one was stored.
+            continue;
+        }
+        let episode_id = query_result.last_insert_rowid();
+        let episode = NewEpisodes {
+            series: file.series,
+            series_name: series_map[&file.series].clone(),
+            episodes: vec![episode_id],
+            first_chapter: file.file_data.chapter.clone(),
+            last_chapter: file.file_data.chapter_end.unwrap_or(file.file_data.chapter),
+        };
+        match new_episodes.entry(file.series) {
+            hashbrown::hash_map::Entry::Occupied(mut entry) => entry.get_mut().merge(episode),
+            hashbrown::hash_map::Entry::Vacant(entry) => {
+                entry.insert(episode);
+            }
+        }
+    }
+    let new_episode_ids = new_episodes
+        .values()
+        .flat_map(|new_episodes| new_episodes.episodes.iter().copied())
+        .collect::<Vec<_>>();
+    if let Err(err) = crate::metadata::extract_episodes_metadata(con, &new_episode_ids).await {
+        log::error!("Cannot extract the metadata of the new episodes: {}", err);
+    }
+    let mut new_episodes = new_episodes.into_values().collect::<Vec<_>>();
+    new_episodes.sort_by(|a, b| a.series_name.cmp(&b.series_name));
+    Ok(new_episodes)
+}
+
+async fn get_file_size(path: &str) -> Option<i64> {
+    match fs::metadata(path).await {
+        Ok(metadata) => Some(metadata.len() as i64),
+        Err(err) => {
+            log::warn!("Cannot get the size of {:?}: {}", path, err);
+            None
+        }
+    }
+}
+
+/// Match newly found files against episodes of the same series whose files are missing, and
+/// point these episodes to the new files instead of registering them as new episodes. This
+/// keeps the read state of episodes whose files were moved or renamed.
+///
+/// An episode matches a file if they have the same volume and chapter, and - if the episode's
+/// file size and content hash are known - the same size and content. An episode that matches more
+/// than one file is left alone. Returns the files that were not matched to any episode.
+pub async fn relink_moved_files(
+    con: &mut crate::SqlitePoolConnection,
+    new_files: Vec<FoundFile>,
+) -> anyhow::Result<Vec<FoundFile>> {
+    let mut new_files_by_key =
+        HashMap::<(i64, Option<i64>, ChapterNumber), Vec<(FoundFile, Option<i64>)>>::new();
+    for file in new_files {
+        let file_size = get_file_size(&file.path).await;
+        new_files_by_key
+            .entry((
+                file.series,
+                file.file_data.volume.map(i64::from),
+                file.file_data.chapter.clone(),
+            ))
+            .or_default()
+            .push((file, file_size));
+    }
+    let serieses: HashSet<i64> = new_files_by_key
+        .keys()
+        .map(|(series, _, _)| *series)
+        .collect();
+
+    let library_roots = LibraryRoots::load(con).await?;
+    let mut tx = con.begin().await?;
+    for series in serieses {
+        let episodes: Vec<models::Episode> =
+            sqlx::query_as("SELECT * FROM episodes WHERE series = ?")
+                .bind(series)
+                .fetch(tx.acquire().await?)
+                .try_collect()
+                .await?;
+        for mut episode in episodes {
+            episode.file = library_roots.resolve(series, &episode.file);
+            let Some(candidates) =
+                new_files_by_key.get_mut(&(series, episode.volume, episode.number.clone()))
+            else {
+                continue;
+            };
+            match fs::metadata(&episode.file).await {
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                _ => continue,
+            }
+            let mut matching = Vec::new();
+            for (index, (file, file_size)) in candidates.iter().enumerate() {
+                if episode.file_size.is_some() && episode.file_size != *file_size {
+                    continue;
+                }
+                // Another rip of the same chapter may have the same size, so the content decides
+                // when it is known.
+                let content_hash = match crate::duplicates::hash_file(&file.path).await {
+                    Ok(content_hash) => Some(content_hash),
+                    Err(err) => {
+                        log::warn!("Cannot hash {:?}: {}", file.path, err);
+                        None
+                    }
+                };
+                if episode.content_hash.is_some() && episode.content_hash != content_hash {
+                    continue;
+                }
+                matching.push((index, content_hash));
+            }
+            let (index, content_hash) = match matching.len() {
+                0 => continue,
+                1 => matching.remove(0),
+                _ => {
+                    log::warn!(
+                        "Episode {:?} has multiple candidates for its moved file - not relinking",
+                        episode.name
+                    );
+                    continue;
+                }
+            };
+            let (file, file_size) = candidates.remove(index);
+            log::info!(
+                "Relinking {:?} from {:?} to {:?}",
+                episode.name,
+                episode.file,
+                file.path
+            );
+            sqlx::query(
+                "UPDATE episodes SET file = ?, file_size = ?, content_hash = ? WHERE id = ?",
+            )
+            .bind(library_roots.to_stored(series, &file.path))
+            .bind(file_size)
+            .bind(content_hash)
+            .bind(episode.id)
+            .execute(tx.acquire().await?)
+            .await?;
+        }
+    }
+    tx.commit().await?;
+
+    Ok(new_files_by_key
+        .into_values()
+        .flat_map(|files| files.into_iter().map(|(file, _)| file))
+        .collect())
+}
+
 #[derive(Debug)]
 pub struct FileData {
     pub volume: Option<i32>,
-    pub chapter: i32,
+    pub chapter: ChapterNumber,
+    /// The last chapter, for files that contain a range of chapters.
+    pub chapter_end: Option<ChapterNumber>,
 }
 
 impl FileData {
@@ -119,40 +421,69 @@ pub fn process_file_match(
         return Ok(None);
     };
 
+    // The pattern may only capture the digits, so take the chapters from the filename to get
+    // fractions and suffixes that come after them.
+    let parse_chapter_at = |chapter_match: regex::Match| {
+        ChapterNumber::parse_prefix(&filename[chapter_match.start()..])
+            .ok_or_else(|| anyhow::anyhow!("{:?} is not a chapter number", chapter_match.as_str()))
+    };
+
+    let chapter = if let Some(c_match) = captures.name("c") {
+        parse_chapter_at(c_match)?
+    } else {
+        let entire_match = captures.get(0).expect("Capture group 0 always exists");
+        let (_, after_match) = filename.split_at(entire_match.end());
+        log::trace!(
+            "No chapter. Match ends at {} which is {:?}",
+            entire_match.end(),
+            after_match
+        );
+        let chapter_start = after_match
+            .find(|c: char| c.is_ascii_digit())
+            .ok_or_else(|| anyhow::Error::msg("No match afer"))?;
+        ChapterNumber::parse_prefix(&after_match[chapter_start..]).expect("starts with a digit")
+    };
+    let chapter_end = captures.name("c_end").map(parse_chapter_at).transpose()?;
+
     Ok(Some(FileData {
         volume: if let Some(v_match) = captures.name("v") {
             Some(v_match.as_str().parse()?)
         } else {
             None
         },
-        chapter: if let Some(c_match) = captures.name("c") {
-            c_match.as_str().parse()?
-        } else {
-            let entire_match = captures.get(0).expect("Capture group 0 always exists");
-            let (_, after_match) = filename.split_at(entire_match.end());
-            log::trace!(
-                "No chapter. Match ends at {} which is {:?}",
-                entire_match.end(),
-                after_match
-            );
-            after_match
-                .split(|c: char| !c.is_ascii_digit())
-                .find(|s| !s.is_empty())
-                .ok_or_else(|| anyhow::Error::msg("No match afer"))?
-                .parse()?
-        },
+        chapter_end: chapter_number::normalize_range_end(&chapter, chapter_end)?,
+        chapter,
     }))
 }
 
-pub async fn discover_in_path(
+/// The resolved paths of all the files that are either registered as episodes or ignored - so
+/// discovering them again should not offer them as new files.
+pub async fn load_known_files(
     con: &mut crate::SqlitePoolConnection,
+) -> anyhow::Result<HashSet<String>> {
+    let library_roots = LibraryRoots::load(con).await?;
+    let mut known_files: HashSet<String> =
+        sqlx::query_as::<_, (i64, String)>("SELECT series, file FROM episodes")
+            .fetch(con.acquire().await?)
+            .map_ok(|(series, file)| library_roots.resolve(series, &file))
+            .try_collect()
+            .await?;
+    sqlx::query_as::<_, (String,)>("SELECT file FROM ignored_files")
+        .fetch(con.acquire().await?)
+        .try_for_each(|(file,)| {
+            known_files.insert(file);
+            futures::future::ready(Ok(()))
+        })
+        .await?;
+    Ok(known_files)
+}
+
+pub async fn discover_in_path(
     path: &str,
     recursive: bool,
+    known_files: &HashSet<String>,
 ) -> anyhow::Result<Vec<String>> {
-    let mut tx = con.begin().await?;
-    sqlx::query("CREATE TEMP TABLE discovered_files(filename text)")
-        .execute(tx.acquire().await?)
-        .await?;
+    let mut new_files = Vec::new();
     let mut search_in = vec![path.to_owned()];
     while let Some(path) = search_in.pop() {
         let mut read_dir_result = match fs::read_dir(&path).await {
@@ -175,48 +506,72 @@ pub async fn discover_in_path(
                     continue;
                 }
             }
-            sqlx::query("INSERT INTO discovered_files(filename) VALUES(?)")
-                .bind(file_path)
-                .execute(tx.acquire().await?)
-                .await?;
+            if !known_files.contains(&file_path) {
+                new_files.push(file_path);
+            }
         }
     }
-    let new_files: Vec<String> = sqlx::query_as::<_, (String,)>(
-        r#"
-        SELECT discovered_files.filename
-        FROM discovered_files
-            LEFT JOIN episodes ON discovered_files.filename = episodes.file
-        WHERE episodes.file IS NULL
-        "#,
-    )
-    .fetch(tx.acquire().await?)
-    .map_ok(|(filename,)| filename)
-    .try_collect()
-    .await?;
-    tx.rollback().await?;
     Ok(new_files)
 }
 
+#[derive(Debug)]
+pub struct DanglingFile {
+    pub episode: models::Episode,
+    pub series_name: String,
+    /// The directory that should have contained the file does not exist either.
+    pub directory_missing: bool,
+    /// The base directory of the series' media type does not exist - usually an unmounted drive.
+    pub base_dir_missing: bool,
+}
+
+impl DanglingFile {
+    pub fn status(&self) -> &'static str {
+        if self.base_dir_missing {
+            "base directory missing"
+        } else if self.directory_missing {
+            "directory missing"
+        } else {
+            "file missing"
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct EpisodeLocation {
+    series_name: String,
+    base_dir: String,
+}
+
 pub async fn run_dangling_files_scan(
     con: &mut crate::SqlitePoolConnection,
-) -> anyhow::Result<Vec<i64>> {
-    let unread_episodes: Vec<models::Episode> =
-        sqlx::query_as("SELECT * FROM episodes WHERE date_of_read IS NULL")
-            .fetch(con.acquire().await?)
-            .try_collect()
-            .await?;
-    let mut file_to_id: HashMap<PathBuf, i64> = unread_episodes
-        .into_iter()
-        .map(|episode| (PathBuf::from(episode.file), episode.id))
-        .collect();
-    let directories: HashSet<_> = file_to_id
-        .keys()
-        .map(|filepath| {
-            let mut filepath = filepath.clone();
-            filepath.pop();
-            filepath
-        })
+) -> anyhow::Result<Vec<DanglingFile>> {
+    let episodes: Vec<FromRowWithExtra<models::Episode, EpisodeLocation>> = sqlx::query_as(
+        r#"
+        SELECT episodes.*
+            , serieses.name AS series_name
+            , media_types.base_dir
+        FROM episodes
+        INNER JOIN serieses ON episodes.series = serieses.id
+        INNER JOIN media_types ON serieses.media_type = media_types.id
+        "#,
+    )
+    .fetch(con.acquire().await?)
+    .map_ok(
+        |mut episode: FromRowWithExtra<models::Episode, EpisodeLocation>| {
+            episode.data.file =
+                crate::library_root::resolve(&episode.extra.base_dir, &episode.data.file);
+            episode
+        },
+    )
+    .try_collect()
+    .await?;
+    let directories: HashSet<_> = episodes
+        .iter()
+        .filter_map(|episode| Path::new(&episode.data.file).parent())
+        .map(|directory| directory.to_owned())
         .collect();
+    let mut existing_files = HashSet::new();
+    let mut missing_directories = HashSet::new();
     for found_files in join_all(directories.into_iter().map(|directory| async move {
         match fs::read_dir(&directory).await {
             Ok(ok) => {
@@ -225,12 +580,12 @@ pub async fn run_dangling_files_scan(
                 while let Some(dir_entry) = read_dir_result.try_next().await? {
                     found_files.push(directory.join(dir_entry.file_name()));
                 }
-                Ok(found_files)
+                Ok((directory, Some(found_files)))
             }
             Err(err) => {
                 if matches!(err.kind(), std::io::ErrorKind::NotFound) {
-                    log::debug!("{:?} does not exist - skipping", directory);
-                    Ok(Vec::new())
+                    log::debug!("{:?} does not exist", directory);
+                    Ok((directory, None))
                 } else {
                     Err(err)
                 }
@@ -239,9 +594,107 @@ pub async fn run_dangling_files_scan(
     }))
     .await
     {
-        for found_file in found_files? {
-            file_to_id.remove(&found_file);
+        match found_files? {
+            (_, Some(found_files)) => existing_files.extend(found_files),
+            (directory, None) => {
+                missing_directories.insert(directory);
+            }
+        }
+    }
+    let mut missing_base_dirs = HashSet::new();
+    for base_dir in episodes
+        .iter()
+        .map(|episode| episode.extra.base_dir.as_str())
+        .filter(|base_dir| !base_dir.is_empty())
+        .collect::<HashSet<_>>()
+    {
+        if let Err(err) = fs::metadata(base_dir).await {
+            if matches!(err.kind(), std::io::ErrorKind::NotFound) {
+                log::warn!("Base directory {:?} does not exist", base_dir);
+                missing_base_dirs.insert(base_dir.to_owned());
+            } else {
+                return Err(err.into());
+            }
         }
     }
-    Ok(file_to_id.values().copied().collect())
+    Ok(episodes
+        .into_iter()
+        .filter(|episode| !existing_files.contains(Path::new(&episode.data.file)))
+        .map(|FromRowWithExtra { data, extra }| DanglingFile {
+            directory_missing: Path::new(&data.file)
+                .parent()
+                .is_some_and(|directory| missing_directories.contains(directory)),
+            base_dir_missing: missing_base_dirs.contains(&extra.base_dir),
+            series_name: extra.series_name,
+            episode: data,
+        })
+        .collect())
+}
+
+/// Refuse removing dangling files in bulk if some of them are missing because their entire base
+/// directory is missing. This usually means a drive is not mounted, rather than files that were
+/// really deleted.
+pub fn verify_safe_to_remove_dangling_files(dangling_files: &[DanglingFile]) -> anyhow::Result<()> {
+    let affected_serieses: HashSet<&str> = dangling_files
+        .iter()
+        .filter(|dangling_file| dangling_file.base_dir_missing)
+        .map(|dangling_file| dangling_file.series_name.as_str())
+        .collect();
+    if !affected_serieses.is_empty() {
+        anyhow::bail!(
+            "The base directories of {:?} are missing - is a drive unmounted?",
+            affected_serieses
+        );
+    }
+    Ok(())
+}
+
+pub async fn remove_episodes(
+    con: &mut crate::SqlitePoolConnection,
+    episode_ids: impl IntoIterator<Item = i64>,
+) -> anyhow::Result<()> {
+    let mut tx = con.begin().await?;
+    for episode_id in episode_ids {
+        sqlx::query("DELETE FROM episodes WHERE id == ?")
+            .bind(episode_id)
+            .execute(tx.acquire().await?)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn relink_episode(
+    con: &mut crate::SqlitePoolConnection,
+    episode_id: i64,
+    file: &str,
+) -> anyhow::Result<()> {
+    let metadata = fs::metadata(file).await?;
+    if !metadata.is_file() {
+        anyhow::bail!("{:?} is not a file", file);
+    }
+    let base_dir: String = sqlx::query_as::<_, (String,)>(
+        r#"
+        SELECT media_types.base_dir
+        FROM episodes
+        INNER JOIN serieses ON serieses.id = episodes.series
+        INNER JOIN media_types ON media_types.id = serieses.media_type
+        WHERE episodes.id = ?
+        "#,
+    )
+    .bind(episode_id)
+    .fetch_optional(con.acquire().await?)
+    .await?
+    .ok_or_else(|| anyhow::anyhow!("No episode with id={}", episode_id))?
+    .0;
+    let query_result = sqlx::query("UPDATE episodes SET file = ?, file_size = ? WHERE id = ?")
+        .bind(crate::library_root::to_stored(&base_dir, file))
+        .bind(metadata.len() as i64)
+        .bind(episode_id)
+        .execute(con.acquire().await?)
+        .await?;
+    if query_result.rows_affected() == 0 {
+        anyhow::bail!("No episode with id={}", episode_id);
+    }
+    Ok(())
 }
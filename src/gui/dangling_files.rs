@@ -0,0 +1,238 @@
+use actix::prelude::*;
+use gtk::prelude::*;
+
+use hashbrown::HashMap;
+
+use crate::files_discovery::DanglingFile;
+use crate::util::db;
+
+#[derive(typed_builder::TypedBuilder)]
+pub struct DanglingFilesActor {
+    factories: crate::gui::Factories,
+    widgets: DanglingFilesWindowWidgets,
+    main_app: actix::Addr<crate::gui::main_app::MainAppActor>,
+    dangling_files: Vec<DanglingFile>,
+    #[builder(setter(skip), default)]
+    rows: HashMap<i64, DanglingFileRow>,
+}
+
+#[derive(woab::WidgetsFromBuilder)]
+pub struct DanglingFilesWindowWidgets {
+    win_dangling_files: gtk::Window,
+    lst_dangling_files: gtk::ListBox,
+    btn_delete_all_dangling_files: gtk::Button,
+}
+
+struct DanglingFileRow {
+    model: DanglingFile,
+    widgets: DanglingFileWidgets,
+}
+
+#[derive(woab::WidgetsFromBuilder)]
+struct DanglingFileWidgets {
+    row_dangling_file: gtk::ListBoxRow,
+    lbl_dangling_episode: gtk::Label,
+    txt_dangling_file: gtk::Entry,
+    lbl_dangling_status: gtk::Label,
+}
+
+impl actix::Actor for DanglingFilesActor {
+    type Context = actix::Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.widgets.win_dangling_files.show();
+        for dangling_file in core::mem::take(&mut self.dangling_files) {
+            let widgets: DanglingFileWidgets = self
+                .factories
+                .row_dangling_file
+                .instantiate()
+                .connect_to((dangling_file.episode.id, ctx.address()))
+                .widgets()
+                .unwrap();
+            widgets.lbl_dangling_episode.set_text(&format!(
+                "{}: {}",
+                dangling_file.series_name, dangling_file.episode.name
+            ));
+            widgets
+                .txt_dangling_file
+                .set_text(&dangling_file.episode.file);
+            widgets.lbl_dangling_status.set_text(dangling_file.status());
+            self.widgets
+                .lst_dangling_files
+                .add(&widgets.row_dangling_file);
+            self.rows.insert(
+                dangling_file.episode.id,
+                DanglingFileRow {
+                    model: dangling_file,
+                    widgets,
+                },
+            );
+        }
+        self.update_delete_all_button();
+    }
+}
+
+impl DanglingFilesActor {
+    fn update_delete_all_button(&self) {
+        let models: Vec<_> = self.rows.values().map(|row| &row.model).collect();
+        let verdict = if models.is_empty() {
+            Err("no dangling files left".to_owned())
+        } else if models.iter().any(|model| model.base_dir_missing) {
+            Err("some base directories are missing - is a drive unmounted?".to_owned())
+        } else {
+            Ok(())
+        };
+        let button = &self.widgets.btn_delete_all_dangling_files;
+        match verdict {
+            Ok(()) => {
+                button.set_sensitive(true);
+                button.set_tooltip_text(None);
+            }
+            Err(reason) => {
+                button.set_sensitive(false);
+                button.set_tooltip_text(Some(&reason));
+            }
+        }
+    }
+
+    fn remove_rows(&mut self, episode_ids: impl IntoIterator<Item = i64>) {
+        for episode_id in episode_ids {
+            if let Some(row) = self.rows.remove(&episode_id) {
+                self.widgets
+                    .lst_dangling_files
+                    .remove(&row.widgets.row_dangling_file);
+            }
+        }
+        self.update_delete_all_button();
+    }
+
+    fn notify_main_app(&self) {
+        self.main_app.do_send(crate::gui::msgs::UpdateSeriesesList);
+        self.main_app
+            .do_send(crate::gui::msgs::RefreshLinksDirectory);
+    }
+}
+
+impl actix::Handler<woab::Signal> for DanglingFilesActor {
+    type Result = woab::SignalResult;
+
+    fn handle(&mut self, msg: woab::Signal, ctx: &mut Self::Context) -> Self::Result {
+        Ok(match msg.name() {
+            "delete_all_dangling_files" => {
+                let episode_ids: Vec<i64> = self.rows.keys().copied().collect();
+                let dialog = gtk::MessageDialog::new(
+                    Some(&self.widgets.win_dangling_files),
+                    gtk::DialogFlags::MODAL,
+                    gtk::MessageType::Warning,
+                    gtk::ButtonsType::YesNo,
+                    &format!(
+                        "Are you sure you want to delete {} episodes?",
+                        episode_ids.len()
+                    ),
+                );
+                ctx.spawn(
+                    async move {
+                        let result = woab::run_dialog(&dialog, true).await;
+                        if result != gtk::ResponseType::Yes {
+                            return None;
+                        }
+                        let mut con = db::request_connection().await.unwrap();
+                        crate::files_discovery::remove_episodes(&mut con, episode_ids.clone())
+                            .await
+                            .unwrap();
+                        Some(episode_ids)
+                    }
+                    .into_actor(self)
+                    .map(|episode_ids, actor, _ctx| {
+                        if let Some(episode_ids) = episode_ids {
+                            actor.remove_rows(episode_ids);
+                            actor.notify_main_app();
+                        }
+                    }),
+                );
+                None
+            }
+            _ => msg.cant_handle()?,
+        })
+    }
+}
+
+impl actix::Handler<woab::Signal<i64>> for DanglingFilesActor {
+    type Result = woab::SignalResult;
+
+    fn handle(&mut self, msg: woab::Signal<i64>, ctx: &mut Self::Context) -> Self::Result {
+        let episode_id = *msg.tag();
+        let row = &self.rows[&episode_id];
+        Ok(match msg.name() {
+            "delete_dangling_file" => {
+                ctx.spawn(
+                    async move {
+                        let mut con = db::request_connection().await.unwrap();
+                        crate::files_discovery::remove_episodes(&mut con, [episode_id])
+                            .await
+                            .unwrap();
+                    }
+                    .into_actor(self)
+                    .map(move |(), actor, _ctx| {
+                        actor.remove_rows([episode_id]);
+                        actor.notify_main_app();
+                    }),
+                );
+                None
+            }
+            "relink_dangling_file" => {
+                let dialog = gtk::FileChooserDialog::with_buttons(
+                    Some("Relink episode"),
+                    Some(&self.widgets.win_dangling_files),
+                    gtk::FileChooserAction::Open,
+                    &[
+                        ("_Cancel", gtk::ResponseType::Cancel),
+                        ("_Select", gtk::ResponseType::Accept),
+                    ],
+                );
+                if let Some(directory) = std::path::Path::new(&row.model.episode.file).parent() {
+                    if directory.is_dir() {
+                        dialog.set_current_folder(directory);
+                    }
+                }
+                ctx.spawn(
+                    async move {
+                        let result = woab::run_dialog(&dialog, false).await;
+                        let filename = dialog.filename();
+                        dialog.close();
+                        let (gtk::ResponseType::Accept, Some(filename)) = (result, filename) else {
+                            return false;
+                        };
+                        let mut con = db::request_connection().await.unwrap();
+                        match crate::files_discovery::relink_episode(
+                            &mut con,
+                            episode_id,
+                            &filename.to_string_lossy(),
+                        )
+                        .await
+                        {
+                            Ok(()) => true,
+                            Err(err) => {
+                                log::error!("Cannot relink episode {}: {}", episode_id, err);
+                                false
+                            }
+                        }
+                    }
+                    .into_actor(self)
+                    .map(move |did_relink, actor, _ctx| {
+                        if did_relink {
+                            actor.remove_rows([episode_id]);
+                            actor.notify_main_app();
+                        }
+                    }),
+                );
+                None
+            }
+            "ignore_dangling_file" => {
+                self.remove_rows([episode_id]);
+                None
+            }
+            _ => msg.cant_handle()?,
+        })
+    }
+}
@@ -15,6 +15,8 @@ pub struct DirectoryActor {
     series: actix::Addr<crate::gui::series::SeriesActor>,
     #[builder(setter(skip), default)]
     preview_unfiltered_paths: Vec<String>,
+    #[builder(setter(skip), default)]
+    preview_file_types_filter: crate::files_discovery::FileTypesFilter,
 }
 
 #[derive(woab::WidgetsFromBuilder, woab::PropSync)]
@@ -226,6 +228,9 @@ impl actix::Handler<woab::Signal> for DirectoryActor {
                             if let Some(result) = result {
                                 actor.model = result;
                                 actor.update_widgets_from_model();
+                                actor
+                                    .series
+                                    .do_send(crate::gui::msgs::UpdateWatchedDirectories);
                             }
                             futures::future::ready(())
                         }),
@@ -245,6 +250,7 @@ impl actix::Handler<woab::Signal> for DirectoryActor {
                 );
                 let directory_id = self.model.id;
                 let addr = ctx.address();
+                let series = self.series.clone();
                 ctx.spawn(
                     async move {
                         let result = woab::run_dialog(&dialog, true).await;
@@ -261,6 +267,7 @@ impl actix::Handler<woab::Signal> for DirectoryActor {
                             let mut con = db::request_connection().await.unwrap();
                             query.execute(con.acquire().await.unwrap()).await.unwrap();
                         }
+                        series.do_send(crate::gui::msgs::UpdateWatchedDirectories);
                         addr.send(woab::Remove).await.unwrap();
                     }
                     .into_actor(self),
@@ -303,6 +310,9 @@ impl actix::Handler<crate::util::edit_mode::InitiateSave> for DirectoryActor {
             chk_directory_recursive,
         } = self.widgets.get_props();
         Box::pin(async move {
+            let mut con = db::request_connection().await?;
+            let base_dir = crate::library_root::series_base_dir(&mut con, series_id).await?;
+            let txt_directory_dir = crate::library_root::to_stored(&base_dir, &txt_directory_dir);
             if directory_id < 0 {
                 let query = sqlx::query(r#"
                     INSERT INTO directories(series, pattern, dir, volume, recursive) VALUES (?, ?, ?, ?, ?)
@@ -316,7 +326,6 @@ impl actix::Handler<crate::util::edit_mode::InitiateSave> for DirectoryActor {
                         Some(txt_directory_volume.parse::<i64>()?)
                     })
                     .bind(chk_directory_recursive);
-                let mut con = db::request_connection().await?;
                 let query_result = query.execute(con.acquire().await?).await?;
                 Ok(query_result.last_insert_rowid())
             } else {
@@ -337,7 +346,6 @@ impl actix::Handler<crate::util::edit_mode::InitiateSave> for DirectoryActor {
                     })
                     .bind(chk_directory_recursive)
                     .bind(directory_id);
-                let mut con = db::request_connection().await?;
                 let query_result = query.execute(con.acquire().await?).await?;
                 if query_result.rows_affected() == 0 {
                     anyhow::bail!("Affected 0 directories with id={}", directory_id);
@@ -385,6 +393,9 @@ impl actix::Handler<crate::gui::msgs::InitiateNewRowSequence> for DirectoryActor
                     if let Some(result) = result {
                         actor.model = result;
                         actor.update_widgets_from_model();
+                        actor
+                            .series
+                            .do_send(crate::gui::msgs::UpdateWatchedDirectories);
                         actor
                             .series
                             .do_send(crate::gui::msgs::RegisterActorAfterNew {
@@ -414,17 +425,50 @@ impl actix::StreamHandler<PreviewEvent> for DirectoryActor {
                     txt_directory_volume: _,
                     chk_directory_recursive,
                 } = self.widgets.get_props();
+                let series = self.series.clone();
                 ctx.spawn(
                     async move {
+                        let file_types_filter = match series
+                            .send(crate::gui::msgs::GetFileTypesFilterForMediaType)
+                            .await
+                            .unwrap()
+                        {
+                            Ok(file_types_filter) => file_types_filter,
+                            Err(err) => {
+                                log::warn!("Cannot find file types: {}", err);
+                                Default::default()
+                            }
+                        };
+                        let base_dir = match series
+                            .send(crate::gui::msgs::GetBaseDirForMediaType)
+                            .await
+                            .unwrap()
+                        {
+                            Ok(base_dir) => base_dir,
+                            Err(err) => {
+                                log::warn!("Cannot find base dir: {}", err);
+                                "".to_owned()
+                            }
+                        };
+                        let txt_directory_dir =
+                            crate::library_root::resolve(&base_dir, &txt_directory_dir);
                         let mut con = db::request_connection().await.unwrap();
+                        let known_files =
+                            match crate::files_discovery::load_known_files(&mut con).await {
+                                Ok(known_files) => known_files,
+                                Err(err) => {
+                                    log::warn!("Cannot load known files: {}", err);
+                                    return None;
+                                }
+                            };
                         match crate::files_discovery::discover_in_path(
-                            &mut con,
                             &txt_directory_dir,
                             chk_directory_recursive,
+                            &known_files,
                         )
                         .await
                         {
-                            Ok(paths) => Some(paths),
+                            Ok(paths) => Some((paths, file_types_filter)),
                             Err(err) => {
                                 log::warn!(
                                     "Cannot discover files in {:?} - {}",
@@ -436,13 +480,14 @@ impl actix::StreamHandler<PreviewEvent> for DirectoryActor {
                         }
                     }
                     .into_actor(self)
-                    .map(|paths, actor, _ctx| {
-                        let paths = if let Some(paths) = paths {
-                            paths
+                    .map(|result, actor, _ctx| {
+                        let (paths, file_types_filter) = if let Some(result) = result {
+                            result
                         } else {
                             return;
                         };
                         actor.preview_unfiltered_paths = paths;
+                        actor.preview_file_types_filter = file_types_filter;
                         actor.apply_pattern_to_preview();
                     }),
                 );
@@ -467,14 +512,24 @@ impl DirectoryActor {
         let lsm = &self.widgets.lsm_directory_scan_preview;
         lsm.clear();
         for path in self.preview_unfiltered_paths.iter() {
+            if !self.preview_file_types_filter.accepts(path) {
+                continue;
+            }
             if let Ok(decision) = crate::files_discovery::process_file_match(path, &regex) {
                 let it = lsm.append();
                 lsm.set_value(&it, 0, &path.to_value());
-                if let Some(crate::files_discovery::FileData { volume, chapter }) = decision {
+                if let Some(crate::files_discovery::FileData {
+                    volume,
+                    chapter,
+                    chapter_end,
+                }) = decision
+                {
                     if let Some(volume) = volume {
                         lsm.set_value(&it, 2, &volume.to_string().to_value());
                     }
-                    lsm.set_value(&it, 1, &chapter.to_string().to_value());
+                    let chapters =
+                        crate::chapter_number::format_range(&chapter, chapter_end.as_ref());
+                    lsm.set_value(&it, 1, &chapters.to_value());
                 }
             }
         }
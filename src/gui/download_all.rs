@@ -0,0 +1,186 @@
+use actix::prelude::*;
+use gtk::prelude::*;
+use hashbrown::HashMap;
+use sqlx::prelude::*;
+
+use crate::downloads::{DownloadProgress, DownloadState};
+use crate::util::db;
+
+#[derive(typed_builder::TypedBuilder)]
+pub struct DownloadAllActor {
+    widgets: DownloadAllWidgets,
+    main_app: actix::Addr<crate::gui::main_app::MainAppActor>,
+    #[builder(setter(skip), default)]
+    rows: HashMap<i64, gtk::TreeIter>,
+}
+
+#[derive(woab::WidgetsFromBuilder)]
+pub struct DownloadAllWidgets {
+    win_download_all: gtk::Window,
+    cbo_download_all_media_type: gtk::ComboBoxText,
+    spn_download_all_concurrency: gtk::SpinButton,
+    lbl_download_all_summary: gtk::Label,
+    spn_download_all: gtk::Spinner,
+    btn_download_all_start: gtk::Button,
+    lsm_download_all: gtk::ListStore,
+}
+
+impl actix::Actor for DownloadAllActor {
+    type Context = actix::Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.widgets
+            .spn_download_all_concurrency
+            .set_value(crate::downloads::DEFAULT_CONCURRENCY as f64);
+        self.widgets
+            .cbo_download_all_media_type
+            .append(Some(""), "All media types");
+        self.widgets
+            .cbo_download_all_media_type
+            .set_active_id(Some(""));
+        ctx.spawn(
+            async {
+                let mut con = db::request_connection().await?;
+                Ok::<Vec<(i64, String)>, anyhow::Error>(
+                    sqlx::query_as("SELECT id, name FROM media_types ORDER BY name")
+                        .fetch_all(con.acquire().await?)
+                        .await?,
+                )
+            }
+            .into_actor(self)
+            .map(|media_types, actor, _ctx| match media_types {
+                Ok(media_types) => {
+                    for (id, name) in media_types {
+                        actor
+                            .widgets
+                            .cbo_download_all_media_type
+                            .append(Some(&id.to_string()), &name);
+                    }
+                }
+                Err(err) => log::error!("Cannot load media types: {}", err),
+            }),
+        );
+        self.widgets.win_download_all.show();
+    }
+}
+
+impl actix::Handler<woab::Signal> for DownloadAllActor {
+    type Result = woab::SignalResult;
+
+    fn handle(&mut self, msg: woab::Signal, ctx: &mut Self::Context) -> Self::Result {
+        Ok(match msg.name() {
+            "start_download_all" => {
+                self.start(ctx);
+                None
+            }
+            _ => msg.cant_handle()?,
+        })
+    }
+}
+
+impl DownloadAllActor {
+    fn start(&mut self, ctx: &mut actix::Context<Self>) {
+        let media_type = self
+            .widgets
+            .cbo_download_all_media_type
+            .active_id()
+            .and_then(|id| id.parse().ok());
+        let concurrency = self.widgets.spn_download_all_concurrency.value_as_int() as usize;
+
+        self.rows.clear();
+        self.widgets.lsm_download_all.clear();
+        self.widgets.lbl_download_all_summary.set_text("");
+        self.widgets.btn_download_all_start.set_sensitive(false);
+        self.widgets.spn_download_all.start();
+
+        let (progress_tx, progress_rx) = tokio::sync::mpsc::unbounded_channel();
+        ctx.add_stream(tokio_stream::wrappers::UnboundedReceiverStream::new(
+            progress_rx,
+        ));
+        ctx.spawn(
+            crate::actors::DownloadRunner::from_registry()
+                .send(crate::msgs::RunAllDownloadCommands {
+                    media_type,
+                    concurrency,
+                    timeout: crate::downloads::DEFAULT_TIMEOUT,
+                    progress: progress_tx,
+                })
+                .into_actor(self)
+                .map(|result, actor, _ctx| {
+                    actor.widgets.btn_download_all_start.set_sensitive(true);
+                    actor.widgets.spn_download_all.stop();
+                    match result.unwrap() {
+                        Ok(new_episodes) => {
+                            actor
+                                .main_app
+                                .do_send(crate::gui::msgs::ShowNewEpisodes(new_episodes));
+                        }
+                        Err(err) => {
+                            log::error!("Cannot run the download commands: {}", err);
+                            actor
+                                .widgets
+                                .lbl_download_all_summary
+                                .set_text(&err.to_string());
+                        }
+                    }
+                    actor
+                        .main_app
+                        .do_send(crate::gui::msgs::RefreshLinksDirectory);
+                    actor.main_app.do_send(crate::gui::msgs::UpdateSeriesesList);
+                }),
+        );
+    }
+
+    fn update_summary(&self) {
+        let mut num_running = 0;
+        let mut num_succeeded = 0;
+        let mut num_failed = 0;
+        let lsm = &self.widgets.lsm_download_all;
+        for it in self.rows.values() {
+            match lsm.value(it, 2).get::<i32>().unwrap() {
+                STATE_RUNNING => num_running += 1,
+                STATE_SUCCEEDED => num_succeeded += 1,
+                STATE_FAILED => num_failed += 1,
+                _ => {}
+            }
+        }
+        self.widgets.lbl_download_all_summary.set_text(&format!(
+            "{}/{} done, {} running, {} failed",
+            num_succeeded + num_failed,
+            self.rows.len(),
+            num_running,
+            num_failed,
+        ));
+    }
+}
+
+const STATE_QUEUED: i32 = 0;
+const STATE_RUNNING: i32 = 1;
+const STATE_SUCCEEDED: i32 = 2;
+const STATE_FAILED: i32 = 3;
+
+impl StreamHandler<DownloadProgress> for DownloadAllActor {
+    fn handle(&mut self, progress: DownloadProgress, _ctx: &mut Self::Context) {
+        let lsm = &self.widgets.lsm_download_all;
+        let it = *self.rows.entry(progress.series).or_insert_with(|| {
+            let it = lsm.append();
+            lsm.set_value(&it, 0, &progress.series_name.to_value());
+            it
+        });
+        let state = match &progress.state {
+            DownloadState::Queued => STATE_QUEUED,
+            DownloadState::Running => STATE_RUNNING,
+            DownloadState::Finished(download_run) if download_run.succeeded() => STATE_SUCCEEDED,
+            DownloadState::Finished(_) | DownloadState::Failed(_) => STATE_FAILED,
+        };
+        lsm.set_value(&it, 1, &progress.state.describe().to_value());
+        lsm.set_value(&it, 2, &state.to_value());
+        self.update_summary();
+        self.main_app
+            .do_send(crate::gui::msgs::ShowDownloadProgress(progress));
+    }
+
+    fn finished(&mut self, _ctx: &mut Self::Context) {
+        // Don't stop the actor - the window stays open for more batches.
+    }
+}
@@ -0,0 +1,183 @@
+use actix::prelude::*;
+use gtk::prelude::*;
+
+use hashbrown::HashMap;
+
+use crate::duplicates::{DuplicateGroup, RedundantFileAction};
+use crate::util::db;
+
+#[derive(typed_builder::TypedBuilder)]
+pub struct DuplicatesActor {
+    factories: crate::gui::Factories,
+    widgets: DuplicatesWindowWidgets,
+    main_app: actix::Addr<crate::gui::main_app::MainAppActor>,
+    groups: Vec<DuplicateGroup>,
+    #[builder(setter(skip), default)]
+    rows: HashMap<i64, DuplicateEpisodeRow>,
+}
+
+#[derive(woab::WidgetsFromBuilder)]
+pub struct DuplicatesWindowWidgets {
+    win_duplicates: gtk::Window,
+    lst_duplicates: gtk::ListBox,
+    cbo_redundant_file_action: gtk::ComboBoxText,
+}
+
+struct DuplicateEpisodeRow {
+    /// Index in `groups`.
+    group: usize,
+    widgets: DuplicateEpisodeWidgets,
+}
+
+#[derive(woab::WidgetsFromBuilder)]
+struct DuplicateEpisodeWidgets {
+    row_duplicate_episode: gtk::ListBoxRow,
+    lbl_duplicate_episode: gtk::Label,
+    txt_duplicate_file: gtk::Entry,
+    lbl_duplicate_read_state: gtk::Label,
+}
+
+impl actix::Actor for DuplicatesActor {
+    type Context = actix::Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.widgets.win_duplicates.show();
+        for (group_index, group) in self.groups.iter().enumerate() {
+            for duplicate in group.episodes.iter() {
+                let widgets: DuplicateEpisodeWidgets = self
+                    .factories
+                    .row_duplicate_episode
+                    .instantiate()
+                    .connect_to((duplicate.episode.id, ctx.address()))
+                    .widgets()
+                    .unwrap();
+                widgets.lbl_duplicate_episode.set_text(&format!(
+                    "{}: {} ({})",
+                    duplicate.series_name,
+                    duplicate.episode.name,
+                    group.reason.description()
+                ));
+                widgets.txt_duplicate_file.set_text(&duplicate.episode.file);
+                widgets.lbl_duplicate_read_state.set_text(&match (
+                    &duplicate.episode.date_of_read,
+                    duplicate.num_reads,
+                ) {
+                    (None, _) => "unread".to_owned(),
+                    (Some(_), 0 | 1) => "read".to_owned(),
+                    (Some(_), num_reads) => format!("read ×{}", num_reads),
+                });
+                self.widgets
+                    .lst_duplicates
+                    .add(&widgets.row_duplicate_episode);
+                self.rows.insert(
+                    duplicate.episode.id,
+                    DuplicateEpisodeRow {
+                        group: group_index,
+                        widgets,
+                    },
+                );
+            }
+        }
+    }
+}
+
+impl DuplicatesActor {
+    fn group_episode_ids(&self, group_index: usize) -> Vec<i64> {
+        self.groups[group_index]
+            .episodes
+            .iter()
+            .map(|duplicate| duplicate.episode.id)
+            .filter(|episode_id| self.rows.contains_key(episode_id))
+            .collect()
+    }
+
+    fn remove_rows(&mut self, episode_ids: impl IntoIterator<Item = i64>) {
+        for episode_id in episode_ids {
+            if let Some(row) = self.rows.remove(&episode_id) {
+                self.widgets
+                    .lst_duplicates
+                    .remove(&row.widgets.row_duplicate_episode);
+            }
+        }
+    }
+
+    fn notify_main_app(&self) {
+        self.main_app.do_send(crate::gui::msgs::UpdateSeriesesList);
+        self.main_app
+            .do_send(crate::gui::msgs::RefreshLinksDirectory);
+    }
+}
+
+impl actix::Handler<woab::Signal<i64>> for DuplicatesActor {
+    type Result = woab::SignalResult;
+
+    fn handle(&mut self, msg: woab::Signal<i64>, ctx: &mut Self::Context) -> Self::Result {
+        let episode_id = *msg.tag();
+        Ok(match msg.name() {
+            "keep_duplicate" => {
+                let group_episode_ids = self.group_episode_ids(self.rows[&episode_id].group);
+                let redundant_ids: Vec<i64> = group_episode_ids
+                    .iter()
+                    .copied()
+                    .filter(|&id| id != episode_id)
+                    .collect();
+                let file_action = self
+                    .widgets
+                    .cbo_redundant_file_action
+                    .active_id()
+                    .and_then(|action| action.parse().ok())
+                    .unwrap_or(RedundantFileAction::Keep);
+                let dialog = (file_action == RedundantFileAction::Delete).then(|| {
+                    gtk::MessageDialog::new(
+                        Some(&self.widgets.win_duplicates),
+                        gtk::DialogFlags::MODAL,
+                        gtk::MessageType::Warning,
+                        gtk::ButtonsType::YesNo,
+                        &format!(
+                            "Are you sure you want to permanently delete {} files?",
+                            redundant_ids.len()
+                        ),
+                    )
+                });
+                ctx.spawn(
+                    async move {
+                        if let Some(dialog) = dialog {
+                            if woab::run_dialog(&dialog, true).await != gtk::ResponseType::Yes {
+                                return false;
+                            }
+                        }
+                        let mut con = db::request_connection().await.unwrap();
+                        match crate::duplicates::merge_duplicates(
+                            &mut con,
+                            episode_id,
+                            &redundant_ids,
+                            file_action,
+                        )
+                        .await
+                        {
+                            Ok(failures) => {
+                                for (file, reason) in failures {
+                                    log::error!("{:?} is now ignored - {}", file, reason);
+                                }
+                                true
+                            }
+                            Err(err) => {
+                                log::error!("Cannot merge duplicates of {}: {}", episode_id, err);
+                                false
+                            }
+                        }
+                    }
+                    .into_actor(self)
+                    .map(move |did_merge, actor, _ctx| {
+                        if did_merge {
+                            actor.remove_rows(group_episode_ids);
+                            actor.notify_main_app();
+                        }
+                    }),
+                );
+                None
+            }
+            _ => msg.cant_handle()?,
+        })
+    }
+}
@@ -0,0 +1,244 @@
+use std::path::PathBuf;
+
+use actix::prelude::*;
+use futures::TryStreamExt;
+use hashbrown::HashMap;
+use notify::Watcher;
+use sqlx::prelude::*;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use crate::gui;
+use crate::util::db;
+
+/// How long to wait after a file appears before scanning, so that a batch of files landing
+/// together (or a file that is still being renamed into place) triggers a single scan.
+const SCAN_DELAY: core::time::Duration = core::time::Duration::from_secs(2);
+
+/// How often to retry watching directories that could not be watched - e.g. because they are on a
+/// drive that is not mounted yet.
+const RETRY_WATCH_INTERVAL: core::time::Duration = core::time::Duration::from_secs(60);
+
+#[derive(typed_builder::TypedBuilder)]
+pub struct FilesWatcherActor {
+    main_app: actix::Addr<gui::main_app::MainAppActor>,
+    #[builder(setter(skip), default)]
+    watcher: Option<notify::RecommendedWatcher>,
+    #[builder(setter(skip), default)]
+    watched_dirs: HashMap<PathBuf, bool>,
+    /// Directories that should be watched but could not be, and whether they are recursive.
+    #[builder(setter(skip), default)]
+    failed_dirs: HashMap<PathBuf, bool>,
+    #[builder(setter(skip), default)]
+    scan_scheduled: bool,
+    #[builder(setter(skip), default)]
+    scan_in_progress: bool,
+}
+
+impl actix::Actor for FilesWatcherActor {
+    type Context = actix::Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        match notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        }) {
+            Ok(watcher) => {
+                self.watcher = Some(watcher);
+                ctx.add_stream(UnboundedReceiverStream::new(rx));
+                ctx.address().do_send(gui::msgs::UpdateWatchedDirectories);
+                ctx.run_interval(RETRY_WATCH_INTERVAL, |actor, ctx| {
+                    actor.retry_failed_dirs(ctx);
+                });
+            }
+            Err(err) => {
+                log::error!("Cannot create files watcher: {}", err);
+            }
+        }
+    }
+}
+
+impl actix::Handler<gui::msgs::UpdateWatchedDirectories> for FilesWatcherActor {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        _msg: gui::msgs::UpdateWatchedDirectories,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
+        ctx.spawn(
+            async move {
+                let mut con = db::request_connection().await?;
+                let mut desired_dirs = HashMap::<PathBuf, bool>::new();
+                sqlx::query_as::<_, (String, String, bool)>(
+                    r#"
+                    SELECT media_types.base_dir, directories.dir, directories.recursive
+                    FROM directories
+                    INNER JOIN serieses ON serieses.id = directories.series
+                    INNER JOIN media_types ON media_types.id = serieses.media_type
+                    "#,
+                )
+                .fetch(con.acquire().await?)
+                .try_for_each(|(base_dir, dir, recursive)| {
+                    let dir = crate::library_root::resolve(&base_dir, &dir);
+                    *desired_dirs.entry(PathBuf::from(dir)).or_default() |= recursive;
+                    futures::future::ready(Ok(()))
+                })
+                .await?;
+                Ok::<_, anyhow::Error>(desired_dirs)
+            }
+            .into_actor(self)
+            .map(|desired_dirs, actor, _ctx| {
+                let desired_dirs = match desired_dirs {
+                    Ok(desired_dirs) => desired_dirs,
+                    Err(err) => {
+                        log::error!("Cannot read directories to watch: {}", err);
+                        return;
+                    }
+                };
+                actor.update_watched_dirs(desired_dirs);
+            }),
+        );
+    }
+}
+
+impl FilesWatcherActor {
+    fn update_watched_dirs(&mut self, desired_dirs: HashMap<PathBuf, bool>) {
+        let Some(watcher) = self.watcher.as_mut() else {
+            return;
+        };
+        self.watched_dirs.retain(|dir, recursive| {
+            if desired_dirs.get(dir) == Some(recursive) {
+                return true;
+            }
+            if let Err(err) = watcher.unwatch(dir) {
+                log::warn!("Cannot stop watching {:?}: {}", dir, err);
+            }
+            false
+        });
+        self.failed_dirs.clear();
+        for (dir, recursive) in desired_dirs {
+            if self.watched_dirs.contains_key(&dir) {
+                continue;
+            }
+            match watch_dir(watcher, &dir, recursive) {
+                Ok(()) => {
+                    self.watched_dirs.insert(dir, recursive);
+                }
+                Err(err) => {
+                    log::warn!("Cannot watch {:?} - will retry: {}", dir, err);
+                    self.failed_dirs.insert(dir, recursive);
+                }
+            }
+        }
+    }
+
+    fn retry_failed_dirs(&mut self, ctx: &mut actix::Context<Self>) {
+        let Some(watcher) = self.watcher.as_mut() else {
+            return;
+        };
+        let watched_dirs = &mut self.watched_dirs;
+        let mut any_watched = false;
+        self.failed_dirs
+            .retain(|dir, recursive| match watch_dir(watcher, dir, *recursive) {
+                Ok(()) => {
+                    watched_dirs.insert(dir.clone(), *recursive);
+                    any_watched = true;
+                    false
+                }
+                Err(err) => {
+                    log::debug!("Still cannot watch {:?}: {}", dir, err);
+                    true
+                }
+            });
+        if any_watched {
+            // Files may have been added before the directory could be watched.
+            self.schedule_scan(ctx);
+        }
+    }
+
+    fn schedule_scan(&mut self, ctx: &mut actix::Context<Self>) {
+        if self.scan_scheduled {
+            return;
+        }
+        self.scan_scheduled = true;
+        ctx.run_later(SCAN_DELAY, |actor, ctx| {
+            if actor.scan_in_progress {
+                // Wait for the current scan to end, so that files landing during it won't queue up
+                // more scans.
+                actor.scan_scheduled = false;
+                actor.schedule_scan(ctx);
+                return;
+            }
+            actor.scan_scheduled = false;
+            actor.scan_in_progress = true;
+            ctx.spawn(
+                crate::actors::DbActor::from_registry()
+                    .send(crate::msgs::ScanFiles { series: None })
+                    .into_actor(actor)
+                    .map(|result, actor, _ctx| {
+                        actor.scan_in_progress = false;
+                        match result.unwrap() {
+                            Ok(new_episodes) if new_episodes.is_empty() => {}
+                            Ok(new_episodes) => {
+                                log::info!(
+                                    "Registered {} new files",
+                                    new_episodes
+                                        .iter()
+                                        .map(|new_episodes| new_episodes.episodes.len())
+                                        .sum::<usize>()
+                                );
+                                actor
+                                    .main_app
+                                    .do_send(gui::msgs::ShowNewEpisodes(new_episodes));
+                                actor.main_app.do_send(gui::msgs::UpdateSeriesesList);
+                                actor.main_app.do_send(gui::msgs::RefreshLinksDirectory);
+                            }
+                            Err(err) => {
+                                log::error!("Cannot scan for new files: {}", err);
+                            }
+                        }
+                    }),
+            );
+        });
+    }
+}
+
+fn watch_dir(
+    watcher: &mut notify::RecommendedWatcher,
+    dir: &std::path::Path,
+    recursive: bool,
+) -> notify::Result<()> {
+    let recursive_mode = if recursive {
+        notify::RecursiveMode::Recursive
+    } else {
+        notify::RecursiveMode::NonRecursive
+    };
+    watcher.watch(dir, recursive_mode)?;
+    log::debug!("Watching {:?} (recursive = {})", dir, recursive);
+    Ok(())
+}
+
+impl actix::StreamHandler<notify::Result<notify::Event>> for FilesWatcherActor {
+    fn handle(&mut self, item: notify::Result<notify::Event>, ctx: &mut Self::Context) {
+        use notify::event::{EventKind, ModifyKind, RenameMode};
+        let event = match item {
+            Ok(event) => event,
+            Err(err) => {
+                log::warn!("Files watcher error: {}", err);
+                return;
+            }
+        };
+        match event.kind {
+            EventKind::Create(_)
+            | EventKind::Modify(ModifyKind::Name(
+                RenameMode::To | RenameMode::Both | RenameMode::Any,
+            )) => {
+                log::debug!("New files: {:?}", event.paths);
+                self.schedule_scan(ctx);
+            }
+            _ => {}
+        }
+    }
+
+    fn finished(&mut self, _ctx: &mut Self::Context) {}
+}
@@ -1,50 +1,101 @@
 use std::path::PathBuf;
 
 use actix::prelude::*;
+use sqlx::prelude::*;
 
 use tokio::fs;
 
-use crate::links_handling::refresh_links_directory;
+use crate::link_mode::LinkMode;
+use crate::links_handling::{refresh_links_directory, LinksFilter, LinksRefreshReport};
+use crate::models;
 use crate::util::db;
 
+/// Where a links directory maintainer gets its directory and filter from.
+#[derive(Debug, Clone)]
+pub enum LinksDirectorySource {
+    /// A directory given on the command line, linking all the media types that maintain symlinks.
+    Path(PathBuf, LinkMode),
+    /// A links directory configured in the database. Reloaded on every refresh.
+    Database(i64),
+}
+
 #[derive(typed_builder::TypedBuilder)]
 pub struct LinksDirectoryMaintainer {
-    dir_path: PathBuf,
+    source: LinksDirectorySource,
+}
+
+async fn load_source(
+    con: &mut crate::SqlitePoolConnection,
+    source: &LinksDirectorySource,
+) -> anyhow::Result<Option<(PathBuf, LinksFilter, LinkMode)>> {
+    Ok(match source {
+        LinksDirectorySource::Path(dir_path, link_mode) => {
+            Some((dir_path.clone(), LinksFilter::default(), *link_mode))
+        }
+        LinksDirectorySource::Database(id) => {
+            let links_directory: Option<models::LinksDirectory> =
+                sqlx::query_as("SELECT * FROM links_directories WHERE id = ?")
+                    .bind(id)
+                    .fetch_optional(con.acquire().await?)
+                    .await?;
+            if let Some(links_directory) = links_directory {
+                let filter = LinksFilter::load(con, &links_directory).await?;
+                Some((
+                    links_directory.dir.into(),
+                    filter,
+                    links_directory.link_mode,
+                ))
+            } else {
+                None
+            }
+        }
+    })
 }
 
 impl actix::Actor for LinksDirectoryMaintainer {
     type Context = actix::Context<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
-        let dir_path = self.dir_path.clone();
-        ctx.spawn(
-            async move {
-                fs::create_dir_all(dir_path).await.unwrap();
-            }
-            .into_actor(self)
-            .map(|(), _actor, ctx| {
-                ctx.address()
-                    .do_send(crate::gui::msgs::RefreshLinksDirectory);
-            }),
-        );
+        ctx.address()
+            .do_send(crate::gui::msgs::RefreshLinksDirectory);
     }
 }
 
 impl actix::Handler<crate::gui::msgs::RefreshLinksDirectory> for LinksDirectoryMaintainer {
-    type Result = ();
+    type Result = ResponseActFuture<Self, anyhow::Result<Vec<(PathBuf, LinksRefreshReport)>>>;
 
     fn handle(
         &mut self,
         _msg: crate::gui::msgs::RefreshLinksDirectory,
-        ctx: &mut Self::Context,
+        _ctx: &mut Self::Context,
     ) -> Self::Result {
-        let dir_path = self.dir_path.clone();
-        ctx.spawn(
+        let source = self.source.clone();
+        Box::pin(
             async move {
-                let mut con = db::request_connection().await.unwrap();
-                refresh_links_directory(&mut con, &dir_path).await.unwrap();
+                let mut con = db::request_connection().await?;
+                let Some((dir_path, filter, link_mode)) = load_source(&mut con, &source).await?
+                else {
+                    return Ok(None);
+                };
+                fs::create_dir_all(&dir_path).await?;
+                let report =
+                    refresh_links_directory(&mut con, &dir_path, &filter, link_mode).await?;
+                report.log_problems(&dir_path);
+                anyhow::Ok(Some((dir_path, report)))
             }
-            .into_actor(self),
-        );
+            .into_actor(self)
+            .map(|result, actor, ctx| match result {
+                Ok(Some(refreshed)) => Ok(vec![refreshed]),
+                // The links directory was removed from the database.
+                Ok(None) => {
+                    ctx.stop();
+                    Ok(Vec::new())
+                }
+                Err(err) => {
+                    log::error!("Cannot refresh links directory {:?}: {}", actor.source, err);
+                    Err(err)
+                }
+            }),
+        )
     }
 }
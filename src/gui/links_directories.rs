@@ -0,0 +1,565 @@
+use actix::prelude::*;
+use gtk::prelude::*;
+
+use crate::gui::links_dir::LinksDirectorySource;
+use crate::link_mode::LinkMode;
+use crate::models;
+use crate::util::db;
+use crate::util::edit_mode::EditMode;
+
+use sqlx::prelude::*;
+
+const LINKS_DIRECTORIES_QUERY: &str = r#"
+    SELECT links_directories.*
+        , COALESCE((
+            SELECT group_concat(media_types.name, '; ')
+            FROM links_directory_media_types
+            INNER JOIN media_types ON media_types.id = links_directory_media_types.media_type
+            WHERE links_directory_media_types.links_directory = links_directories.id
+        ), '') AS media_type_names
+        , COALESCE((
+            SELECT group_concat(serieses.name, '; ')
+            FROM links_directory_serieses
+            INNER JOIN serieses ON serieses.id = links_directory_serieses.series
+            WHERE links_directory_serieses.links_directory = links_directories.id
+        ), '') AS series_names
+    FROM links_directories
+    WHERE ?1 IS NULL OR links_directories.id = ?1
+"#;
+
+/// Query all the links directories, or only the one with the given id.
+fn links_directories_query(
+    id: Option<i64>,
+) -> crate::SqliteQueryAs<'static, models::LinksDirectoryWithFilterNames> {
+    sqlx::query_as(LINKS_DIRECTORIES_QUERY).bind(id)
+}
+
+#[derive(typed_builder::TypedBuilder)]
+pub struct LinksDirectoriesActor {
+    factories: crate::gui::Factories,
+    widgets: LinksDirectoriesWindowWidgets,
+    main_app: actix::Addr<crate::gui::main_app::MainAppActor>,
+    #[builder(setter(skip), default)]
+    links_directories: hashbrown::HashMap<i64, LinksDirectoryRow>,
+}
+
+#[derive(woab::WidgetsFromBuilder)]
+pub struct LinksDirectoriesWindowWidgets {
+    win_links_directories: gtk::Window,
+    lst_links_directories: gtk::ListBox,
+}
+
+impl actix::Actor for LinksDirectoriesActor {
+    type Context = actix::Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.widgets.win_links_directories.show();
+
+        ctx.spawn(
+            crate::actors::DbActor::from_registry()
+                .send(crate::msgs::RefreshList {
+                    orig_ids: Default::default(),
+                    query: links_directories_query(None),
+                    id_dlg: |row_data: &models::LinksDirectoryWithFilterNames| {
+                        row_data.links_directory.id
+                    },
+                    addr: ctx.address(),
+                })
+                .into_actor(self)
+                .then(move |_, actor, ctx| {
+                    actor.add_new_entry_row(ctx);
+                    futures::future::ready(())
+                }),
+        );
+    }
+}
+
+impl actix::Handler<woab::Signal> for LinksDirectoriesActor {
+    type Result = woab::SignalResult;
+
+    fn handle(&mut self, msg: woab::Signal, _ctx: &mut Self::Context) -> Self::Result {
+        msg.cant_handle()
+    }
+}
+
+impl actix::Handler<crate::msgs::UpdateListRowData<models::LinksDirectoryWithFilterNames>>
+    for LinksDirectoriesActor
+{
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: crate::msgs::UpdateListRowData<models::LinksDirectoryWithFilterNames>,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
+        for data in msg.0 {
+            match self.links_directories.entry(data.links_directory.id) {
+                hashbrown::hash_map::Entry::Occupied(mut entry) => {
+                    let entry = entry.get_mut();
+                    if entry.model != data {
+                        entry.model = data;
+                        entry.update_widgets_from_model();
+                        entry.widgets.row_links_directory.changed();
+                    }
+                }
+                hashbrown::hash_map::Entry::Vacant(entry) => {
+                    let widgets: LinksDirectoryWidgets = self
+                        .factories
+                        .row_links_directory
+                        .instantiate()
+                        .connect_to((data.links_directory.id, ctx.address()))
+                        .widgets()
+                        .unwrap();
+                    let entry = entry.insert(LinksDirectoryRow {
+                        model: data,
+                        widgets,
+                    });
+                    entry.update_widgets_from_model();
+                    self.widgets
+                        .lst_links_directories
+                        .add(&entry.widgets.row_links_directory);
+                }
+            }
+        }
+    }
+}
+
+impl LinksDirectoriesActor {
+    fn add_new_entry_row(&mut self, ctx: &mut actix::Context<Self>) {
+        let data = models::LinksDirectoryWithFilterNames {
+            links_directory: models::LinksDirectory {
+                id: -1,
+                name: "".to_owned(),
+                dir: "".to_owned(),
+                max_unread_per_series: None,
+                link_mode: Default::default(),
+            },
+            media_type_names: "".to_owned(),
+            series_names: "".to_owned(),
+        };
+        let entry = if let hashbrown::hash_map::Entry::Vacant(entry) =
+            self.links_directories.entry(data.links_directory.id)
+        {
+            entry
+        } else {
+            return;
+        };
+        let widgets: LinksDirectoryWidgets = self
+            .factories
+            .row_links_directory
+            .instantiate()
+            .connect_to((data.links_directory.id, ctx.address()))
+            .widgets()
+            .unwrap();
+        let entry = entry.insert(LinksDirectoryRow {
+            model: data,
+            widgets,
+        });
+        entry.update_widgets_from_model();
+        self.widgets
+            .lst_links_directories
+            .add(&entry.widgets.row_links_directory);
+        ctx.spawn(
+            entry
+                .add_verifications_to_edit_mode(
+                    EditMode::builder()
+                        .stack(entry.widgets.stk_links_directory_edit.clone())
+                        .stack_page("new")
+                        .save_button(entry.widgets.btn_save_new_links_directory.clone())
+                        .build(),
+                )
+                .edit_mode(ctx.address().recipient(), entry.model.links_directory.id)
+                .into_actor(self)
+                .then(move |user_saved, actor, _| {
+                    async move {
+                        if let Some(links_directory_id) = user_saved {
+                            let mut con = db::request_connection().await.unwrap();
+                            Some(
+                                links_directories_query(Some(links_directory_id))
+                                    .fetch_one(con.acquire().await.unwrap())
+                                    .await
+                                    .unwrap(),
+                            )
+                        } else {
+                            None
+                        }
+                    }
+                    .into_actor(actor)
+                })
+                .then(|result, actor, ctx| {
+                    if let Some(result) = result {
+                        let mut row = actor
+                            .links_directories
+                            .remove(&-1)
+                            .expect("entry with id=-1 should have been in links_directories");
+                        row.model = result;
+                        row.update_widgets_from_model();
+                        actor
+                            .main_app
+                            .do_send(crate::gui::msgs::MaintainLinksDirectory(
+                                LinksDirectorySource::Database(row.model.links_directory.id),
+                            ));
+                        actor
+                            .links_directories
+                            .insert(row.model.links_directory.id, row);
+                        actor.add_new_entry_row(ctx);
+                    }
+                    futures::future::ready(())
+                }),
+        );
+    }
+}
+
+struct LinksDirectoryRow {
+    model: models::LinksDirectoryWithFilterNames,
+    widgets: LinksDirectoryWidgets,
+}
+
+#[derive(woab::WidgetsFromBuilder, woab::PropSync)]
+struct LinksDirectoryWidgets {
+    row_links_directory: gtk::ListBoxRow,
+    #[prop_sync(set, get)]
+    txt_links_directory_name: gtk::Entry,
+    #[prop_sync(set, get)]
+    txt_links_directory_dir: gtk::Entry,
+    #[prop_sync(set, get)]
+    txt_links_directory_media_types: gtk::Entry,
+    #[prop_sync(set, get)]
+    txt_links_directory_serieses: gtk::Entry,
+    #[prop_sync(set, get)]
+    txt_links_directory_max_unread: gtk::Entry,
+    #[prop_sync("active-id": String, set, get)]
+    cbo_links_directory_link_mode: gtk::ComboBoxText,
+    stk_links_directory_edit: gtk::Stack,
+    btn_save_links_directory: gtk::Button,
+    btn_cancel_links_directory_edit: gtk::Button,
+    btn_save_new_links_directory: gtk::Button,
+}
+
+impl LinksDirectoryRow {
+    fn max_unread_text(&self) -> String {
+        self.model
+            .links_directory
+            .max_unread_per_series
+            .map(|max_unread| max_unread.to_string())
+            .unwrap_or_default()
+    }
+
+    fn update_widgets_from_model(&self) {
+        self.widgets.set_props(&LinksDirectoryWidgetsPropSetter {
+            txt_links_directory_name: &self.model.links_directory.name,
+            txt_links_directory_dir: &self.model.links_directory.dir,
+            txt_links_directory_media_types: &self.model.media_type_names,
+            txt_links_directory_serieses: &self.model.series_names,
+            txt_links_directory_max_unread: &self.max_unread_text(),
+            cbo_links_directory_link_mode: self.model.links_directory.link_mode.to_string(),
+        });
+    }
+
+    fn add_verifications_to_edit_mode(&self, edit_mode: EditMode) -> EditMode {
+        edit_mode
+            .with_edit_widget(
+                self.widgets.txt_links_directory_name.clone(),
+                "changed",
+                self.model.links_directory.name.clone(),
+                |text| {
+                    if text.is_empty() {
+                        Err("links directory name cannot be empty".to_owned())
+                    } else {
+                        Ok(())
+                    }
+                },
+            )
+            .with_edit_widget(
+                self.widgets.txt_links_directory_dir.clone(),
+                "changed",
+                self.model.links_directory.dir.clone(),
+                |text| {
+                    if text.is_empty() {
+                        Err("links directory must be set".to_owned())
+                    } else {
+                        Ok(())
+                    }
+                },
+            )
+            .with_edit_widget(
+                self.widgets.txt_links_directory_media_types.clone(),
+                "changed",
+                self.model.media_type_names.clone(),
+                |_| Ok(()),
+            )
+            .with_edit_widget(
+                self.widgets.txt_links_directory_serieses.clone(),
+                "changed",
+                self.model.series_names.clone(),
+                |_| Ok(()),
+            )
+            .with_edit_widget(
+                self.widgets.txt_links_directory_max_unread.clone(),
+                "changed",
+                self.max_unread_text(),
+                |text| parse_max_unread(text).map(|_| ()),
+            )
+            .with_edit_widget(
+                self.widgets.cbo_links_directory_link_mode.clone(),
+                "changed",
+                self.model.links_directory.link_mode.to_string(),
+                |_| Ok(()),
+            )
+    }
+}
+
+fn parse_max_unread(text: &str) -> Result<Option<i64>, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    match text.parse() {
+        Ok(max_unread) if 0 < max_unread => Ok(Some(max_unread)),
+        _ => Err(format!("{:?} is not a positive number", text)),
+    }
+}
+
+/// Resolve the `;` separated names to ids from `table`.
+async fn resolve_names(
+    con: &mut sqlx::SqliteConnection,
+    table: &str,
+    names: &str,
+) -> anyhow::Result<Vec<i64>> {
+    let mut ids = Vec::new();
+    for name in names.split(';').map(|name| name.trim()) {
+        if name.is_empty() {
+            continue;
+        }
+        let (id,): (i64,) = sqlx::query_as(&format!("SELECT id FROM {} WHERE name = ?", table))
+            .bind(name)
+            .fetch_optional(con.acquire().await?)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("There is no {:?} in {}", name, table))?;
+        ids.push(id);
+    }
+    Ok(ids)
+}
+
+impl actix::Handler<woab::Signal<i64>> for LinksDirectoriesActor {
+    type Result = woab::SignalResult;
+
+    fn handle(&mut self, msg: woab::Signal<i64>, ctx: &mut Self::Context) -> Self::Result {
+        let links_directory_id = *msg.tag();
+        let links_directory = &self.links_directories[&links_directory_id];
+        Ok(match msg.name() {
+            "open_links_directory_dir_dialog" => {
+                let icon_position: gtk::EntryIconPosition = msg.param(1)?;
+                match (
+                    links_directory
+                        .widgets
+                        .txt_links_directory_dir
+                        .is_editable(),
+                    icon_position,
+                ) {
+                    (true, gtk::EntryIconPosition::Primary) => {
+                        ctx.spawn(
+                            crate::util::dialogs::run_set_directory_dialog(
+                                links_directory.widgets.txt_links_directory_dir.clone(),
+                                None,
+                            )
+                            .into_actor(self),
+                        );
+                    }
+                    (true, gtk::EntryIconPosition::Secondary) => {
+                        links_directory.widgets.txt_links_directory_dir.set_text("");
+                    }
+                    _ => (),
+                }
+
+                None
+            }
+            "edit_links_directory" => {
+                ctx.spawn(
+                    links_directory
+                        .add_verifications_to_edit_mode(
+                            EditMode::builder()
+                                .stack(links_directory.widgets.stk_links_directory_edit.clone())
+                                .save_button(
+                                    links_directory.widgets.btn_save_links_directory.clone(),
+                                )
+                                .cancel_button(
+                                    links_directory
+                                        .widgets
+                                        .btn_cancel_links_directory_edit
+                                        .clone(),
+                                )
+                                .build(),
+                        )
+                        .edit_mode(ctx.address().recipient(), links_directory_id)
+                        .into_actor(self)
+                        .then(move |_, actor, ctx| {
+                            crate::actors::DbActor::from_registry()
+                                .send(crate::msgs::RefreshList {
+                                    orig_ids: Default::default(),
+                                    query: links_directories_query(Some(links_directory_id)),
+                                    id_dlg: |row_data: &models::LinksDirectoryWithFilterNames| {
+                                        row_data.links_directory.id
+                                    },
+                                    addr: ctx.address(),
+                                })
+                                .into_actor(actor)
+                                .then(move |_, _, _| futures::future::ready(()))
+                        }),
+                );
+                None
+            }
+            "delete_links_directory" => {
+                let links_directory_name = links_directory.model.links_directory.name.clone();
+                ctx.spawn(
+                    async move {
+                        let user_decision = woab::run_dialog(
+                            &gtk::MessageDialog::new::<gtk::ApplicationWindow>(
+                                None,
+                                gtk::DialogFlags::MODAL,
+                                gtk::MessageType::Warning,
+                                gtk::ButtonsType::YesNo,
+                                &format!(
+                                    "Are you sure you want to delete {:?}? The links in it will not be removed.",
+                                    links_directory_name
+                                ),
+                            ),
+                            true,
+                        )
+                        .await;
+                        if user_decision == gtk::ResponseType::Yes {
+                            let mut con = db::request_connection().await.unwrap();
+                            sqlx::query("DELETE FROM links_directories WHERE id = ?")
+                                .bind(links_directory_id)
+                                .execute(con.acquire().await.unwrap())
+                                .await
+                                .unwrap();
+                            true
+                        } else {
+                            false
+                        }
+                    }
+                    .into_actor(self)
+                    .then(move |did_delete, actor, _ctx| {
+                        if did_delete {
+                            if let Some(row) = actor.links_directories.remove(&links_directory_id)
+                            {
+                                actor
+                                    .widgets
+                                    .lst_links_directories
+                                    .remove(&row.widgets.row_links_directory);
+                            }
+                            // The maintainer of the deleted directory stops itself on refresh.
+                            actor
+                                .main_app
+                                .do_send(crate::gui::msgs::RefreshLinksDirectory);
+                        }
+                        futures::future::ready(())
+                    }),
+                );
+                None
+            }
+            _ => msg.cant_handle()?,
+        })
+    }
+}
+
+impl actix::Handler<crate::util::edit_mode::InitiateSave<i64>> for LinksDirectoriesActor {
+    type Result = actix::ResponseActFuture<Self, anyhow::Result<i64>>;
+
+    fn handle(
+        &mut self,
+        msg: crate::util::edit_mode::InitiateSave<i64>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let links_directory_id = msg.0;
+        let links_directory = &self.links_directories[&links_directory_id];
+        let LinksDirectoryWidgetsPropGetter {
+            txt_links_directory_name,
+            txt_links_directory_dir,
+            txt_links_directory_media_types,
+            txt_links_directory_serieses,
+            txt_links_directory_max_unread,
+            cbo_links_directory_link_mode,
+        } = links_directory.widgets.get_props();
+        let main_app = self.main_app.clone();
+        Box::pin(
+            async move {
+                let max_unread_per_series = parse_max_unread(&txt_links_directory_max_unread)
+                    .map_err(|err| anyhow::anyhow!(err))?;
+                let link_mode: LinkMode = cbo_links_directory_link_mode.parse()?;
+                let mut con = db::request_connection().await?;
+                let mut tx = con.begin().await?;
+                let media_type_ids =
+                    resolve_names(&mut tx, "media_types", &txt_links_directory_media_types)
+                        .await?;
+                let series_ids =
+                    resolve_names(&mut tx, "serieses", &txt_links_directory_serieses).await?;
+                let links_directory_id = if links_directory_id < 0 {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO links_directories(name, dir, max_unread_per_series, link_mode)
+                        VALUES(?, ?, ?, ?)
+                        "#,
+                    )
+                    .bind(txt_links_directory_name)
+                    .bind(txt_links_directory_dir)
+                    .bind(max_unread_per_series)
+                    .bind(link_mode)
+                    .execute(tx.acquire().await?)
+                    .await?
+                    .last_insert_rowid()
+                } else {
+                    sqlx::query(
+                        r#"
+                        UPDATE links_directories
+                        SET name = ?
+                          , dir = ?
+                          , max_unread_per_series = ?
+                          , link_mode = ?
+                        WHERE id = ?
+                        "#,
+                    )
+                    .bind(txt_links_directory_name)
+                    .bind(txt_links_directory_dir)
+                    .bind(max_unread_per_series)
+                    .bind(link_mode)
+                    .bind(links_directory_id)
+                    .execute(tx.acquire().await?)
+                    .await?;
+                    sqlx::query("DELETE FROM links_directory_media_types WHERE links_directory = ?")
+                        .bind(links_directory_id)
+                        .execute(tx.acquire().await?)
+                        .await?;
+                    sqlx::query("DELETE FROM links_directory_serieses WHERE links_directory = ?")
+                        .bind(links_directory_id)
+                        .execute(tx.acquire().await?)
+                        .await?;
+                    links_directory_id
+                };
+                for media_type_id in media_type_ids {
+                    sqlx::query(
+                        "INSERT INTO links_directory_media_types(links_directory, media_type) VALUES(?, ?)",
+                    )
+                    .bind(links_directory_id)
+                    .bind(media_type_id)
+                    .execute(tx.acquire().await?)
+                    .await?;
+                }
+                for series_id in series_ids {
+                    sqlx::query(
+                        "INSERT INTO links_directory_serieses(links_directory, series) VALUES(?, ?)",
+                    )
+                    .bind(links_directory_id)
+                    .bind(series_id)
+                    .execute(tx.acquire().await?)
+                    .await?;
+                }
+                tx.commit().await?;
+                main_app.do_send(crate::gui::msgs::RefreshLinksDirectory);
+                Ok(links_directory_id)
+            }
+            .into_actor(self),
+        )
+    }
+}
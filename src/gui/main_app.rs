@@ -4,24 +4,44 @@ use sqlx::prelude::*;
 
 use hashbrown::HashMap;
 
+use crate::files_discovery::NewEpisodes;
 use crate::gui;
 use crate::models;
-use crate::util::db::{stream_query, FromRowWithExtra};
+use crate::util::db::{self, stream_query, FromRowWithExtra};
 use crate::util::TypedQuark;
-use gui::links_dir::LinksDirectoryMaintainer;
+use gui::dangling_files::DanglingFilesActor;
+use gui::download_all::DownloadAllActor;
+use gui::duplicates::DuplicatesActor;
+use gui::files_watcher::FilesWatcherActor;
+use gui::links_dir::{LinksDirectoryMaintainer, LinksDirectorySource};
+use gui::links_directories::LinksDirectoriesActor;
 use gui::media_types::MediaTypesActor;
+use gui::scheduled_jobs::ScheduledJobsActor;
+use gui::scheduler::SchedulerActor;
 use gui::series::{SeriesActor, SeriesSortAndFilterData, SeriesWidgets};
+use gui::statistics::StatisticsActor;
+
+/// Sending a new notification with this ID replaces the previous one.
+const NEW_EPISODES_NOTIFICATION: &str = "new-episodes";
 
 #[derive(typed_builder::TypedBuilder)]
 pub struct MainAppActor {
     pub widgets: MainAppWidgets,
     pub factories: gui::Factories,
+    app: gtk::Application,
     #[builder(setter(skip), default)]
     serieses: HashMap<i64, actix::Addr<SeriesActor>>,
     #[builder(setter(skip), default = TypedQuark::new("series_sort_and_filter_data"))]
     series_sort_and_filter_data: TypedQuark<SeriesSortAndFilterData>,
     #[builder(setter(skip), default)]
     links_directory_maintainers: Vec<actix::Addr<LinksDirectoryMaintainer>>,
+    #[builder(setter(skip), default)]
+    files_watcher: Option<actix::Addr<FilesWatcherActor>>,
+    #[builder(setter(skip), default)]
+    scheduler: Option<actix::Addr<SchedulerActor>>,
+    /// Shown in the banner until the user looks at them or dismisses it.
+    #[builder(setter(skip), default)]
+    new_episodes: Vec<NewEpisodes>,
 }
 
 impl actix::Actor for MainAppActor {
@@ -33,6 +53,20 @@ impl actix::Actor for MainAppActor {
             &css_provider,
             gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
         );
+        self.files_watcher = Some(
+            FilesWatcherActor::builder()
+                .main_app(ctx.address())
+                .build()
+                .start(),
+        );
+        self.scheduler = Some(
+            SchedulerActor::builder()
+                .main_app(ctx.address())
+                .build()
+                .start(),
+        );
+        ctx.address()
+            .do_send(gui::msgs::UpdateSchedulerStatus { running: None });
         let addr = ctx.address();
         ctx.spawn(
             async move {
@@ -44,6 +78,17 @@ impl actix::Actor for MainAppActor {
                     .await
                     .unwrap()
                     .unwrap();
+                let mut con = db::request_connection().await.unwrap();
+                let links_directory_ids: Vec<(i64,)> =
+                    sqlx::query_as("SELECT id FROM links_directories")
+                        .fetch_all(con.acquire().await.unwrap())
+                        .await
+                        .unwrap();
+                for (id,) in links_directory_ids {
+                    addr.do_send(gui::msgs::MaintainLinksDirectory(
+                        LinksDirectorySource::Database(id),
+                    ));
+                }
             }
             .into_actor(self),
         );
@@ -59,6 +104,10 @@ pub struct MainAppWidgets {
     txt_series_filter: gtk::Entry,
     spn_scan_files: gtk::Spinner,
     spn_clean_dangling: gtk::Spinner,
+    spn_find_duplicates: gtk::Spinner,
+    lbl_scheduler_status: gtk::Label,
+    bar_new_episodes: gtk::InfoBar,
+    lbl_new_episodes: gtk::Label,
 }
 
 impl actix::Handler<woab::Signal> for MainAppActor {
@@ -93,60 +142,155 @@ impl actix::Handler<woab::Signal> for MainAppActor {
                 let button: gtk::Button = msg.param(0)?;
                 self.widgets.spn_scan_files.start();
                 button.set_sensitive(false);
+                ctx.spawn(
+                    crate::actors::DbActor::from_registry()
+                        .send(crate::msgs::ScanFiles { series: None })
+                        .into_actor(self)
+                        .then(|result, actor, ctx| {
+                            ctx.address()
+                                .do_send(gui::msgs::ShowNewEpisodes(result.unwrap().unwrap()));
+                            ctx.address().do_send(gui::msgs::RefreshLinksDirectory);
+                            ctx.address()
+                                .send(gui::msgs::UpdateSeriesesList)
+                                .into_actor(actor)
+                        })
+                        .then(move |result, actor, _| {
+                            button.set_sensitive(true);
+                            actor.widgets.spn_scan_files.stop();
+                            result.unwrap().unwrap();
+                            futures::future::ready(())
+                        }),
+                );
+                None
+            }
+            "clean_dangling" => {
+                let button: gtk::Button = msg.param(0)?;
+                self.widgets.spn_clean_dangling.start();
+                button.set_sensitive(false);
                 ctx.spawn(
                     async {
-                        let new_files = crate::actors::DbActor::from_registry()
-                            .send(crate::msgs::DiscoverFiles)
+                        crate::actors::DbActor::from_registry()
+                            .send(crate::msgs::FindDanglingFiles)
                             .await
                             .unwrap()
-                            .unwrap();
-                        Self::register_files(
-                            crate::util::db::request_connection().await.unwrap(),
-                            new_files,
-                        )
-                        .await
+                            .unwrap()
                     }
                     .into_actor(self)
-                    .then(|result, actor, ctx| {
-                        result.unwrap();
-                        ctx.address().do_send(gui::msgs::RefreshLinksDirectory);
-                        ctx.address()
-                            .send(gui::msgs::UpdateSeriesesList)
-                            .into_actor(actor)
-                    })
-                    .then(move |result, actor, _| {
+                    .then(move |dangling_files, actor, ctx| {
                         button.set_sensitive(true);
-                        actor.widgets.spn_scan_files.stop();
-                        result.unwrap().unwrap();
-                        futures::future::ready(())
+                        actor.widgets.spn_clean_dangling.stop();
+                        if dangling_files.is_empty() {
+                            let dialog = gtk::MessageDialog::new(
+                                Some(&actor.widgets.app_main),
+                                gtk::DialogFlags::MODAL,
+                                gtk::MessageType::Info,
+                                gtk::ButtonsType::Close,
+                                "No dangling files found",
+                            );
+                            return futures::future::Either::Left(
+                                async move {
+                                    woab::run_dialog(&dialog, true).await;
+                                }
+                                .into_actor(actor),
+                            );
+                        }
+                        let bld = actor.factories.win_dangling_files.instantiate();
+                        let addr = DanglingFilesActor::builder()
+                            .factories(actor.factories.clone())
+                            .widgets(bld.widgets().unwrap())
+                            .main_app(ctx.address())
+                            .dangling_files(dangling_files)
+                            .build()
+                            .start();
+                        bld.connect_to(addr);
+                        futures::future::Either::Right(futures::future::ready(()).into_actor(actor))
                     }),
                 );
                 None
             }
-            "clean_dangling" => {
+            "find_duplicates" => {
                 let button: gtk::Button = msg.param(0)?;
-                self.widgets.spn_clean_dangling.start();
+                self.widgets.spn_find_duplicates.start();
                 button.set_sensitive(false);
                 ctx.spawn(
                     async {
-                        crate::actors::DbActor::from_registry()
-                            .send(crate::msgs::FindAndRemoveDanglingFiles)
-                            .await
-                            .unwrap()
-                            .unwrap();
+                        let mut con = db::request_connection().await?;
+                        crate::duplicates::find_duplicates(&mut con).await
                     }
                     .into_actor(self)
-                    .then(|_result, actor, ctx| {
-                        ctx.address().do_send(gui::msgs::RefreshLinksDirectory);
-                        ctx.address()
-                            .send(gui::msgs::UpdateSeriesesList)
-                            .into_actor(actor)
-                    })
-                    .then(move |result, actor, _| {
+                    .then(move |groups, actor, ctx| {
                         button.set_sensitive(true);
-                        actor.widgets.spn_clean_dangling.stop();
-                        result.unwrap().unwrap();
-                        futures::future::ready(())
+                        actor.widgets.spn_find_duplicates.stop();
+                        let groups = match groups {
+                            Ok(groups) => groups,
+                            Err(err) => {
+                                log::error!("Cannot find duplicates: {}", err);
+                                Vec::new()
+                            }
+                        };
+                        if groups.is_empty() {
+                            let dialog = gtk::MessageDialog::new(
+                                Some(&actor.widgets.app_main),
+                                gtk::DialogFlags::MODAL,
+                                gtk::MessageType::Info,
+                                gtk::ButtonsType::Close,
+                                "No duplicate episodes found",
+                            );
+                            return futures::future::Either::Left(
+                                async move {
+                                    woab::run_dialog(&dialog, true).await;
+                                }
+                                .into_actor(actor),
+                            );
+                        }
+                        // The window itself has no signals - only its rows do.
+                        let bld = actor.factories.win_duplicates.instantiate();
+                        DuplicatesActor::builder()
+                            .factories(actor.factories.clone())
+                            .widgets(bld.widgets().unwrap())
+                            .main_app(ctx.address())
+                            .groups(groups)
+                            .build()
+                            .start();
+                        futures::future::Either::Right(futures::future::ready(()).into_actor(actor))
+                    }),
+                );
+                None
+            }
+            "continue_reading" => {
+                ctx.spawn(
+                    async {
+                        let mut con = db::request_connection().await?;
+                        crate::read_events::find_series_to_continue(&mut con).await
+                    }
+                    .into_actor(self)
+                    .then(|series_id, actor, _ctx| {
+                        let series_id = match series_id {
+                            Ok(series_id) => series_id,
+                            Err(err) => {
+                                log::error!("Cannot find a series to continue: {}", err);
+                                None
+                            }
+                        };
+                        if let Some(series) = series_id.and_then(|id| actor.serieses.get(&id)) {
+                            series.do_send(gui::msgs::ContinueReading);
+                            return futures::future::Either::Right(
+                                futures::future::ready(()).into_actor(actor),
+                            );
+                        }
+                        let dialog = gtk::MessageDialog::new(
+                            Some(&actor.widgets.app_main),
+                            gtk::DialogFlags::MODAL,
+                            gtk::MessageType::Info,
+                            gtk::ButtonsType::Close,
+                            "No series to continue",
+                        );
+                        futures::future::Either::Left(
+                            async move {
+                                woab::run_dialog(&dialog, true).await;
+                            }
+                            .into_actor(actor),
+                        )
                     }),
                 );
                 None
@@ -164,6 +308,7 @@ impl actix::Handler<woab::Signal> for MainAppActor {
                     name: "".to_owned(),
                     download_command_dir: None,
                     download_command: None,
+                    source: None,
                 };
                 self.series_sort_and_filter_data
                     .set(&widgets.row_series, (0, 0, &data).into());
@@ -204,12 +349,146 @@ impl actix::Handler<woab::Signal> for MainAppActor {
                 bld.connect_to(addr);
                 None
             }
+            "open_links_directories_window" => {
+                let bld = self.factories.win_links_directories.instantiate();
+                let addr = LinksDirectoriesActor::builder()
+                    .factories(self.factories.clone())
+                    .widgets(bld.widgets().unwrap())
+                    .main_app(ctx.address())
+                    .build()
+                    .start();
+                bld.connect_to(addr);
+                None
+            }
+            "open_download_all_window" => {
+                let bld = self.factories.win_download_all.instantiate();
+                let addr = DownloadAllActor::builder()
+                    .widgets(bld.widgets().unwrap())
+                    .main_app(ctx.address())
+                    .build()
+                    .start();
+                bld.connect_to(addr);
+                None
+            }
+            "new_episodes_link_activated" => {
+                let uri: String = msg.param(1)?;
+                if let Ok(series_id) = uri.parse() {
+                    self.reveal_new_episodes(series_id);
+                }
+                Some(glib::Propagation::Stop)
+            }
+            "close_new_episodes_banner" => {
+                self.new_episodes.clear();
+                self.update_new_episodes_banner();
+                None
+            }
+            "show_new_episodes" => {
+                let series_id: i64 = msg.action_param()?;
+                self.widgets.app_main.present();
+                self.reveal_new_episodes(series_id);
+                None
+            }
+            "open_scheduled_jobs_window" => {
+                let scheduler = if let Some(scheduler) = &self.scheduler {
+                    scheduler.clone()
+                } else {
+                    return Ok(None);
+                };
+                let bld = self.factories.win_scheduled_jobs.instantiate();
+                let addr = ScheduledJobsActor::builder()
+                    .factories(self.factories.clone())
+                    .widgets(bld.widgets().unwrap())
+                    .main_app(ctx.address())
+                    .scheduler(scheduler)
+                    .build()
+                    .start();
+                bld.connect_to(addr);
+                None
+            }
+            "open_statistics_window" => {
+                let bld = self.factories.win_statistics.instantiate();
+                let addr = StatisticsActor::builder()
+                    .widgets(bld.widgets().unwrap())
+                    .build()
+                    .start();
+                bld.connect_to(addr);
+                None
+            }
             _ => msg.cant_handle()?,
         })
     }
 }
 
 impl MainAppActor {
+    fn update_new_episodes_banner(&self) {
+        if self.new_episodes.is_empty() {
+            self.widgets.bar_new_episodes.hide();
+            self.app.withdraw_notification(NEW_EPISODES_NOTIFICATION);
+            return;
+        }
+        let markup = self
+            .new_episodes
+            .iter()
+            .map(|new_episodes| {
+                format!(
+                    "<a href=\"{}\">{}</a>",
+                    new_episodes.series,
+                    glib::markup_escape_text(&new_episodes.describe())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.widgets.lbl_new_episodes.set_markup(&markup);
+        self.widgets.bar_new_episodes.show();
+    }
+
+    fn send_new_episodes_notification(&self) {
+        let Some(first) = self.new_episodes.first() else {
+            return;
+        };
+        let notification = gio::Notification::new("New chapters");
+        notification.set_body(Some(
+            &self
+                .new_episodes
+                .iter()
+                .map(NewEpisodes::describe)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ));
+        // Clicking the notification reveals the first series. When there are more, each gets a
+        // button.
+        notification.set_default_action_and_target_value(
+            "app.show-new-episodes",
+            Some(&first.series.to_variant()),
+        );
+        if 1 < self.new_episodes.len() {
+            for new_episodes in self.new_episodes.iter() {
+                notification.add_button_with_target_value(
+                    &new_episodes.series_name,
+                    "app.show-new-episodes",
+                    Some(&new_episodes.series.to_variant()),
+                );
+            }
+        }
+        self.app
+            .send_notification(Some(NEW_EPISODES_NOTIFICATION), &notification);
+    }
+
+    /// Expand the series and scroll to its new episodes, and remove them from the banner.
+    fn reveal_new_episodes(&mut self, series_id: i64) {
+        if let Some(index) = self
+            .new_episodes
+            .iter()
+            .position(|new_episodes| new_episodes.series == series_id)
+        {
+            let new_episodes = self.new_episodes.remove(index);
+            if let Some(series) = self.serieses.get(&series_id) {
+                series.do_send(gui::msgs::RevealEpisodes(new_episodes.episodes));
+            }
+        }
+        self.update_new_episodes_banner();
+    }
+
     fn update_series_filter(&self) {
         use fuzzy_matcher::FuzzyMatcher;
         let fuzzy_matcher = fuzzy_matcher::skim::SkimMatcherV2::default();
@@ -227,49 +506,6 @@ impl MainAppActor {
                 }),
         );
     }
-
-    async fn register_files(
-        mut con: crate::SqlitePoolConnection,
-        new_files: Vec<crate::files_discovery::FoundFile>,
-    ) -> anyhow::Result<()> {
-        use futures::stream::StreamExt;
-        let mut series_map = hashbrown::HashMap::<i64, String>::new();
-        sqlx::query_as::<_, (i64, String)>("SELECT id, name FROM serieses")
-            .fetch(con.acquire().await?)
-            .for_each(|row| {
-                let (id, name) = row.unwrap();
-                series_map.insert(id, name);
-                futures::future::ready(())
-            })
-            .await;
-        let statement = con
-            .prepare(
-                r#"
-            INSERT INTO episodes(series, volume, number, name, file, date_of_read)
-            VALUES(?, ?, ?, ?, ?, NULL);
-            "#,
-            )
-            .await?;
-        for file in new_files {
-            statement
-                .query()
-                .bind(file.series)
-                .bind(file.file_data.volume)
-                .bind(file.file_data.chapter)
-                .bind(if let Some(volume) = file.file_data.volume {
-                    format!(
-                        "{} v{:?} c{}",
-                        series_map[&file.series], volume, file.file_data.chapter
-                    )
-                } else {
-                    format!("{} c{}", series_map[&file.series], file.file_data.chapter)
-                })
-                .bind(file.path)
-                .execute(con.acquire().await?)
-                .await?;
-        }
-        Ok(())
-    }
 }
 
 impl actix::Handler<gui::msgs::UpdateMediaTypesList> for MainAppActor {
@@ -408,9 +644,9 @@ impl actix::Handler<crate::gui::msgs::MaintainLinksDirectory> for MainAppActor {
         msg: crate::gui::msgs::MaintainLinksDirectory,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
-        let crate::gui::msgs::MaintainLinksDirectory(links_directory) = msg;
+        let crate::gui::msgs::MaintainLinksDirectory(source) = msg;
         let addr = LinksDirectoryMaintainer::builder()
-            .dir_path(links_directory.into())
+            .source(source)
             .build()
             .start();
         self.links_directory_maintainers.push(addr);
@@ -418,15 +654,162 @@ impl actix::Handler<crate::gui::msgs::MaintainLinksDirectory> for MainAppActor {
 }
 
 impl actix::Handler<crate::gui::msgs::RefreshLinksDirectory> for MainAppActor {
-    type Result = ();
+    type Result = ResponseFuture<
+        anyhow::Result<
+            Vec<(
+                std::path::PathBuf,
+                crate::links_handling::LinksRefreshReport,
+            )>,
+        >,
+    >;
 
     fn handle(
         &mut self,
         _msg: crate::gui::msgs::RefreshLinksDirectory,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
-        for addr in self.links_directory_maintainers.iter() {
-            addr.do_send(gui::msgs::RefreshLinksDirectory);
+        // Maintainers of links directories that were removed from the database stop themselves.
+        self.links_directory_maintainers
+            .retain(|addr| addr.connected());
+        let refreshes = self
+            .links_directory_maintainers
+            .iter()
+            .map(|addr| addr.send(gui::msgs::RefreshLinksDirectory))
+            .collect::<Vec<_>>();
+        Box::pin(async move {
+            let mut reports = Vec::new();
+            let mut errors = Vec::new();
+            for result in futures::future::join_all(refreshes).await {
+                match result {
+                    Ok(Ok(refreshed)) => reports.extend(refreshed),
+                    Ok(Err(err)) => errors.push(err.to_string()),
+                    // The maintainer stopped because its links directory was removed.
+                    Err(actix::MailboxError::Closed) => {}
+                    Err(err) => errors.push(err.to_string()),
+                }
+            }
+            if !errors.is_empty() {
+                anyhow::bail!("Cannot refresh links directories: {}", errors.join("; "));
+            }
+            Ok(reports)
+        })
+    }
+}
+
+impl actix::Handler<crate::gui::msgs::UpdateWatchedDirectories> for MainAppActor {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        _msg: crate::gui::msgs::UpdateWatchedDirectories,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        if let Some(files_watcher) = &self.files_watcher {
+            files_watcher.do_send(gui::msgs::UpdateWatchedDirectories);
+        }
+    }
+}
+
+impl actix::Handler<gui::msgs::ShowDownloadProgress> for MainAppActor {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: gui::msgs::ShowDownloadProgress,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        if let Some(series_actor) = self.serieses.get(&msg.0.series) {
+            series_actor.do_send(msg);
+        }
+    }
+}
+
+impl actix::Handler<gui::msgs::UpdateSchedulerStatus> for MainAppActor {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: gui::msgs::UpdateSchedulerStatus,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
+        if let Some(job) = msg.running {
+            self.widgets
+                .lbl_scheduler_status
+                .set_text(&format!("{}: running…", job.title()));
+            return;
+        }
+        ctx.spawn(
+            async {
+                let mut con = db::request_connection().await?;
+                crate::scheduler::load_scheduled_jobs(&mut con).await
+            }
+            .into_actor(self)
+            .map(|jobs, actor, _ctx| {
+                let jobs = match jobs {
+                    Ok(jobs) => jobs,
+                    Err(err) => {
+                        log::error!("Cannot load scheduled jobs: {}", err);
+                        return;
+                    }
+                };
+                let lbl = &actor.widgets.lbl_scheduler_status;
+                match jobs
+                    .iter()
+                    .filter(|job| job.last_run.is_some())
+                    .max_by_key(|job| job.last_run)
+                {
+                    Some(job) => {
+                        lbl.set_text(&format!("{}: {}", job.job.title(), job.describe_last_run()))
+                    }
+                    None => lbl.set_text("No scheduled job ran yet"),
+                }
+                let tooltip = jobs
+                    .iter()
+                    .map(|job| {
+                        format!(
+                            "{} ({}): {}",
+                            job.job.title(),
+                            if 0 < job.interval_minutes {
+                                format!("every {} minutes", job.interval_minutes)
+                            } else {
+                                "not scheduled".to_owned()
+                            },
+                            job.describe_last_run()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                lbl.set_tooltip_text(Some(&tooltip));
+            }),
+        );
+    }
+}
+
+impl actix::Handler<gui::msgs::ShowNewEpisodes> for MainAppActor {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: gui::msgs::ShowNewEpisodes,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        if msg.0.is_empty() {
+            return;
+        }
+        for new_episodes in msg.0 {
+            if let Some(existing) = self
+                .new_episodes
+                .iter_mut()
+                .find(|existing| existing.series == new_episodes.series)
+            {
+                existing.merge(new_episodes);
+            } else {
+                self.new_episodes.push(new_episodes);
+            }
         }
+        self.new_episodes
+            .sort_by(|a, b| a.series_name.cmp(&b.series_name));
+        self.update_new_episodes_banner();
+        self.send_new_episodes_notification();
     }
 }
@@ -103,6 +103,10 @@ impl MediaTypesActor {
             adjacent_file_types: "".to_owned(),
             program: "".to_owned(),
             maintain_symlinks: false,
+            mark_read_on_exit: false,
+            link_template: "".to_owned(),
+            extract_metadata: false,
+            download_command_template: "".to_owned(),
         };
         let entry =
             if let hashbrown::hash_map::Entry::Vacant(entry) = self.media_types.entry(data.id) {
@@ -189,6 +193,14 @@ struct MediaTypeWidgets {
     txt_media_type_program: gtk::Entry,
     #[prop_sync("active": bool, set, get)]
     chk_media_type_maintain_symlinks: gtk::ToggleButton,
+    #[prop_sync("active": bool, set, get)]
+    chk_media_type_mark_read_on_exit: gtk::ToggleButton,
+    #[prop_sync(set, get)]
+    txt_media_type_link_template: gtk::Entry,
+    #[prop_sync("active": bool, set, get)]
+    chk_media_type_extract_metadata: gtk::ToggleButton,
+    #[prop_sync(set, get)]
+    txt_media_type_download_command_template: gtk::Entry,
     stk_media_type_edit: gtk::Stack,
     btn_save_media_type: gtk::Button,
     btn_cancel_media_type_edit: gtk::Button,
@@ -204,6 +216,10 @@ impl MediaTypeRow {
             txt_media_type_adjacent_file_types: &self.model.adjacent_file_types,
             txt_media_type_program: &self.model.program,
             chk_media_type_maintain_symlinks: self.model.maintain_symlinks,
+            chk_media_type_mark_read_on_exit: self.model.mark_read_on_exit,
+            txt_media_type_link_template: &self.model.link_template,
+            chk_media_type_extract_metadata: self.model.extract_metadata,
+            txt_media_type_download_command_template: &self.model.download_command_template,
         });
     }
 
@@ -251,6 +267,46 @@ impl MediaTypeRow {
                 self.model.maintain_symlinks,
                 |_| Ok(()),
             )
+            .with_edit_widget(
+                self.widgets.chk_media_type_mark_read_on_exit.clone(),
+                "toggled",
+                self.model.mark_read_on_exit,
+                |_| Ok(()),
+            )
+            .with_edit_widget(
+                self.widgets.txt_media_type_link_template.clone(),
+                "changed",
+                self.model.link_template.clone(),
+                |text| {
+                    if text.is_empty() {
+                        return Ok(());
+                    }
+                    text.parse::<crate::link_template::LinkTemplate>()
+                        .map(|_| ())
+                        .map_err(|err| err.to_string())
+                },
+            )
+            .with_edit_widget(
+                self.widgets.chk_media_type_extract_metadata.clone(),
+                "toggled",
+                self.model.extract_metadata,
+                |_| Ok(()),
+            )
+            .with_edit_widget(
+                self.widgets
+                    .txt_media_type_download_command_template
+                    .clone(),
+                "changed",
+                self.model.download_command_template.clone(),
+                |text| {
+                    if text.is_empty() {
+                        return Ok(());
+                    }
+                    text.parse::<crate::download_template::DownloadTemplate>()
+                        .map(|_| ())
+                        .map_err(|err| err.to_string())
+                },
+            )
     }
 }
 
@@ -405,24 +461,43 @@ impl actix::Handler<crate::util::edit_mode::InitiateSave<i64>> for MediaTypesAct
             txt_media_type_adjacent_file_types,
             txt_media_type_program,
             chk_media_type_maintain_symlinks,
+            chk_media_type_mark_read_on_exit,
+            txt_media_type_link_template,
+            chk_media_type_extract_metadata,
+            txt_media_type_download_command_template,
         } = media_type.widgets.get_props();
+        let old_base_dir = media_type.model.base_dir.clone();
         let main_app = self.main_app.clone();
         Box::pin(async move {
             if media_type_id < 0 {
                 let query = sqlx::query(r#"
-                    INSERT INTO media_types(name, base_dir, file_types, adjacent_file_types, program, maintain_symlinks)
-                    VALUES(?, ?, ?, ?, ?, ?)
+                    INSERT INTO media_types(name, base_dir, file_types, adjacent_file_types, program, maintain_symlinks, mark_read_on_exit, link_template, extract_metadata, download_command_template)
+                    VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
                 "#)
                     .bind(txt_media_type_name)
                     .bind(txt_media_type_base_dir)
                     .bind(txt_media_type_file_types)
                     .bind(txt_media_type_adjacent_file_types)
                     .bind(txt_media_type_program)
-                    .bind(chk_media_type_maintain_symlinks);
+                    .bind(chk_media_type_maintain_symlinks)
+                    .bind(chk_media_type_mark_read_on_exit)
+                    .bind(txt_media_type_link_template)
+                    .bind(chk_media_type_extract_metadata)
+                    .bind(txt_media_type_download_command_template);
                 let mut con = db::request_connection().await?;
                 let query_result = query.execute(con.acquire().await?).await?;
                 Ok(query_result.last_insert_rowid())
             } else {
+                let mut con = db::request_connection().await?;
+                let relocate = txt_media_type_base_dir != old_base_dir;
+                if relocate {
+                    confirm_relocation(&mut con, media_type_id, &txt_media_type_base_dir).await?;
+                }
+                // Relocating the paths and saving the new base directory must happen together.
+                let mut tx = con.begin().await?;
+                if relocate {
+                    crate::library_root::move_base_dir(&mut tx, media_type_id, &txt_media_type_base_dir).await?;
+                }
                 let query = sqlx::query(r#"
                     UPDATE media_types
                     SET name = ?
@@ -431,6 +506,10 @@ impl actix::Handler<crate::util::edit_mode::InitiateSave<i64>> for MediaTypesAct
                       , adjacent_file_types = ?
                       , program = ?
                       , maintain_symlinks = ?
+                      , mark_read_on_exit = ?
+                      , link_template = ?
+                      , extract_metadata = ?
+                      , download_command_template = ?
                     WHERE id = ?
                 "#)
                     .bind(txt_media_type_name)
@@ -439,12 +518,47 @@ impl actix::Handler<crate::util::edit_mode::InitiateSave<i64>> for MediaTypesAct
                     .bind(txt_media_type_adjacent_file_types)
                     .bind(txt_media_type_program)
                     .bind(chk_media_type_maintain_symlinks)
+                    .bind(chk_media_type_mark_read_on_exit)
+                    .bind(txt_media_type_link_template)
+                    .bind(chk_media_type_extract_metadata)
+                    .bind(txt_media_type_download_command_template)
                     .bind(media_type_id);
-                let mut con = db::request_connection().await?;
-                query.execute(con.acquire().await?).await?;
+                query.execute(tx.acquire().await?).await?;
+                tx.commit().await?;
                 main_app.do_send(crate::gui::msgs::UpdateMediaTypesList);
                 Ok(media_type_id)
             }
         }.into_actor(self))
     }
 }
+
+/// Ask the user whether to relocate the media type to its new base directory if some of the
+/// episode files are not there. Fails if the user refuses.
+async fn confirm_relocation(
+    con: &mut crate::SqlitePoolConnection,
+    media_type_id: i64,
+    new_base_dir: &str,
+) -> anyhow::Result<()> {
+    let check = crate::library_root::check_relocation(con, media_type_id, new_base_dir).await?;
+    if !check.is_complete() {
+        for missing in check.missing.iter() {
+            log::warn!("Missing from the new base directory: {:?}", missing);
+        }
+        let dialog = gtk::MessageDialog::new::<gtk::ApplicationWindow>(
+            None,
+            gtk::DialogFlags::MODAL,
+            gtk::MessageType::Warning,
+            gtk::ButtonsType::YesNo,
+            &format!(
+                "{} of {} episode files are missing from {:?}. Relocate anyway?",
+                check.missing.len(),
+                check.missing.len() + check.num_found,
+                new_base_dir,
+            ),
+        );
+        if woab::run_dialog(&dialog, true).await != gtk::ResponseType::Yes {
+            anyhow::bail!("Relocation to {:?} cancelled", new_base_dir);
+        }
+    }
+    Ok(())
+}
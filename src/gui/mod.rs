@@ -1,12 +1,20 @@
 use actix::prelude::*;
 use gio::prelude::*;
 
+mod dangling_files;
 mod directory;
+mod download_all;
+mod duplicates;
+mod files_watcher;
 mod links_dir;
+mod links_directories;
 mod main_app;
 mod media_types;
 mod msgs;
+mod scheduled_jobs;
+mod scheduler;
 mod series;
+mod statistics;
 
 pub fn start_gui() -> anyhow::Result<i32> {
     use structopt::StructOpt;
@@ -19,12 +27,18 @@ pub fn start_gui() -> anyhow::Result<i32> {
         &*crate::Asset::get("gui.glade").unwrap().data,
     )?);
 
-    let app = gtk::Application::new(None, gio::ApplicationFlags::HANDLES_COMMAND_LINE);
+    // The application is unique so that it owns its D-Bus name, which is how clicking its
+    // notifications reaches it. Later invocations forward their command line to it and exit.
+    let app = gtk::Application::new(
+        Some("com.github.idanarye.chapter-tracker"),
+        gio::ApplicationFlags::HANDLES_COMMAND_LINE,
+    );
     woab::block_on(async {
         let bld = factories.app_main.instantiate();
         let main_app = main_app::MainAppActor::builder()
             .widgets(bld.widgets().unwrap())
             .factories(factories)
+            .app(app.clone())
             .build()
             .start();
         // app.add_main_option(
@@ -40,15 +54,58 @@ pub fn start_gui() -> anyhow::Result<i32> {
         // app.activate();
         // 0
         // });
-        app.connect_command_line(|app, _| {
-            app.activate();
-            0
+        app.connect_command_line({
+            let main_app = main_app.clone();
+            move |app, command_line| {
+                if command_line.is_remote() {
+                    match crate::CliArgs::from_iter_safe(command_line.arguments()) {
+                        Ok(remote_args) => {
+                            if remote_args.dbfile.is_some() {
+                                log::warn!("Ignoring the --dbfile of a later invocation");
+                            }
+                            if let Some(links_directory) = remote_args.linksdir {
+                                // Relative paths are relative to where the later invocation ran.
+                                let links_directory = match command_line.cwd() {
+                                    Some(cwd) => cwd.join(links_directory),
+                                    None => links_directory.into(),
+                                };
+                                main_app.do_send(msgs::MaintainLinksDirectory(
+                                    links_dir::LinksDirectorySource::Path(
+                                        links_directory,
+                                        remote_args.linksmode,
+                                    ),
+                                ));
+                            }
+                        }
+                        Err(err) => {
+                            log::error!("Bad command line from a later invocation: {}", err);
+                            return 1;
+                        }
+                    }
+                }
+                app.activate();
+                0
+            }
         });
         woab::route_signal(&app, "activate", "app_activate", main_app.clone()).unwrap();
         woab::route_signal(&app, "shutdown", "app_shutdown", main_app.clone()).unwrap();
 
+        // The default action of the new chapters notifications. Its parameter is the series ID.
+        let show_new_episodes =
+            gio::SimpleAction::new("show-new-episodes", Some(glib::VariantTy::INT64));
+        woab::route_signal(
+            &show_new_episodes,
+            "activate",
+            "show_new_episodes",
+            main_app.clone(),
+        )
+        .unwrap();
+        app.add_action(&show_new_episodes);
+
         if let Some(links_directory) = cli_args.linksdir {
-            main_app.do_send(msgs::MaintainLinksDirectory(links_directory));
+            main_app.do_send(msgs::MaintainLinksDirectory(
+                links_dir::LinksDirectorySource::Path(links_directory.into(), cli_args.linksmode),
+            ));
         }
 
         bld.connect_to(main_app);
@@ -62,13 +119,40 @@ pub fn start_gui() -> anyhow::Result<i32> {
 pub struct FactoriesInner {
     #[factory(extra(lsm_media_types))]
     pub app_main: woab::BuilderFactory,
+    #[factory(extra(pop_series_analysis, pop_download_log))]
     pub row_series: woab::BuilderFactory,
+    #[factory(extra(mnu_episode_bulk))]
     pub row_episode: woab::BuilderFactory,
     #[factory(extra(lsm_directory_scan_preview, srt_directory_scan_preview))]
     pub row_directory: woab::BuilderFactory,
 
     pub win_media_types: woab::BuilderFactory,
     pub row_media_type: woab::BuilderFactory,
+    pub win_links_directories: woab::BuilderFactory,
+    pub row_links_directory: woab::BuilderFactory,
+
+    pub win_dangling_files: woab::BuilderFactory,
+    pub row_dangling_file: woab::BuilderFactory,
+
+    #[factory(extra(adj_download_all_concurrency, lsm_download_all))]
+    pub win_download_all: woab::BuilderFactory,
+
+    pub win_scheduled_jobs: woab::BuilderFactory,
+    #[factory(extra(adj_scheduled_job_interval))]
+    pub row_scheduled_job: woab::BuilderFactory,
+
+    pub win_duplicates: woab::BuilderFactory,
+    pub row_duplicate_episode: woab::BuilderFactory,
+
+    #[factory(extra(
+        lsm_statistics_reads_per_day,
+        lsm_statistics_reads_per_week,
+        lsm_statistics_reads_per_month,
+        lsm_statistics_media_types,
+        lsm_statistics_longest_streaks,
+        lsm_statistics_unread_backlog
+    ))]
+    pub win_statistics: woab::BuilderFactory,
 }
 
 type Factories = std::rc::Rc<FactoriesInner>;
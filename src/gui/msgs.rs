@@ -28,14 +28,53 @@ pub struct InitiateNewRowSequence;
 #[rtype(result = "anyhow::Result<String>")]
 pub struct GetBaseDirForMediaType;
 
+#[derive(actix::Message)]
+#[rtype(result = "anyhow::Result<crate::files_discovery::FileTypesFilter>")]
+pub struct GetFileTypesFilterForMediaType;
+
 #[derive(actix::Message)]
 #[rtype(result = "()")]
 pub struct UpdateModel<T>(pub T);
 
 #[derive(actix::Message)]
 #[rtype(result = "()")]
-pub struct MaintainLinksDirectory(pub String);
+pub struct MaintainLinksDirectory(pub crate::gui::links_dir::LinksDirectorySource);
 
+/// Responds with the directories that were refreshed and what each refresh did.
 #[derive(actix::Message)]
-#[rtype(result = "()")]
+#[rtype(
+    result = "anyhow::Result<Vec<(std::path::PathBuf, crate::links_handling::LinksRefreshReport)>>"
+)]
 pub struct RefreshLinksDirectory;
+
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct UpdateWatchedDirectories;
+
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct ContinueReading;
+
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct ShowDownloadProgress(pub crate::downloads::DownloadProgress);
+
+#[derive(actix::Message)]
+#[rtype(result = "anyhow::Result<()>")]
+pub struct RunScheduledJob(pub i64);
+
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct UpdateSchedulerStatus {
+    pub running: Option<crate::scheduler::ScheduledJob>,
+}
+
+/// Tell the user about the episodes a scan registered.
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct ShowNewEpisodes(pub Vec<crate::files_discovery::NewEpisodes>);
+
+/// Expand the series' episodes and scroll to these episodes.
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct RevealEpisodes(pub Vec<i64>);
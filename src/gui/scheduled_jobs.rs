@@ -0,0 +1,147 @@
+use actix::prelude::*;
+use gtk::prelude::*;
+use hashbrown::HashMap;
+
+use crate::gui;
+use crate::scheduler::ScheduledJobState;
+use crate::util::db;
+
+#[derive(typed_builder::TypedBuilder)]
+pub struct ScheduledJobsActor {
+    factories: gui::Factories,
+    widgets: ScheduledJobsWindowWidgets,
+    main_app: actix::Addr<gui::main_app::MainAppActor>,
+    scheduler: actix::Addr<gui::scheduler::SchedulerActor>,
+    #[builder(setter(skip), default)]
+    rows: HashMap<i64, ScheduledJobWidgets>,
+}
+
+#[derive(woab::WidgetsFromBuilder)]
+pub struct ScheduledJobsWindowWidgets {
+    win_scheduled_jobs: gtk::Window,
+    lst_scheduled_jobs: gtk::ListBox,
+}
+
+#[derive(woab::WidgetsFromBuilder)]
+struct ScheduledJobWidgets {
+    row_scheduled_job: gtk::ListBoxRow,
+    lbl_scheduled_job_title: gtk::Label,
+    spn_scheduled_job_interval: gtk::SpinButton,
+    lbl_scheduled_job_last_run: gtk::Label,
+    spn_scheduled_job_running: gtk::Spinner,
+    btn_run_scheduled_job: gtk::Button,
+}
+
+impl actix::Actor for ScheduledJobsActor {
+    type Context = actix::Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.widgets.win_scheduled_jobs.show();
+        self.update_rows(ctx);
+    }
+}
+
+impl ScheduledJobsActor {
+    fn update_rows(&mut self, ctx: &mut actix::Context<Self>) {
+        ctx.spawn(
+            async {
+                let mut con = db::request_connection().await?;
+                crate::scheduler::load_scheduled_jobs(&mut con).await
+            }
+            .into_actor(self)
+            .map(|jobs, actor, ctx| match jobs {
+                Ok(jobs) => {
+                    for job in jobs {
+                        actor.update_row(ctx, &job);
+                    }
+                }
+                Err(err) => log::error!("Cannot load scheduled jobs: {}", err),
+            }),
+        );
+    }
+
+    fn update_row(&mut self, ctx: &mut actix::Context<Self>, job: &ScheduledJobState) {
+        let widgets = match self.rows.entry(job.id) {
+            hashbrown::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            hashbrown::hash_map::Entry::Vacant(entry) => {
+                let bld = self.factories.row_scheduled_job.instantiate();
+                let widgets: ScheduledJobWidgets = bld.widgets().unwrap();
+                widgets.lbl_scheduled_job_title.set_text(job.job.title());
+                // Set before connecting, so that it doesn't get saved back.
+                widgets
+                    .spn_scheduled_job_interval
+                    .set_value(job.interval_minutes as f64);
+                bld.connect_to((job.id, ctx.address()));
+                self.widgets
+                    .lst_scheduled_jobs
+                    .add(&widgets.row_scheduled_job);
+                entry.insert(widgets)
+            }
+        };
+        widgets
+            .lbl_scheduled_job_last_run
+            .set_text(&job.describe_last_run());
+    }
+}
+
+impl actix::Handler<woab::Signal> for ScheduledJobsActor {
+    type Result = woab::SignalResult;
+
+    fn handle(&mut self, msg: woab::Signal, _ctx: &mut Self::Context) -> Self::Result {
+        msg.cant_handle()
+    }
+}
+
+impl actix::Handler<woab::Signal<i64>> for ScheduledJobsActor {
+    type Result = woab::SignalResult;
+
+    fn handle(&mut self, msg: woab::Signal<i64>, ctx: &mut Self::Context) -> Self::Result {
+        let job_id = *msg.tag();
+        let widgets = &self.rows[&job_id];
+        Ok(match msg.name() {
+            "scheduled_job_interval_changed" => {
+                let interval_minutes = widgets.spn_scheduled_job_interval.value_as_int() as i64;
+                let main_app = self.main_app.clone();
+                ctx.spawn(
+                    async move {
+                        let mut con = db::request_connection().await?;
+                        crate::scheduler::set_job_interval(&mut con, job_id, interval_minutes)
+                            .await?;
+                        main_app.do_send(gui::msgs::UpdateSchedulerStatus { running: None });
+                        Ok::<_, anyhow::Error>(())
+                    }
+                    .into_actor(self)
+                    .map(|result, _, _| {
+                        if let Err(err) = result {
+                            log::error!("Cannot set the interval of scheduled job: {}", err);
+                        }
+                    }),
+                );
+                None
+            }
+            "run_scheduled_job" => {
+                widgets.btn_run_scheduled_job.set_sensitive(false);
+                widgets.spn_scheduled_job_running.start();
+                ctx.spawn(
+                    self.scheduler
+                        .send(gui::msgs::RunScheduledJob(job_id))
+                        .into_actor(self)
+                        .map(move |result, actor, ctx| {
+                            let widgets = &actor.rows[&job_id];
+                            widgets.btn_run_scheduled_job.set_sensitive(true);
+                            widgets.spn_scheduled_job_running.stop();
+                            if let Err(err) = result.unwrap() {
+                                widgets
+                                    .lbl_scheduled_job_last_run
+                                    .set_text(&err.to_string());
+                            } else {
+                                actor.update_rows(ctx);
+                            }
+                        }),
+                );
+                None
+            }
+            _ => msg.cant_handle()?,
+        })
+    }
+}
@@ -0,0 +1,220 @@
+use actix::prelude::*;
+
+use crate::gui;
+use crate::scheduler::{ScheduledJob, ScheduledJobState};
+use crate::util::db;
+
+/// How often to check for jobs that are due.
+const CHECK_INTERVAL: core::time::Duration = core::time::Duration::from_secs(60);
+
+/// Runs the scheduled jobs when they are due, one at a time.
+#[derive(typed_builder::TypedBuilder)]
+pub struct SchedulerActor {
+    main_app: actix::Addr<gui::main_app::MainAppActor>,
+    /// Set while a job runs - and while looking for one to run - so that jobs don't overlap.
+    #[builder(setter(skip), default)]
+    busy: bool,
+}
+
+impl actix::Actor for SchedulerActor {
+    type Context = actix::Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(CHECK_INTERVAL, |actor, ctx| {
+            actor.run_due_job(ctx);
+        });
+    }
+}
+
+impl SchedulerActor {
+    fn run_due_job(&mut self, ctx: &mut actix::Context<Self>) {
+        if self.busy {
+            return;
+        }
+        self.busy = true;
+        ctx.spawn(
+            async {
+                let mut con = db::request_connection().await?;
+                crate::scheduler::next_due_job(&mut con).await
+            }
+            .into_actor(self)
+            .then(|job, actor, _ctx| {
+                actor.busy = false;
+                match job {
+                    Ok(Some(job)) => {
+                        futures::future::Either::Left(actor.run_job(job).map(|result, _, _| {
+                            if let Err(err) = result {
+                                log::error!("Cannot record scheduled job run: {}", err);
+                            }
+                        }))
+                    }
+                    Ok(None) => futures::future::Either::Right(actix::fut::ready(())),
+                    Err(err) => {
+                        log::error!("Cannot check for due scheduled jobs: {}", err);
+                        futures::future::Either::Right(actix::fut::ready(()))
+                    }
+                }
+            }),
+        );
+    }
+
+    /// Run the job and record its result. The job's failure is recorded - only failing to record
+    /// it is an error.
+    fn run_job(
+        &mut self,
+        job: ScheduledJobState,
+    ) -> impl ActorFuture<Self, Output = anyhow::Result<()>> {
+        self.busy = true;
+        self.main_app.do_send(gui::msgs::UpdateSchedulerStatus {
+            running: Some(job.job),
+        });
+        let main_app = self.main_app.clone();
+        async move {
+            log::info!("Running scheduled job {}", job.job);
+            let (succeeded, result) = match run_job(job.job, &main_app).await {
+                Ok(result) => (true, result),
+                Err(err) => (false, err.to_string()),
+            };
+            log::info!("Scheduled job {} finished: {}", job.job, result);
+            let mut con = db::request_connection().await?;
+            crate::scheduler::record_job_run(&mut con, job.id, succeeded, &result).await
+        }
+        .into_actor(self)
+        .map(|result, actor, _ctx| {
+            actor.busy = false;
+            actor
+                .main_app
+                .do_send(gui::msgs::UpdateSchedulerStatus { running: None });
+            result
+        })
+    }
+}
+
+/// Run the job, and describe how it went.
+async fn run_job(
+    job: ScheduledJob,
+    main_app: &actix::Addr<gui::main_app::MainAppActor>,
+) -> anyhow::Result<String> {
+    Ok(match job {
+        ScheduledJob::Scan => {
+            let new_episodes = crate::actors::DbActor::from_registry()
+                .send(crate::msgs::ScanFiles { series: None })
+                .await??;
+            let num_new_files = new_episodes
+                .iter()
+                .map(|new_episodes| new_episodes.episodes.len())
+                .sum::<usize>();
+            main_app.do_send(gui::msgs::RefreshLinksDirectory);
+            main_app.send(gui::msgs::UpdateSeriesesList).await??;
+            main_app.do_send(gui::msgs::ShowNewEpisodes(new_episodes));
+            format!("registered {} new files", num_new_files)
+        }
+        ScheduledJob::CleanDangling => {
+            let dangling_files = crate::actors::DbActor::from_registry()
+                .send(crate::msgs::FindDanglingFiles)
+                .await??;
+            format!(
+                "found {} dangling files (use Clean Dangling to remove them)",
+                dangling_files.len()
+            )
+        }
+        ScheduledJob::RefreshLinks => {
+            let reports = main_app.send(gui::msgs::RefreshLinksDirectory).await??;
+            let summary = format!(
+                "refreshed {} links directories: {} created, {} removed",
+                reports.len(),
+                reports
+                    .iter()
+                    .map(|(_, report)| report.num_created)
+                    .sum::<usize>(),
+                reports
+                    .iter()
+                    .map(|(_, report)| report.num_removed)
+                    .sum::<usize>(),
+            );
+            let with_problems = reports
+                .iter()
+                .filter(|(_, report)| report.has_problems())
+                .map(|(dir_path, _)| dir_path.display().to_string())
+                .collect::<Vec<_>>();
+            if !with_problems.is_empty() {
+                anyhow::bail!("{} - problems in {}", summary, with_problems.join(", "));
+            }
+            summary
+        }
+        ScheduledJob::Download => {
+            let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+            let run = crate::actors::DownloadRunner::from_registry().send(
+                crate::msgs::RunAllDownloadCommands {
+                    media_type: None,
+                    concurrency: crate::downloads::DEFAULT_CONCURRENCY,
+                    timeout: crate::downloads::DEFAULT_TIMEOUT,
+                    progress: progress_tx,
+                },
+            );
+            let mut num_succeeded = 0;
+            let mut num_failed = 0;
+            let count_progress = async {
+                // Ends when the batch finishes and drops the sender.
+                while let Some(progress) = progress_rx.recv().await {
+                    match &progress.state {
+                        crate::downloads::DownloadState::Finished(download_run)
+                            if download_run.succeeded() =>
+                        {
+                            num_succeeded += 1
+                        }
+                        crate::downloads::DownloadState::Finished(_)
+                        | crate::downloads::DownloadState::Failed(_) => num_failed += 1,
+                        _ => {}
+                    }
+                    main_app.do_send(gui::msgs::ShowDownloadProgress(progress));
+                }
+            };
+            let (result, ()) = futures::future::join(run, count_progress).await;
+            let new_episodes = result??;
+            main_app.do_send(gui::msgs::RefreshLinksDirectory);
+            main_app.send(gui::msgs::UpdateSeriesesList).await??;
+            main_app.do_send(gui::msgs::ShowNewEpisodes(new_episodes));
+            let summary = format!(
+                "{} download commands succeeded, {} failed",
+                num_succeeded, num_failed
+            );
+            if 0 < num_failed {
+                anyhow::bail!(summary);
+            }
+            summary
+        }
+    })
+}
+
+impl actix::Handler<gui::msgs::RunScheduledJob> for SchedulerActor {
+    type Result = ResponseActFuture<Self, anyhow::Result<()>>;
+
+    fn handle(
+        &mut self,
+        msg: gui::msgs::RunScheduledJob,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        if self.busy {
+            return Box::pin(actix::fut::ready(Err(anyhow::anyhow!(
+                "Another scheduled job is running"
+            ))));
+        }
+        self.busy = true;
+        let job_id = msg.0;
+        Box::pin(
+            async move {
+                let mut con = db::request_connection().await?;
+                crate::scheduler::load_scheduled_job(&mut con, job_id).await
+            }
+            .into_actor(self)
+            .then(|job, actor, _ctx| {
+                actor.busy = false;
+                match job {
+                    Ok(job) => futures::future::Either::Left(actor.run_job(job)),
+                    Err(err) => futures::future::Either::Right(actix::fut::ready(Err(err))),
+                }
+            }),
+        )
+    }
+}
@@ -5,9 +5,10 @@ use hashbrown::HashMap;
 
 use sqlx::prelude::*;
 
+use crate::chapter_number::{self, ChapterNumber};
 use crate::gui::directory::{DirectoryActor, DirectoryWidgets};
 use crate::models;
-use crate::util::db;
+use crate::util::db::{self, FromRowWithExtra};
 use crate::util::edit_mode::EditMode;
 use crate::util::TypedQuark;
 
@@ -27,6 +28,9 @@ pub struct SeriesActor {
     episode_sort_and_filter_data: TypedQuark<EpisodeSortAndFilterData>,
     #[builder(setter(skip), default)]
     directories: HashMap<i64, actix::Addr<DirectoryActor>>,
+    /// Episodes to scroll to once they are loaded.
+    #[builder(setter(skip), default)]
+    episodes_to_reveal: Vec<i64>,
 }
 
 pub struct SeriesSortAndFilterData {
@@ -46,14 +50,14 @@ impl core::convert::From<(i32, i32, &models::Series)> for SeriesSortAndFilterDat
 }
 
 struct EpisodeSortAndFilterData {
-    number: i64,
+    number: ChapterNumber,
     volume: Option<i64>,
 }
 
 impl core::convert::From<&models::Episode> for EpisodeSortAndFilterData {
     fn from(episode: &models::Episode) -> Self {
         Self {
-            number: episode.number,
+            number: episode.number.clone(),
             volume: episode.volume,
         }
     }
@@ -62,9 +66,11 @@ impl core::convert::From<&models::Episode> for EpisodeSortAndFilterData {
 impl actix::Actor for SeriesActor {
     type Context = actix::Context<Self>;
 
-    fn started(&mut self, _ctx: &mut Self::Context) {
+    fn started(&mut self, ctx: &mut Self::Context) {
         self.update_widgets_from_model();
         self.set_order_func();
+        self.update_analysis(ctx);
+        self.update_download_log(ctx);
     }
 }
 
@@ -79,6 +85,8 @@ pub struct SeriesWidgets {
     txt_download_command: gtk::Entry,
     #[prop_sync(set, get)]
     txt_download_command_dir: gtk::Entry,
+    #[prop_sync(set, get)]
+    txt_series_source: gtk::Entry,
     tgl_series_unread: gtk::ToggleButton,
     rvl_episodes: gtk::Revealer,
     lst_episodes: gtk::ListBox,
@@ -87,6 +95,13 @@ pub struct SeriesWidgets {
     btn_save_series: gtk::Button,
     btn_cancel_series_edit: gtk::Button,
     btn_save_new_series: gtk::Button,
+    btn_continue_reading: gtk::Button,
+    btn_series_analysis: gtk::MenuButton,
+    lbl_series_analysis_badge: gtk::Label,
+    lbl_series_analysis: gtk::Label,
+    btn_download_log: gtk::MenuButton,
+    lbl_download_status: gtk::Label,
+    lbl_download_log: gtk::Label,
 }
 
 impl actix::Handler<woab::Signal> for SeriesActor {
@@ -211,31 +226,7 @@ impl actix::Handler<woab::Signal> for SeriesActor {
                     icon_position,
                 ) {
                     (_, gtk::EntryIconPosition::Primary) => {
-                        let download_command = self.widgets.txt_download_command.text();
-                        let download_command = download_command.as_str();
-                        if !download_command.is_empty() {
-                            use std::process::Command;
-                            let mut command = if cfg!(target_os = "windows") {
-                                let mut command = Command::new("cmd");
-                                command.arg("/C");
-                                command
-                            } else {
-                                let mut command = Command::new("sh");
-                                command.arg("-c");
-                                command
-                            };
-
-                            command.arg(download_command);
-
-                            let download_command_dir = self.widgets.txt_download_command_dir.text();
-                            let download_command_dir = download_command_dir.as_str();
-                            if !download_command_dir.is_empty() {
-                                command.current_dir(download_command_dir);
-                            }
-                            if let Err(err) = command.spawn() {
-                                log::error!("Failed to spawn {:?}: {}", command, err);
-                            }
-                        }
+                        self.run_download_command(ctx);
                     }
                     (true, gtk::EntryIconPosition::Secondary) => {
                         self.widgets.txt_download_command.set_text("");
@@ -244,11 +235,27 @@ impl actix::Handler<woab::Signal> for SeriesActor {
                 }
                 None
             }
+            "continue_reading" => {
+                self.continue_reading(ctx);
+                None
+            }
             _ => msg.cant_handle()?,
         })
     }
 }
 
+impl actix::Handler<crate::gui::msgs::ContinueReading> for SeriesActor {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        _msg: crate::gui::msgs::ContinueReading,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
+        self.continue_reading(ctx);
+    }
+}
+
 impl actix::Handler<crate::util::edit_mode::InitiateSave> for SeriesActor {
     type Result = actix::ResponseActFuture<Self, anyhow::Result<i64>>;
 
@@ -263,20 +270,22 @@ impl actix::Handler<crate::util::edit_mode::InitiateSave> for SeriesActor {
             cbo_series_media_type,
             txt_download_command,
             txt_download_command_dir,
+            txt_series_source,
         } = self.widgets.get_props();
         Box::pin(
             async move {
                 if series_id < 0 {
                     let query = sqlx::query(
                         r#"
-                    INSERT INTO serieses(name, media_type, download_command, download_command_dir)
-                    VALUES(?, ?, ?, ?)
+                    INSERT INTO serieses(name, media_type, download_command, download_command_dir, source)
+                    VALUES(?, ?, ?, ?, ?)
                 "#,
                     )
                     .bind(txt_series_name)
                     .bind(cbo_series_media_type.parse::<i64>().unwrap())
                     .bind(txt_download_command)
-                    .bind(txt_download_command_dir);
+                    .bind(txt_download_command_dir)
+                    .bind(txt_series_source);
                     let mut con = db::request_connection().await?;
                     let query_result = query.execute(con.acquire().await?).await?;
                     Ok(query_result.last_insert_rowid())
@@ -288,6 +297,7 @@ impl actix::Handler<crate::util::edit_mode::InitiateSave> for SeriesActor {
                       , media_type = ?
                       , download_command = ?
                       , download_command_dir = ?
+                      , source = ?
                     WHERE id == ?
                 "#,
                     )
@@ -295,12 +305,18 @@ impl actix::Handler<crate::util::edit_mode::InitiateSave> for SeriesActor {
                     .bind(cbo_series_media_type.parse::<i64>().unwrap())
                     .bind(txt_download_command)
                     .bind(txt_download_command_dir)
+                    .bind(txt_series_source)
                     .bind(series_id);
                     let mut con = db::request_connection().await?;
-                    let query_result = query.execute(con.acquire().await?).await?;
+                    let mut tx = con.begin().await?;
+                    let old_base_dir =
+                        crate::library_root::series_base_dir(&mut tx, series_id).await?;
+                    let query_result = query.execute(tx.acquire().await?).await?;
                     if query_result.rows_affected() == 0 {
                         anyhow::bail!("Affected 0 serieses with id={}", series_id);
                     }
+                    crate::library_root::rebase_series(&mut tx, series_id, &old_base_dir).await?;
+                    tx.commit().await?;
                     Ok(series_id)
                 }
             }
@@ -340,10 +356,44 @@ impl
                 self.update_episodes(ctx, None);
             }
             self.update_sort_and_filter_data();
+            self.update_analysis(ctx);
         }
     }
 }
 
+impl actix::Handler<crate::gui::msgs::RevealEpisodes> for SeriesActor {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: crate::gui::msgs::RevealEpisodes,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
+        self.episodes_to_reveal = msg.0;
+        if self.widgets.tgl_series_unread.is_active() {
+            self.update_episodes(ctx, None);
+        } else {
+            // Toggling loads the episodes and reveals them.
+            let tgl_series_unread = self.widgets.tgl_series_unread.clone();
+            woab::spawn_outside(async move {
+                tgl_series_unread.set_active(true);
+            });
+        }
+    }
+}
+
+impl actix::Handler<crate::gui::msgs::ShowDownloadProgress> for SeriesActor {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: crate::gui::msgs::ShowDownloadProgress,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        self.show_download_state(&msg.0.state);
+    }
+}
+
 impl actix::Handler<woab::Signal<i64>> for SeriesActor {
     type Result = woab::SignalResult;
 
@@ -354,11 +404,9 @@ impl actix::Handler<woab::Signal<i64>> for SeriesActor {
                 ctx.spawn(
                     async move {
                         let mut con = db::request_connection().await.unwrap();
-                        let query = sqlx::query(
-                            "UPDATE episodes SET date_of_read = datetime() WHERE id == ?",
-                        )
-                        .bind(episode_id);
-                        query.execute(con.acquire().await.unwrap()).await.unwrap();
+                        crate::read_events::mark_read(&mut con, episode_id, "gui")
+                            .await
+                            .unwrap();
                     }
                     .into_actor(self)
                     .then(move |_, actor, ctx| {
@@ -376,10 +424,9 @@ impl actix::Handler<woab::Signal<i64>> for SeriesActor {
                 ctx.spawn(
                     async move {
                         let mut con = db::request_connection().await.unwrap();
-                        let query =
-                            sqlx::query("UPDATE episodes SET date_of_read = NULL WHERE id == ?")
-                                .bind(episode_id);
-                        query.execute(con.acquire().await.unwrap()).await.unwrap();
+                        crate::read_events::mark_unread(&mut con, episode_id)
+                            .await
+                            .unwrap();
                     }
                     .into_actor(self)
                     .then(move |_, actor, ctx| {
@@ -393,6 +440,50 @@ impl actix::Handler<woab::Signal<i64>> for SeriesActor {
                 );
                 None
             }
+            "mark_read_up_to" | "mark_volume_read" | "mark_volume_unread" | "mark_all_read"
+            | "mark_all_unread" => {
+                use crate::read_events::BulkSelection;
+                let episode = &self.episodes[&episode_id].model;
+                let (selection, read) = match msg.name() {
+                    "mark_read_up_to" => (
+                        BulkSelection::UpTo {
+                            volume: episode.volume,
+                            number: episode.number.clone(),
+                        },
+                        true,
+                    ),
+                    "mark_volume_read" => (BulkSelection::Volume(episode.volume), true),
+                    "mark_volume_unread" => (BulkSelection::Volume(episode.volume), false),
+                    "mark_all_read" => (BulkSelection::All, true),
+                    "mark_all_unread" => (BulkSelection::All, false),
+                    _ => unreachable!(),
+                };
+                let series_id = self.model.id;
+                ctx.spawn(
+                    async move {
+                        let mut con = db::request_connection().await?;
+                        crate::read_events::bulk_set_read_state(
+                            &mut con, series_id, selection, read, "gui",
+                        )
+                        .await
+                    }
+                    .into_actor(self)
+                    .map(|result, actor, ctx| match result {
+                        Ok(0) => {}
+                        Ok(_) => {
+                            actor.update_episodes(ctx, None);
+                            actor.update_series_read_stats(ctx);
+                            actor
+                                .main_app
+                                .do_send(crate::gui::msgs::RefreshLinksDirectory);
+                        }
+                        Err(err) => {
+                            log::error!("Cannot change read state: {}", err);
+                        }
+                    }),
+                );
+                None
+            }
             "edit_episode" => {
                 let episode = &self.episodes[&episode_id];
                 ctx.spawn(
@@ -422,8 +513,11 @@ impl actix::Handler<woab::Signal<i64>> for SeriesActor {
                         .with_edit_widget(
                             episode.widgets.txt_chapter.clone(),
                             "changed",
-                            episode.model.number.to_string(),
-                            |text| match text.parse::<i64>() {
+                            chapter_number::format_range(
+                                &episode.model.number,
+                                episode.model.number_end.as_ref(),
+                            ),
+                            |text| match chapter_number::parse_range(text) {
                                 Ok(_) => Ok(()),
                                 Err(err) => Err(err.to_string()),
                             },
@@ -444,6 +538,7 @@ impl actix::Handler<woab::Signal<i64>> for SeriesActor {
                         .into_actor(self)
                         .then(move |_, actor, ctx| {
                             actor.update_episodes(ctx, Some(episode_id));
+                            actor.update_analysis(ctx);
                             futures::future::ready(())
                         }),
                 );
@@ -476,7 +571,10 @@ impl actix::Handler<woab::Signal<i64>> for SeriesActor {
                         query.execute(con.acquire().await.unwrap()).await.unwrap();
                         lst_episodes.remove(&row_episode);
                     }
-                    .into_actor(self),
+                    .into_actor(self)
+                    .map(|_, actor, ctx| {
+                        actor.update_series_read_stats(ctx);
+                    }),
                 );
                 None
             }
@@ -485,9 +583,9 @@ impl actix::Handler<woab::Signal<i64>> for SeriesActor {
                 ctx.spawn(
                     async move {
                         let mut con = db::request_connection().await.unwrap();
-                        let (program,): (String,) = sqlx::query_as(
+                        let (program, base_dir): (String, String) = sqlx::query_as(
                             r#"
-                        SELECT media_types.program
+                        SELECT media_types.program, media_types.base_dir
                         FROM serieses
                         INNER JOIN media_types ON serieses.media_type = media_types.id
                         WHERE serieses.id = ?
@@ -497,11 +595,14 @@ impl actix::Handler<woab::Signal<i64>> for SeriesActor {
                         .fetch_one(con.acquire().await.unwrap())
                         .await
                         .unwrap();
-                        program
+                        (program, base_dir)
                     }
                     .into_actor(self)
-                    .then(move |program, actor, _ctx| {
-                        let file = &actor.episodes[&episode_id].model.file;
+                    .then(move |(program, base_dir), actor, _ctx| {
+                        let file = &crate::library_root::resolve(
+                            &base_dir,
+                            &actor.episodes[&episode_id].model.file,
+                        );
                         match std::process::Command::new(&program).arg(file).spawn() {
                             Ok(_) => (),
                             Err(err) => {
@@ -538,11 +639,76 @@ impl SeriesActor {
             cbo_series_media_type: self.model.media_type.to_string(),
             txt_download_command: self.model.download_command.as_deref().unwrap_or(""),
             txt_download_command_dir: self.model.download_command_dir.as_deref().unwrap_or(""),
+            txt_series_source: self.model.source.as_deref().unwrap_or(""),
         });
         self.widgets.tgl_series_unread.set_label(&format!(
             "{}/{}",
             self.series_read_stats.num_unread, self.series_read_stats.num_episodes
         ));
+        self.widgets
+            .btn_continue_reading
+            .set_sensitive(0 < self.series_read_stats.num_unread);
+    }
+
+    fn continue_reading(&mut self, ctx: &mut actix::Context<Self>) {
+        let series_id = self.model.id;
+        ctx.spawn(
+            async move {
+                let mut con = db::request_connection().await?;
+                let Some(FromRowWithExtra {
+                    data: episode,
+                    extra: launch_settings,
+                }) = crate::read_events::find_next_unread(&mut con, series_id).await?
+                else {
+                    return Ok(None);
+                };
+                // Don't hold a connection from the pool while the program is running.
+                drop(con);
+                let mut child = tokio::process::Command::new(&launch_settings.program)
+                    .arg(&episode.file)
+                    .spawn()
+                    .map_err(|err| {
+                        anyhow::anyhow!(
+                            "Cannot play {:?} with {:?}: {}",
+                            episode.file,
+                            launch_settings.program,
+                            err
+                        )
+                    })?;
+                if !launch_settings.mark_read_on_exit {
+                    return Ok(None);
+                }
+                let exit_status = child.wait().await?;
+                if !exit_status.success() {
+                    log::warn!(
+                        "Not marking {:?} as read - {:?} exited with {}",
+                        episode.name,
+                        launch_settings.program,
+                        exit_status
+                    );
+                    return Ok(None);
+                }
+                let mut con = db::request_connection().await?;
+                crate::read_events::mark_read(&mut con, episode.id, "continue").await?;
+                Ok::<_, anyhow::Error>(Some(episode.id))
+            }
+            .into_actor(self)
+            .map(|result, actor, ctx| match result {
+                Ok(Some(episode_id)) => {
+                    if actor.widgets.rvl_episodes.reveals_child() {
+                        actor.update_episodes(ctx, Some(episode_id));
+                    }
+                    actor.update_series_read_stats(ctx);
+                    actor
+                        .main_app
+                        .do_send(crate::gui::msgs::RefreshLinksDirectory);
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    log::error!("Cannot continue reading: {}", err);
+                }
+            }),
+        );
     }
 
     fn update_series_read_stats(&mut self, ctx: &mut actix::Context<Self>) {
@@ -561,26 +727,223 @@ impl SeriesActor {
                 query.fetch_one(con.acquire().await.unwrap()).await.unwrap()
             }
             .into_actor(self)
-            .then(move |result, actor, _ctx| {
+            .then(move |result, actor, ctx| {
                 actor.series_read_stats = result;
                 actor.update_widgets_from_model();
                 actor.update_sort_and_filter_data();
+                actor.update_analysis(ctx);
                 futures::future::ready(())
             }),
         );
     }
 
+    fn update_analysis(&mut self, ctx: &mut actix::Context<Self>) {
+        let series_id = self.model.id;
+        if series_id < 0 {
+            return;
+        }
+        ctx.spawn(
+            async move {
+                let mut con = db::request_connection().await?;
+                crate::series_analysis::analyze_series(&mut con, series_id).await
+            }
+            .into_actor(self)
+            .map(|analysis, actor, _ctx| {
+                let analysis = match analysis {
+                    Ok(analysis) => analysis,
+                    Err(err) => {
+                        log::error!("Cannot analyze {:?}: {}", actor.model.name, err);
+                        return;
+                    }
+                };
+                if analysis.is_empty() {
+                    actor.widgets.btn_series_analysis.hide();
+                    return;
+                }
+                actor
+                    .widgets
+                    .lbl_series_analysis_badge
+                    .set_text(&format!("⚠ {}", analysis.num_problems()));
+                actor.widgets.lbl_series_analysis.set_text(
+                    &analysis
+                        .problem_descriptions()
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                );
+                actor.widgets.btn_series_analysis.show();
+            }),
+        );
+    }
+
+    fn run_download_command(&mut self, ctx: &mut actix::Context<Self>) {
+        let series_id = self.model.id;
+        if series_id < 0 {
+            return;
+        }
+        self.show_download_state(&crate::downloads::DownloadState::Running);
+        ctx.spawn(
+            crate::actors::DownloadRunner::from_registry()
+                .send(crate::msgs::RunDownloadCommand {
+                    series: series_id,
+                    timeout: crate::downloads::DEFAULT_TIMEOUT,
+                })
+                .into_actor(self)
+                .map(|result, actor, _ctx| {
+                    actor.show_download_state(&match result.unwrap() {
+                        Ok((download_run, new_episodes)) => {
+                            actor
+                                .main_app
+                                .do_send(crate::gui::msgs::ShowNewEpisodes(new_episodes));
+                            crate::downloads::DownloadState::Finished(download_run)
+                        }
+                        Err(err) => {
+                            log::error!(
+                                "Cannot run the download command of {:?}: {}",
+                                actor.model.name,
+                                err
+                            );
+                            crate::downloads::DownloadState::Failed(err.to_string())
+                        }
+                    });
+                    actor
+                        .main_app
+                        .do_send(crate::gui::msgs::RefreshLinksDirectory);
+                    actor.main_app.do_send(crate::gui::msgs::UpdateSeriesesList);
+                }),
+        );
+    }
+
+    fn update_download_log(&mut self, ctx: &mut actix::Context<Self>) {
+        let series_id = self.model.id;
+        if series_id < 0 {
+            return;
+        }
+        ctx.spawn(
+            async move {
+                let mut con = db::request_connection().await?;
+                crate::downloads::latest_download_run(&mut con, series_id).await
+            }
+            .into_actor(self)
+            .map(|download_run, actor, _ctx| match download_run {
+                Ok(Some(download_run)) => actor.show_download_run(&download_run),
+                Ok(None) => {}
+                Err(err) => {
+                    log::error!(
+                        "Cannot load the download log of {:?}: {}",
+                        actor.model.name,
+                        err
+                    );
+                }
+            }),
+        );
+    }
+
+    fn show_download_state(&self, state: &crate::downloads::DownloadState) {
+        use crate::downloads::DownloadState;
+        match state {
+            DownloadState::Queued | DownloadState::Running => {
+                self.widgets.lbl_download_status.set_text("⏳");
+                self.widgets
+                    .btn_download_log
+                    .set_tooltip_text(Some(&format!("Download command is {}", state.describe())));
+            }
+            DownloadState::Finished(download_run) => self.show_download_run(download_run),
+            DownloadState::Failed(err) => {
+                self.widgets.lbl_download_status.set_text("✘");
+                self.widgets.btn_download_log.set_tooltip_text(Some(err));
+                self.widgets.lbl_download_log.set_text(err);
+            }
+        }
+        self.widgets.btn_download_log.show();
+    }
+
+    fn show_download_run(&self, download_run: &crate::downloads::DownloadRun) {
+        self.widgets
+            .lbl_download_status
+            .set_text(if download_run.succeeded() {
+                "✔"
+            } else {
+                "✘"
+            });
+        self.widgets
+            .btn_download_log
+            .set_tooltip_text(Some(&format!(
+                "Last download {} at {}",
+                download_run.status(),
+                download_run.date_finished
+            )));
+        self.widgets.lbl_download_log.set_text(&format!(
+            "$ {}\nStarted at {}, {} after {} seconds\n\n{}",
+            download_run.command,
+            download_run.date_started,
+            download_run.status(),
+            (download_run.date_finished - download_run.date_started).num_seconds(),
+            download_run.output,
+        ));
+        self.widgets.btn_download_log.show();
+    }
+
+    fn scroll_to_revealed_episodes(&mut self, ctx: &mut actix::Context<Self>) {
+        let rows = self
+            .episodes_to_reveal
+            .iter()
+            .filter_map(|episode_id| self.episodes.get(episode_id))
+            .map(|episode| episode.widgets.row_episode.clone())
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            return;
+        }
+        self.episodes_to_reveal.clear();
+        let row_series = self.widgets.row_series.clone();
+        ctx.spawn(
+            async move {
+                // Let the revealer finish expanding, so that the rows are where they will stay.
+                actix::clock::sleep(core::time::Duration::from_millis(300)).await;
+                let Some(lst_serieses) = row_series
+                    .parent()
+                    .and_then(|parent| parent.downcast::<gtk::ListBox>().ok())
+                else {
+                    return;
+                };
+                let Some((y, first_row)) = rows
+                    .iter()
+                    .filter_map(|row| {
+                        Some((row.translate_coordinates(&lst_serieses, 0, 0)?.1, row))
+                    })
+                    .min_by_key(|(y, _)| *y)
+                else {
+                    return;
+                };
+                if let Some(adjustment) = lst_serieses.adjustment() {
+                    adjustment.set_value(y as f64);
+                }
+                first_row.grab_focus();
+            }
+            .into_actor(self),
+        );
+    }
+
     fn update_episodes(&mut self, ctx: &mut actix::Context<Self>, episode_id: Option<i64>) {
         crate::actors::DbActor::from_registry().do_send(crate::msgs::RefreshList {
             orig_ids: self.episodes.keys().copied().collect(),
-            query: if let Some(episode_id) = episode_id {
-                sqlx::query_as("SELECT * FROM episodes WHERE series = ? and id = ?")
-                    .bind(self.model.id)
-                    .bind(episode_id)
-            } else {
-                sqlx::query_as("SELECT * FROM episodes WHERE series = ?").bind(self.model.id)
-            },
-            id_dlg: |row_data: &models::Episode| -> i64 { row_data.id },
+            query: sqlx::query_as(
+                r#"
+                SELECT episodes.*
+                    , COUNT(read_events.id) AS num_reads
+                    , group_concat(read_events.timestamp, char(10)) AS read_history
+                FROM episodes
+                LEFT JOIN read_events ON read_events.episode = episodes.id
+                WHERE episodes.series = ? AND (? IS NULL OR episodes.id = ?)
+                GROUP BY episodes.id
+                "#,
+            )
+            .bind(self.model.id)
+            .bind(episode_id)
+            .bind(episode_id),
+            id_dlg:
+                |row_data: &FromRowWithExtra<models::Episode, models::EpisodeReadHistory>| -> i64 {
+                    row_data.data.id
+                },
             addr: ctx.address(),
         });
     }
@@ -674,23 +1037,42 @@ impl SeriesActor {
                     .unwrap_or_else(|| "".to_owned()),
                 |_| Ok(()),
             )
+            .with_edit_widget(
+                self.widgets.txt_series_source.clone(),
+                "changed",
+                self.model.source.clone().unwrap_or_else(|| "".to_owned()),
+                |_| Ok(()),
+            )
     }
 }
 
-impl actix::Handler<crate::msgs::UpdateListRowData<models::Episode>> for SeriesActor {
+impl
+    actix::Handler<
+        crate::msgs::UpdateListRowData<
+            FromRowWithExtra<models::Episode, models::EpisodeReadHistory>,
+        >,
+    > for SeriesActor
+{
     type Result = ();
 
     fn handle(
         &mut self,
-        msg: crate::msgs::UpdateListRowData<models::Episode>,
+        msg: crate::msgs::UpdateListRowData<
+            FromRowWithExtra<models::Episode, models::EpisodeReadHistory>,
+        >,
         ctx: &mut Self::Context,
     ) -> Self::Result {
-        for data in msg.0 {
+        for FromRowWithExtra {
+            data,
+            extra: read_history,
+        } in msg.0
+        {
             match self.episodes.entry(data.id) {
                 hashbrown::hash_map::Entry::Occupied(mut entry) => {
                     let entry = entry.get_mut();
-                    if entry.model != data {
+                    if entry.model != data || entry.read_history != read_history {
                         entry.model = data;
+                        entry.read_history = read_history;
                         self.episode_sort_and_filter_data
                             .set(&entry.widgets.row_episode, (&entry.model).into());
                         entry.update_widgets_from_model();
@@ -709,6 +1091,7 @@ impl actix::Handler<crate::msgs::UpdateListRowData<models::Episode>> for SeriesA
                         .set(&widgets.row_episode, (&data).into());
                     let entry = entry.insert(EpisodeRow {
                         model: data,
+                        read_history,
                         widgets,
                     });
                     entry.update_widgets_from_model();
@@ -716,6 +1099,7 @@ impl actix::Handler<crate::msgs::UpdateListRowData<models::Episode>> for SeriesA
                 }
             }
         }
+        self.scroll_to_revealed_episodes(ctx);
     }
 }
 
@@ -821,8 +1205,38 @@ impl actix::Handler<crate::gui::msgs::GetBaseDirForMediaType> for SeriesActor {
     }
 }
 
+impl actix::Handler<crate::gui::msgs::GetFileTypesFilterForMediaType> for SeriesActor {
+    type Result =
+        actix::ResponseActFuture<Self, anyhow::Result<crate::files_discovery::FileTypesFilter>>;
+
+    fn handle(
+        &mut self,
+        _msg: crate::gui::msgs::GetFileTypesFilterForMediaType,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let media_type = self.model.media_type;
+        Box::pin(
+            async move {
+                let query = sqlx::query_as(
+                    "SELECT file_types, adjacent_file_types FROM media_types WHERE id = ?",
+                )
+                .bind(media_type);
+                let mut con = db::request_connection().await?;
+                let (file_types, adjacent_file_types): (String, String) =
+                    query.fetch_one(con.acquire().await?).await?;
+                Ok(crate::files_discovery::FileTypesFilter::new(
+                    &file_types,
+                    &adjacent_file_types,
+                ))
+            }
+            .into_actor(self),
+        )
+    }
+}
+
 struct EpisodeRow {
     model: models::Episode,
+    read_history: models::EpisodeReadHistory,
     widgets: EpisodeWidgets,
 }
 
@@ -836,7 +1250,10 @@ impl EpisodeRow {
                 .volume
                 .map(|v| v.to_string())
                 .unwrap_or_else(|| "".to_owned()),
-            txt_chapter: &self.model.number.to_string(),
+            txt_chapter: &chapter_number::format_range(
+                &self.model.number,
+                self.model.number_end.as_ref(),
+            ),
         });
         self.widgets.stk_read_state.set_property(
             "visible-child-name",
@@ -846,6 +1263,32 @@ impl EpisodeRow {
                 "episode-is-not-read"
             },
         );
+        self.widgets
+            .lbl_read_count
+            .set_text(&match self.read_history.num_reads {
+                0 => "".to_owned(),
+                num_reads => format!("×{}", num_reads),
+            });
+        self.widgets
+            .lbl_read_count
+            .set_tooltip_text(self.read_history.read_history.as_deref());
+        self.widgets
+            .lbl_episode_metadata
+            .set_text(self.model.title.as_deref().unwrap_or(""));
+        let details = vec![
+            self.model.release_date.clone(),
+            self.model
+                .page_count
+                .map(|page_count| format!("{} pages", page_count)),
+            self.model.duration.map(crate::metadata::format_duration),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(", ");
+        self.widgets
+            .lbl_episode_metadata
+            .set_tooltip_text(Some(details.as_str()).filter(|details| !details.is_empty()));
     }
 }
 
@@ -861,6 +1304,8 @@ struct EpisodeWidgets {
     #[prop_sync(set, get)]
     txt_file: gtk::Entry,
     stk_read_state: gtk::Stack,
+    lbl_read_count: gtk::Label,
+    lbl_episode_metadata: gtk::Label,
     stk_episode_edit: gtk::Stack,
     btn_save_episode: gtk::Button,
     btn_cancel_episode_edit: gtk::Button,
@@ -892,6 +1337,7 @@ impl actix::Handler<crate::util::edit_mode::InitiateSave<i64>> for SeriesActor {
         _ctx: &mut Self::Context,
     ) -> Self::Result {
         let episode_id = msg.0;
+        let series_id = self.model.id;
         let episode = &self.episodes[&episode_id];
         let EpisodeWidgetsPropGetter {
             txt_volume,
@@ -902,11 +1348,16 @@ impl actix::Handler<crate::util::edit_mode::InitiateSave<i64>> for SeriesActor {
 
         Box::pin(
             async move {
+                let (chapter, chapter_end) = chapter_number::parse_range(&txt_chapter)?;
+                let mut con = db::request_connection().await?;
+                let base_dir = crate::library_root::series_base_dir(&mut con, series_id).await?;
                 let query = sqlx::query(
                     r#"
                 UPDATE episodes
                 SET volume = ?
                   , number = ?
+                  , number_suffix = ?
+                  , number_end = ?
                   , name = ?
                   , file = ?
                 WHERE id == ?
@@ -917,11 +1368,12 @@ impl actix::Handler<crate::util::edit_mode::InitiateSave<i64>> for SeriesActor {
                 } else {
                     Some(txt_volume.parse::<i64>()?)
                 })
-                .bind(txt_chapter.parse::<i64>()?)
+                .bind(chapter.stored_number())
+                .bind(chapter.suffix().to_owned())
+                .bind(chapter_end)
                 .bind(txt_name)
-                .bind(txt_file)
+                .bind(crate::library_root::to_stored(&base_dir, &txt_file))
                 .bind(episode_id);
-                let mut con = db::request_connection().await?;
                 query.execute(con.acquire().await?).await?;
                 Ok(episode_id)
             }
@@ -945,3 +1397,15 @@ impl actix::Handler<crate::gui::msgs::RegisterActorAfterNew<crate::gui::director
         self.add_row_for_new_directory(ctx);
     }
 }
+
+impl actix::Handler<crate::gui::msgs::UpdateWatchedDirectories> for SeriesActor {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: crate::gui::msgs::UpdateWatchedDirectories,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        self.main_app.do_send(msg);
+    }
+}
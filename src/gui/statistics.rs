@@ -0,0 +1,161 @@
+use actix::prelude::*;
+use gtk::prelude::*;
+
+use crate::statistics::{ReadingStreak, ReadsInPeriod, Statistics};
+use crate::util::db;
+
+#[derive(typed_builder::TypedBuilder)]
+pub struct StatisticsActor {
+    widgets: StatisticsWindowWidgets,
+}
+
+#[derive(woab::WidgetsFromBuilder)]
+pub struct StatisticsWindowWidgets {
+    win_statistics: gtk::Window,
+    spn_statistics: gtk::Spinner,
+    lbl_statistics_total_reads: gtk::Label,
+    lbl_statistics_current_streak: gtk::Label,
+    lbl_statistics_average_time_to_read: gtk::Label,
+    lsm_statistics_reads_per_day: gtk::ListStore,
+    lsm_statistics_reads_per_week: gtk::ListStore,
+    lsm_statistics_reads_per_month: gtk::ListStore,
+    lsm_statistics_media_types: gtk::ListStore,
+    lsm_statistics_longest_streaks: gtk::ListStore,
+    lsm_statistics_unread_backlog: gtk::ListStore,
+}
+
+impl actix::Actor for StatisticsActor {
+    type Context = actix::Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.widgets.win_statistics.show();
+        self.refresh(ctx);
+    }
+}
+
+impl StatisticsActor {
+    fn refresh(&mut self, ctx: &mut actix::Context<Self>) {
+        self.widgets.spn_statistics.start();
+        ctx.spawn(
+            async {
+                let mut con = db::request_connection().await?;
+                crate::statistics::gather_statistics(&mut con).await
+            }
+            .into_actor(self)
+            .map(|statistics, actor, _ctx| {
+                actor.widgets.spn_statistics.stop();
+                match statistics {
+                    Ok(statistics) => actor.update_widgets(&statistics),
+                    Err(err) => log::error!("Cannot gather statistics: {}", err),
+                }
+            }),
+        );
+    }
+
+    fn update_widgets(&self, statistics: &Statistics) {
+        let mut details = Vec::new();
+        if statistics.total_chapters_read != statistics.total_reads {
+            details.push(format!("{} chapters", statistics.total_chapters_read));
+        }
+        if 0 < statistics.total_pages_read {
+            details.push(format!("{} pages", statistics.total_pages_read));
+        }
+        if 0 < statistics.total_minutes_read {
+            details.push(format!("{} minutes", statistics.total_minutes_read));
+        }
+        self.widgets
+            .lbl_statistics_total_reads
+            .set_text(&if details.is_empty() {
+                statistics.total_reads.to_string()
+            } else {
+                format!("{} ({})", statistics.total_reads, details.join(", "))
+            });
+        self.widgets
+            .lbl_statistics_current_streak
+            .set_text(&match statistics.current_streak {
+                Some(streak) => format_streak_length(&streak),
+                None => "none".to_owned(),
+            });
+        self.widgets
+            .lbl_statistics_average_time_to_read
+            .set_text(&match statistics.average_days_to_read {
+                Some(days) if days < 1.0 => format!("{:.0} hours", days * 24.0),
+                Some(days) => format!("{:.1} days", days),
+                None => "unknown".to_owned(),
+            });
+
+        for (lsm, reads) in [
+            (
+                &self.widgets.lsm_statistics_reads_per_day,
+                &statistics.reads_per_day,
+            ),
+            (
+                &self.widgets.lsm_statistics_reads_per_week,
+                &statistics.reads_per_week,
+            ),
+            (
+                &self.widgets.lsm_statistics_reads_per_month,
+                &statistics.reads_per_month,
+            ),
+        ] {
+            lsm.clear();
+            for ReadsInPeriod { period, num_reads } in reads.iter() {
+                let it = lsm.append();
+                lsm.set_value(&it, 0, &period.to_value());
+                lsm.set_value(&it, 1, &num_reads.to_value());
+            }
+        }
+
+        let lsm = &self.widgets.lsm_statistics_media_types;
+        lsm.clear();
+        for media_type in statistics.media_types.iter() {
+            let it = lsm.append();
+            lsm.set_value(&it, 0, &media_type.name.to_value());
+            lsm.set_value(&it, 1, &media_type.num_serieses.to_value());
+            lsm.set_value(&it, 2, &media_type.num_episodes.to_value());
+            lsm.set_value(&it, 3, &media_type.num_unread.to_value());
+            lsm.set_value(&it, 4, &media_type.num_reads.to_value());
+            lsm.set_value(&it, 5, &media_type.pages_read.to_value());
+            lsm.set_value(&it, 6, &media_type.minutes_read.to_value());
+        }
+
+        let lsm = &self.widgets.lsm_statistics_longest_streaks;
+        lsm.clear();
+        for streak in statistics.longest_streaks.iter() {
+            let it = lsm.append();
+            lsm.set_value(&it, 0, &streak.start.to_string().to_value());
+            lsm.set_value(&it, 1, &streak.end.to_string().to_value());
+            lsm.set_value(&it, 2, &streak.num_days().to_value());
+        }
+
+        let lsm = &self.widgets.lsm_statistics_unread_backlog;
+        lsm.clear();
+        for series in statistics.unread_backlog.iter() {
+            let it = lsm.append();
+            lsm.set_value(&it, 0, &series.series_name.to_value());
+            lsm.set_value(&it, 1, &series.media_type_name.to_value());
+            lsm.set_value(&it, 2, &series.num_unread.to_value());
+        }
+    }
+}
+
+fn format_streak_length(streak: &ReadingStreak) -> String {
+    match streak.num_days() {
+        1 => "1 day".to_owned(),
+        num_days => format!("{} days", num_days),
+    }
+}
+
+impl actix::Handler<woab::Signal> for StatisticsActor {
+    type Result = woab::SignalResult;
+
+    fn handle(&mut self, msg: woab::Signal, ctx: &mut Self::Context) -> Self::Result {
+        Ok(match msg.name() {
+            "refresh_statistics" => {
+                self.refresh(ctx);
+                None
+            }
+            _ => msg.cant_handle()?,
+        })
+    }
+}
@@ -1,9 +1,23 @@
 pub mod actors;
+pub mod chapter_number;
+pub mod cli;
+pub mod download_template;
+pub mod downloads;
+pub mod duplicates;
+pub mod export_import;
 pub mod files_discovery;
 mod gui;
+pub mod library_root;
+pub mod link_mode;
+pub mod link_template;
 pub mod links_handling;
+pub mod metadata;
 mod models;
 pub mod msgs;
+pub mod read_events;
+pub mod scheduler;
+pub mod series_analysis;
+pub mod statistics;
 mod util;
 
 pub use gui::start_gui;
@@ -37,4 +51,9 @@ pub struct CliArgs {
     dbfile: Option<String>,
     #[structopt(long)]
     linksdir: Option<String>,
+    /// How to create the entries of --linksdir: symlink, relative-symlink, hardlink, reflink or copy
+    #[structopt(long, default_value = "symlink")]
+    linksmode: link_mode::LinkMode,
+    #[structopt(subcommand)]
+    pub command: Option<cli::CliCommand>,
 }
@@ -0,0 +1,320 @@
+//! Paths of episode files and scanned directories are stored relative to the base directory of
+//! their media type, so that a library can be moved to another mount point by only changing the
+//! base directory. Paths outside the base directory, and paths of media types without a base
+//! directory, are stored as they are.
+//!
+//! Everything outside the database works with resolved paths - so stored paths should be resolved
+//! right after they are read, and converted right before they are written.
+
+use std::path::Path;
+
+use futures::TryStreamExt;
+use hashbrown::HashMap;
+use sqlx::prelude::*;
+
+/// How a path that is the base directory itself is stored.
+const BASE_DIR_ITSELF: &str = ".";
+
+fn trimmed(base_dir: &str) -> &str {
+    if base_dir == "/" {
+        ""
+    } else {
+        base_dir.trim_end_matches('/')
+    }
+}
+
+/// The path to store in the database for `path`.
+pub fn to_stored(base_dir: &str, path: &str) -> String {
+    let base_dir = trimmed(base_dir);
+    if base_dir.is_empty() {
+        return path.to_owned();
+    }
+    let path = path.trim_end_matches('/');
+    if path == base_dir {
+        return BASE_DIR_ITSELF.to_owned();
+    }
+    match path
+        .strip_prefix(base_dir)
+        .and_then(|rest| rest.strip_prefix('/'))
+    {
+        Some(relative) => relative.to_owned(),
+        None => path.to_owned(),
+    }
+}
+
+/// The actual path of a path stored in the database.
+pub fn resolve(base_dir: &str, stored: &str) -> String {
+    let base_dir = trimmed(base_dir);
+    if base_dir.is_empty() || Path::new(stored).is_absolute() {
+        stored.to_owned()
+    } else if stored == BASE_DIR_ITSELF {
+        base_dir.to_owned()
+    } else {
+        format!("{}/{}", base_dir, stored)
+    }
+}
+
+/// The base directories of all the serieses, for resolving and storing paths in bulk.
+#[derive(Debug, Default)]
+pub struct LibraryRoots {
+    series_base_dirs: HashMap<i64, String>,
+}
+
+impl LibraryRoots {
+    pub async fn load(con: &mut sqlx::SqliteConnection) -> anyhow::Result<Self> {
+        let series_base_dirs = sqlx::query_as::<_, (i64, String)>(
+            r#"
+            SELECT serieses.id, media_types.base_dir
+            FROM serieses
+            INNER JOIN media_types ON media_types.id = serieses.media_type
+            "#,
+        )
+        .fetch(con.acquire().await?)
+        .try_collect()
+        .await?;
+        Ok(Self { series_base_dirs })
+    }
+
+    pub fn base_dir(&self, series_id: i64) -> &str {
+        self.series_base_dirs
+            .get(&series_id)
+            .map(|base_dir| base_dir.as_str())
+            .unwrap_or("")
+    }
+
+    pub fn resolve(&self, series_id: i64, stored: &str) -> String {
+        resolve(self.base_dir(series_id), stored)
+    }
+
+    pub fn to_stored(&self, series_id: i64, path: &str) -> String {
+        to_stored(self.base_dir(series_id), path)
+    }
+}
+
+pub async fn series_base_dir(
+    con: &mut sqlx::SqliteConnection,
+    series_id: i64,
+) -> anyhow::Result<String> {
+    let (base_dir,): (String,) = sqlx::query_as(
+        r#"
+        SELECT media_types.base_dir
+        FROM serieses
+        INNER JOIN media_types ON media_types.id = serieses.media_type
+        WHERE serieses.id = ?
+        "#,
+    )
+    .bind(series_id)
+    .fetch_optional(con.acquire().await?)
+    .await?
+    .ok_or_else(|| anyhow::anyhow!("No series with id={}", series_id))?;
+    Ok(base_dir)
+}
+
+/// How many of the files of a media type would be found if its base directory was moved.
+#[derive(Debug)]
+pub struct RelocationCheck {
+    pub num_found: usize,
+    pub missing: Vec<String>,
+}
+
+impl RelocationCheck {
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct StoredEpisodePath {
+    id: i64,
+    file: String,
+}
+
+async fn media_type_episode_paths(
+    con: &mut sqlx::SqliteConnection,
+    media_type_id: i64,
+) -> anyhow::Result<Vec<StoredEpisodePath>> {
+    Ok(sqlx::query_as(
+        r#"
+        SELECT episodes.id, episodes.file
+        FROM episodes
+        INNER JOIN serieses ON serieses.id = episodes.series
+        WHERE serieses.media_type = ?
+        "#,
+    )
+    .bind(media_type_id)
+    .fetch(con.acquire().await?)
+    .try_collect()
+    .await?)
+}
+
+async fn media_type_base_dir(
+    con: &mut sqlx::SqliteConnection,
+    media_type_id: i64,
+) -> anyhow::Result<String> {
+    let (base_dir,): (String,) = sqlx::query_as("SELECT base_dir FROM media_types WHERE id = ?")
+        .bind(media_type_id)
+        .fetch_optional(con.acquire().await?)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("No media type with id={}", media_type_id))?;
+    Ok(base_dir)
+}
+
+/// Check which episode files of the media type exist under `new_base_dir`.
+pub async fn check_relocation(
+    con: &mut sqlx::SqliteConnection,
+    media_type_id: i64,
+    new_base_dir: &str,
+) -> anyhow::Result<RelocationCheck> {
+    if !trimmed(new_base_dir).is_empty()
+        && !tokio::fs::metadata(new_base_dir)
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+    {
+        anyhow::bail!("{:?} is not a directory", new_base_dir);
+    }
+    let old_base_dir = media_type_base_dir(con, media_type_id).await?;
+    let mut check = RelocationCheck {
+        num_found: 0,
+        missing: Vec::new(),
+    };
+    for StoredEpisodePath { file, .. } in media_type_episode_paths(con, media_type_id).await? {
+        let file = relocated(&old_base_dir, new_base_dir, &file);
+        if tokio::fs::metadata(&file).await.is_ok() {
+            check.num_found += 1;
+        } else {
+            check.missing.push(file);
+        }
+    }
+    Ok(check)
+}
+
+/// Where a stored path will be once the base directory moves. Absolute paths inside the old base
+/// directory move with it. Removing the base directory leaves everything where it was.
+fn relocated(old_base_dir: &str, new_base_dir: &str, stored: &str) -> String {
+    if trimmed(new_base_dir).is_empty() {
+        resolve(old_base_dir, stored)
+    } else {
+        resolve(new_base_dir, &to_stored(old_base_dir, stored))
+    }
+}
+
+/// Move the base directory of a media type. Paths inside the old or the new base directory are
+/// stored relative to the new one, and ignored files inside the old one are moved too.
+pub async fn relocate_base_dir(
+    con: &mut crate::SqlitePoolConnection,
+    media_type_id: i64,
+    new_base_dir: &str,
+) -> anyhow::Result<()> {
+    let mut tx = con.begin().await?;
+    move_base_dir(&mut tx, media_type_id, new_base_dir).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Like [`relocate_base_dir`], for callers that already run in a transaction.
+pub async fn move_base_dir(
+    tx: &mut sqlx::SqliteConnection,
+    media_type_id: i64,
+    new_base_dir: &str,
+) -> anyhow::Result<()> {
+    let old_base_dir = media_type_base_dir(tx, media_type_id).await?;
+
+    for StoredEpisodePath { id, file } in media_type_episode_paths(tx, media_type_id).await? {
+        let new_file = to_stored(new_base_dir, &relocated(&old_base_dir, new_base_dir, &file));
+        if new_file != file {
+            sqlx::query("UPDATE episodes SET file = ? WHERE id = ?")
+                .bind(new_file)
+                .bind(id)
+                .execute(tx.acquire().await?)
+                .await?;
+        }
+    }
+
+    let directories: Vec<(i64, String)> = sqlx::query_as(
+        r#"
+        SELECT directories.id, directories.dir
+        FROM directories
+        INNER JOIN serieses ON serieses.id = directories.series
+        WHERE serieses.media_type = ?
+        "#,
+    )
+    .bind(media_type_id)
+    .fetch(tx.acquire().await?)
+    .try_collect()
+    .await?;
+    for (id, dir) in directories {
+        let new_dir = to_stored(new_base_dir, &relocated(&old_base_dir, new_base_dir, &dir));
+        if new_dir != dir {
+            sqlx::query("UPDATE directories SET dir = ? WHERE id = ?")
+                .bind(new_dir)
+                .bind(id)
+                .execute(tx.acquire().await?)
+                .await?;
+        }
+    }
+
+    if !trimmed(&old_base_dir).is_empty() && !trimmed(new_base_dir).is_empty() {
+        let ignored_files: Vec<(String,)> = sqlx::query_as("SELECT file FROM ignored_files")
+            .fetch(tx.acquire().await?)
+            .try_collect()
+            .await?;
+        for (file,) in ignored_files {
+            let relative = to_stored(&old_base_dir, &file);
+            if Path::new(&relative).is_absolute() {
+                continue;
+            }
+            sqlx::query("UPDATE OR IGNORE ignored_files SET file = ? WHERE file = ?")
+                .bind(resolve(new_base_dir, &relative))
+                .bind(&file)
+                .execute(tx.acquire().await?)
+                .await?;
+        }
+    }
+
+    sqlx::query("UPDATE media_types SET base_dir = ? WHERE id = ?")
+        .bind(new_base_dir)
+        .bind(media_type_id)
+        .execute(tx.acquire().await?)
+        .await?;
+    Ok(())
+}
+
+/// Restore the paths of a series that moved to another media type, so that they stay relative to
+/// the new media type's base directory.
+pub async fn rebase_series(
+    con: &mut sqlx::SqliteConnection,
+    series_id: i64,
+    old_base_dir: &str,
+) -> anyhow::Result<()> {
+    let new_base_dir = series_base_dir(con, series_id).await?;
+    if new_base_dir == old_base_dir {
+        return Ok(());
+    }
+    for (select, update) in [
+        (
+            "SELECT id, file FROM episodes WHERE series = ?",
+            "UPDATE episodes SET file = ? WHERE id = ?",
+        ),
+        (
+            "SELECT id, dir FROM directories WHERE series = ?",
+            "UPDATE directories SET dir = ? WHERE id = ?",
+        ),
+    ] {
+        let rows: Vec<(i64, String)> = sqlx::query_as(select)
+            .bind(series_id)
+            .fetch(con.acquire().await?)
+            .try_collect()
+            .await?;
+        for (id, path) in rows {
+            let new_path = to_stored(&new_base_dir, &resolve(old_base_dir, &path));
+            if new_path != path {
+                sqlx::query(update)
+                    .bind(new_path)
+                    .bind(id)
+                    .execute(con.acquire().await?)
+                    .await?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,240 @@
+//! How the entries of a links directory are materialised.
+
+use core::fmt;
+use core::str::FromStr;
+use std::path::{Path, PathBuf};
+
+use sqlx::sqlite::Sqlite;
+use tokio::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LinkMode {
+    /// A symlink to the absolute path of the file.
+    #[default]
+    Symlink,
+    /// A symlink to the path of the file relative to the link, so that the links survive moving
+    /// the library and the links directory together.
+    RelativeSymlink,
+    /// A hardlink. The links directory must be on the same filesystem as the files.
+    Hardlink,
+    /// A copy-on-write copy, where the filesystem supports it. Falls back to a full copy.
+    Reflink,
+    /// A full copy. Recopied when the size or modification time of the file changes.
+    Copy,
+}
+
+impl LinkMode {
+    pub const ALL: [LinkMode; 5] = [
+        LinkMode::Symlink,
+        LinkMode::RelativeSymlink,
+        LinkMode::Hardlink,
+        LinkMode::Reflink,
+        LinkMode::Copy,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkMode::Symlink => "symlink",
+            LinkMode::RelativeSymlink => "relative-symlink",
+            LinkMode::Hardlink => "hardlink",
+            LinkMode::Reflink => "reflink",
+            LinkMode::Copy => "copy",
+        }
+    }
+
+    /// Whether `link` is already what materialising `target` in this mode would create.
+    pub async fn is_up_to_date(&self, target: &Path, link: &Path) -> anyhow::Result<bool> {
+        use std::os::unix::fs::MetadataExt;
+
+        let link_metadata = match fs::symlink_metadata(link).await {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(match self {
+            LinkMode::Symlink | LinkMode::RelativeSymlink => {
+                if !link_metadata.file_type().is_symlink() {
+                    return Ok(false);
+                }
+                fs::read_link(link).await? == self.symlink_target(target, link)?
+            }
+            LinkMode::Hardlink => {
+                let Ok(target_metadata) = fs::metadata(target).await else {
+                    return Ok(false);
+                };
+                link_metadata.is_file()
+                    && link_metadata.dev() == target_metadata.dev()
+                    && link_metadata.ino() == target_metadata.ino()
+            }
+            LinkMode::Reflink | LinkMode::Copy => {
+                let Ok(target_metadata) = fs::metadata(target).await else {
+                    return Ok(false);
+                };
+                link_metadata.is_file()
+                    && link_metadata.len() == target_metadata.len()
+                    && link_metadata.modified()? == target_metadata.modified()?
+            }
+        })
+    }
+
+    /// Create `link` for `target`. `link` must not exist.
+    pub async fn materialize(&self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        match self {
+            LinkMode::Symlink | LinkMode::RelativeSymlink => {
+                fs::symlink(self.symlink_target(target, link)?, link).await?;
+            }
+            LinkMode::Hardlink => {
+                fs::hard_link(target, link).await.map_err(|err| {
+                    anyhow::anyhow!(
+                        "Cannot hardlink {:?} to {:?} (they must be on the same filesystem): {}",
+                        link,
+                        target,
+                        err
+                    )
+                })?;
+            }
+            LinkMode::Reflink => {
+                let (target, link) = (target.to_owned(), link.to_owned());
+                tokio::task::spawn_blocking(move || {
+                    if let Err(err) = reflink(&target, &link) {
+                        log::debug!(
+                            "Cannot reflink {:?} to {:?} ({}) - copying instead",
+                            link,
+                            target,
+                            err
+                        );
+                        copy_with_mtime(&target, &link)?;
+                    }
+                    anyhow::Ok(())
+                })
+                .await??;
+            }
+            LinkMode::Copy => {
+                let (target, link) = (target.to_owned(), link.to_owned());
+                tokio::task::spawn_blocking(move || copy_with_mtime(&target, &link)).await??;
+            }
+        }
+        Ok(())
+    }
+
+    fn symlink_target(&self, target: &Path, link: &Path) -> anyhow::Result<PathBuf> {
+        Ok(if *self == LinkMode::RelativeSymlink {
+            let link_dir = std::path::absolute(link)?
+                .parent()
+                .map(|parent| parent.to_owned())
+                .unwrap_or_default();
+            relative_path(&link_dir, &std::path::absolute(target)?)
+        } else {
+            target.to_owned()
+        })
+    }
+}
+
+/// The path of `to` relative to the directory `from_dir`. Both must be absolute.
+fn relative_path(from_dir: &Path, to: &Path) -> PathBuf {
+    let from_components: Vec<_> = from_dir.components().collect();
+    let to_components: Vec<_> = to.components().collect();
+    let num_common = from_components
+        .iter()
+        .zip(to_components.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut result = PathBuf::new();
+    for _ in num_common..from_components.len() {
+        result.push("..");
+    }
+    for component in &to_components[num_common..] {
+        result.push(component);
+    }
+    result
+}
+
+/// Copy the file and give the copy the modification time of the original, so that
+/// [`LinkMode::is_up_to_date`] can tell when the original changes. A failed copy is removed, so
+/// that it does not stay behind as a file chapter-tracker does not know it created.
+fn copy_with_mtime(target: &Path, link: &Path) -> anyhow::Result<()> {
+    let result = (|| {
+        std::fs::copy(target, link)?;
+        let modified = std::fs::metadata(target)?.modified()?;
+        std::fs::File::options()
+            .write(true)
+            .open(link)?
+            .set_modified(modified)?;
+        anyhow::Ok(())
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(link);
+    }
+    result
+}
+
+#[cfg(target_os = "linux")]
+fn reflink(target: &Path, link: &Path) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // From linux/fs.h
+    const FICLONE: libc::c_ulong = 0x40049409;
+
+    let source = std::fs::File::open(target)?;
+    let destination = std::fs::File::options()
+        .write(true)
+        .create_new(true)
+        .open(link)?;
+    // SAFETY: both file descriptors are open for the duration of the call.
+    let result = unsafe { libc::ioctl(destination.as_raw_fd(), FICLONE as _, source.as_raw_fd()) };
+    if result != 0 {
+        let err = std::io::Error::last_os_error();
+        drop(destination);
+        std::fs::remove_file(link)?;
+        return Err(err);
+    }
+    destination.set_modified(source.metadata()?.modified()?)?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_target: &Path, _link: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "reflinks are only supported on Linux",
+    ))
+}
+
+impl fmt::Display for LinkMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LinkMode {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        LinkMode::ALL
+            .iter()
+            .copied()
+            .find(|mode| mode.as_str() == text)
+            .ok_or_else(|| anyhow::anyhow!("Unknown link mode {:?}", text))
+    }
+}
+
+impl sqlx::Type<Sqlite> for LinkMode {
+    fn type_info() -> sqlx::sqlite::SqliteTypeInfo {
+        <String as sqlx::Type<Sqlite>>::type_info()
+    }
+}
+
+impl<'q> sqlx::Encode<'q, Sqlite> for LinkMode {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        sqlx::Encode::<Sqlite>::encode(self.as_str(), buf)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Sqlite> for LinkMode {
+    fn decode(value: sqlx::sqlite::SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(<&str as sqlx::Decode<Sqlite>>::decode(value)?.parse()?)
+    }
+}
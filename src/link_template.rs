@@ -0,0 +1,302 @@
+//! Templates for the names of the links in the links directories, like
+//! `{series}/{volume:02}/{series} c{chapter:04}.{ext}`.
+//!
+//! The placeholders are:
+//!
+//! * `{series}` - the name of the series.
+//! * `{media_type}` - the name of the media type.
+//! * `{volume}` - the volume, or nothing if the episode has no volume.
+//! * `{chapter}` - the chapter number, or range of chapters (`10-12`).
+//! * `{id}` - the id of the episode.
+//! * `{filename}` - the name of the original file, without its extension.
+//! * `{ext}` - the extension of the original file.
+//!
+//! `{volume}`, `{chapter}` and `{id}` can be zero-padded with a width, e.g. `{chapter:04}`. Use `{{`
+//! and `}}` for literal braces.
+//!
+//! A `/` in the template creates subdirectories. A `/` in a placeholder's value is replaced with `_`,
+//! so that series names cannot escape the links directory, and empty path components (e.g. from
+//! `{volume}/` when there is no volume) are dropped. A template that renders to an empty path for an
+//! episode cannot be used to link it.
+
+use core::fmt::Write;
+use core::str::FromStr;
+use std::path::PathBuf;
+
+use crate::chapter_number::ChapterNumber;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkTemplate {
+    parts: Vec<TemplatePart<Placeholder>>,
+}
+
+/// A piece of a parsed template. Shared with other kinds of templates, which have their own
+/// placeholders.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TemplatePart<P> {
+    Literal(String),
+    Placeholder(P, Option<usize>),
+}
+
+pub(crate) trait TemplatePlaceholder: FromStr<Err = anyhow::Error> {
+    /// Whether the placeholder accepts a zero-padding width, like `{chapter:04}`.
+    fn can_pad(&self) -> bool;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Placeholder {
+    Series,
+    MediaType,
+    Volume,
+    Chapter,
+    Id,
+    Filename,
+    Ext,
+}
+
+impl TemplatePlaceholder for Placeholder {
+    fn can_pad(&self) -> bool {
+        matches!(
+            self,
+            Placeholder::Volume | Placeholder::Chapter | Placeholder::Id
+        )
+    }
+}
+
+impl FromStr for Placeholder {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Ok(match text {
+            "series" => Placeholder::Series,
+            "media_type" => Placeholder::MediaType,
+            "volume" => Placeholder::Volume,
+            "chapter" => Placeholder::Chapter,
+            "id" => Placeholder::Id,
+            "filename" => Placeholder::Filename,
+            "ext" => Placeholder::Ext,
+            _ => anyhow::bail!("Unknown placeholder {{{}}}", text),
+        })
+    }
+}
+
+/// The values for the placeholders of a single link.
+pub struct LinkTemplateValues<'a> {
+    pub series: &'a str,
+    pub media_type: &'a str,
+    pub volume: Option<i64>,
+    pub chapter: &'a ChapterNumber,
+    pub chapter_end: Option<&'a ChapterNumber>,
+    pub id: i64,
+    pub filename: &'a str,
+    pub ext: &'a str,
+}
+
+impl FromStr for LinkTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let parts = parse_template_parts(text)?;
+        if parts.is_empty() {
+            anyhow::bail!("Link template cannot be empty");
+        }
+        Ok(Self { parts })
+    }
+}
+
+/// Split a template to literals and placeholders, handling `{{`, `}}` and padding widths.
+pub(crate) fn parse_template_parts<P: TemplatePlaceholder>(
+    text: &str,
+) -> anyhow::Result<Vec<TemplatePart<P>>> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("{{") {
+            literal.push('{');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("}}") {
+            literal.push('}');
+            rest = after;
+        } else if c == '{' {
+            let end = rest
+                .find('}')
+                .ok_or_else(|| anyhow::anyhow!("Unclosed {{ in {:?}", text))?;
+            let (name, width) = match rest[1..end].split_once(':') {
+                Some((name, width)) => (name, Some(width)),
+                None => (&rest[1..end], None),
+            };
+            let placeholder: P = name.parse()?;
+            let width = if let Some(width) = width {
+                if !placeholder.can_pad() {
+                    anyhow::bail!("{{{}}} cannot be padded", name);
+                }
+                let width = width
+                    .strip_prefix('0')
+                    .and_then(|width| width.parse().ok())
+                    .ok_or_else(|| anyhow::anyhow!("Bad padding {:?} for {{{}}}", width, name))?;
+                Some(width)
+            } else {
+                None
+            };
+            if !literal.is_empty() {
+                parts.push(TemplatePart::Literal(core::mem::take(&mut literal)));
+            }
+            parts.push(TemplatePart::Placeholder(placeholder, width));
+            rest = &rest[end + 1..];
+        } else if c == '}' {
+            anyhow::bail!("Unmatched }} in {:?}", text);
+        } else {
+            literal.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(TemplatePart::Literal(literal));
+    }
+    Ok(parts)
+}
+
+impl LinkTemplate {
+    /// The path of the link, relative to the links directory.
+    pub fn render(&self, values: &LinkTemplateValues) -> anyhow::Result<PathBuf> {
+        let mut result = String::new();
+        for part in self.parts.iter() {
+            match part {
+                TemplatePart::Literal(literal) => result.push_str(literal),
+                TemplatePart::Placeholder(placeholder, width) => {
+                    let width = width.unwrap_or(0);
+                    let value = match placeholder {
+                        Placeholder::Series => values.series.to_owned(),
+                        Placeholder::MediaType => values.media_type.to_owned(),
+                        Placeholder::Volume => values
+                            .volume
+                            .map(|volume| format!("{:0width$}", volume, width = width))
+                            .unwrap_or_default(),
+                        Placeholder::Chapter => {
+                            let mut chapter = values.chapter.padded(width);
+                            if let Some(chapter_end) = values.chapter_end {
+                                write!(&mut chapter, "-{}", chapter_end.padded(width)).unwrap();
+                            }
+                            chapter
+                        }
+                        Placeholder::Id => format!("{:0width$}", values.id, width = width),
+                        Placeholder::Filename => values.filename.to_owned(),
+                        Placeholder::Ext => values.ext.to_owned(),
+                    };
+                    result.push_str(&value.replace('/', "_"));
+                }
+            }
+        }
+        let path: PathBuf = result
+            .split('/')
+            .filter(|component| !matches!(*component, "" | "." | ".."))
+            .collect();
+        if path.as_os_str().is_empty() {
+            anyhow::bail!(
+                "Link template renders to an empty path for episode {}",
+                values.id
+            );
+        }
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, values: &LinkTemplateValues) -> anyhow::Result<PathBuf> {
+        template.parse::<LinkTemplate>()?.render(values)
+    }
+
+    fn values(chapter: &ChapterNumber) -> LinkTemplateValues<'_> {
+        LinkTemplateValues {
+            series: "Series",
+            media_type: "Manga",
+            volume: Some(3),
+            chapter,
+            chapter_end: None,
+            id: 42,
+            filename: "series_c7",
+            ext: "cbz",
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("".parse::<LinkTemplate>().is_err());
+        assert!("{series".parse::<LinkTemplate>().is_err());
+        assert!("series}".parse::<LinkTemplate>().is_err());
+        assert!("{nonexistent}".parse::<LinkTemplate>().is_err());
+        assert!("{series:03}".parse::<LinkTemplate>().is_err());
+        assert!("{chapter:3}".parse::<LinkTemplate>().is_err());
+    }
+
+    #[test]
+    fn test_escaping() {
+        let chapter = ChapterNumber::from(7);
+        assert_eq!(
+            render("{{{series}}} {{chapter}}.{ext}", &values(&chapter)).unwrap(),
+            PathBuf::from("{Series} {chapter}.cbz")
+        );
+    }
+
+    #[test]
+    fn test_padding() {
+        let chapter = "7.5a".parse().unwrap();
+        let chapter_end = ChapterNumber::from(9);
+        let values = LinkTemplateValues {
+            chapter_end: Some(&chapter_end),
+            ..values(&chapter)
+        };
+        assert_eq!(
+            render(
+                "{series}/v{volume:02} c{chapter:03} #{id:04}.{ext}",
+                &values
+            )
+            .unwrap(),
+            PathBuf::from("Series/v03 c007.5a-009 #0042.cbz")
+        );
+    }
+
+    #[test]
+    fn test_sanitizing() {
+        let chapter = ChapterNumber::from(7);
+        let values = LinkTemplateValues {
+            series: "../AC/DC",
+            ..values(&chapter)
+        };
+        assert_eq!(
+            render("{series}/{chapter}.{ext}", &values).unwrap(),
+            PathBuf::from(".._AC_DC/7.cbz")
+        );
+        assert_eq!(
+            render("../{chapter}/./{ext}", &values).unwrap(),
+            PathBuf::from("7/cbz")
+        );
+        let values = LinkTemplateValues {
+            series: "..",
+            ..values
+        };
+        assert_eq!(
+            render("{series}/{chapter}", &values).unwrap(),
+            PathBuf::from("7")
+        );
+    }
+
+    #[test]
+    fn test_missing_volume() {
+        let chapter = ChapterNumber::from(7);
+        let values = LinkTemplateValues {
+            volume: None,
+            ..values(&chapter)
+        };
+        assert_eq!(
+            render("{series}/{volume}/{chapter}", &values).unwrap(),
+            PathBuf::from("Series/7")
+        );
+        assert!(render("{volume}", &values).is_err());
+        assert!(render("{volume}/", &values).is_err());
+    }
+}
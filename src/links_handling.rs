@@ -4,9 +4,11 @@ use futures::TryStreamExt;
 use hashbrown::{HashMap, HashSet};
 use sqlx::prelude::*;
 use tokio::fs;
-use tokio_stream::wrappers::ReadDirStream;
 
+use crate::link_mode::LinkMode;
+use crate::link_template::{LinkTemplate, LinkTemplateValues};
 use crate::models;
+use crate::util::db::FromRowWithExtra;
 
 pub async fn prepare_media_type_to_adjacent_types_mapping(
     con: &mut crate::SqlitePoolConnection,
@@ -45,32 +47,254 @@ pub async fn prepare_series_to_adjacent_types_mapping<'a>(
     Ok(mapping)
 }
 
+/// Which unread episodes get links in a links directory.
+#[derive(Debug, Default, Clone)]
+pub struct LinksFilter {
+    /// Empty means all the media types that maintain symlinks.
+    pub media_types: HashSet<i64>,
+    /// Empty means all serieses.
+    pub serieses: HashSet<i64>,
+    /// Only link the first unread episodes of each series.
+    pub max_unread_per_series: Option<i64>,
+}
+
+impl LinksFilter {
+    pub async fn load(
+        con: &mut sqlx::SqliteConnection,
+        links_directory: &models::LinksDirectory,
+    ) -> anyhow::Result<Self> {
+        let media_types = sqlx::query_as::<_, (i64,)>(
+            "SELECT media_type FROM links_directory_media_types WHERE links_directory = ?",
+        )
+        .bind(links_directory.id)
+        .fetch(con.acquire().await?)
+        .map_ok(|(media_type,)| media_type)
+        .try_collect()
+        .await?;
+        let serieses = sqlx::query_as::<_, (i64,)>(
+            "SELECT series FROM links_directory_serieses WHERE links_directory = ?",
+        )
+        .bind(links_directory.id)
+        .fetch(con.acquire().await?)
+        .map_ok(|(series,)| series)
+        .try_collect()
+        .await?;
+        Ok(Self {
+            media_types,
+            serieses,
+            max_unread_per_series: links_directory.max_unread_per_series,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct EpisodeLinkingSettings {
+    media_type: i64,
+    maintain_symlinks: bool,
+    link_template: String,
+    series_name: String,
+    media_type_name: String,
+    base_dir: String,
+}
+
+/// Refresh all the links directories configured in the database.
+pub async fn refresh_configured_links_directories(
+    con: &mut crate::SqlitePoolConnection,
+) -> anyhow::Result<Vec<(models::LinksDirectory, LinksRefreshReport)>> {
+    let links_directories: Vec<models::LinksDirectory> =
+        sqlx::query_as("SELECT * FROM links_directories")
+            .fetch(con.acquire().await?)
+            .try_collect()
+            .await?;
+    let mut reports = Vec::new();
+    for links_directory in links_directories {
+        let filter = LinksFilter::load(con, &links_directory).await?;
+        let links_dir_path = Path::new(&links_directory.dir);
+        fs::create_dir_all(links_dir_path).await?;
+        let report =
+            refresh_links_directory(con, links_dir_path, &filter, links_directory.link_mode)
+                .await?;
+        reports.push((links_directory, report));
+    }
+    Ok(reports)
+}
+
+/// Lists the entries of a links directory that were created by chapter-tracker, one path
+/// (relative to the links directory) per line. Only these entries are ever removed.
+pub const MANIFEST_FILE_NAME: &str = ".chapter-tracker-links";
+const MANIFEST_TEMP_FILE_NAME: &str = ".chapter-tracker-links.tmp";
+
+/// What a refresh of a links directory did, and what it could not do.
+#[derive(Debug, Default)]
+pub struct LinksRefreshReport {
+    pub num_created: usize,
+    pub num_removed: usize,
+    pub num_kept: usize,
+    /// Files in the links directory that chapter-tracker did not create. They are left in place.
+    pub unknown_files: Vec<PathBuf>,
+    /// Links that could not be created or removed, with the reason.
+    pub failures: Vec<(PathBuf, String)>,
+}
+
+impl LinksRefreshReport {
+    pub fn has_problems(&self) -> bool {
+        !self.unknown_files.is_empty() || !self.failures.is_empty()
+    }
+
+    /// Log the problems as warnings.
+    pub fn log_problems(&self, links_dir_path: &Path) {
+        for unknown_file in self.unknown_files.iter() {
+            log::warn!(
+                "{:?} was not created by chapter-tracker - leaving it in place",
+                unknown_file
+            );
+        }
+        for (path, reason) in self.failures.iter() {
+            log::warn!(
+                "Problem with {:?} in {:?}: {}",
+                path,
+                links_dir_path,
+                reason
+            );
+        }
+    }
+}
+
+impl core::fmt::Display for LinksRefreshReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} created, {} removed, {} kept",
+            self.num_created, self.num_removed, self.num_kept
+        )?;
+        for unknown_file in self.unknown_files.iter() {
+            write!(
+                f,
+                "\n  Not created by chapter-tracker: {}",
+                unknown_file.display()
+            )?;
+        }
+        for (path, reason) in self.failures.iter() {
+            write!(f, "\n  Failed: {}: {}", path.display(), reason)?;
+        }
+        Ok(())
+    }
+}
+
+async fn read_manifest(links_dir_path: &Path) -> anyhow::Result<Option<HashSet<PathBuf>>> {
+    match fs::read_to_string(links_dir_path.join(MANIFEST_FILE_NAME)).await {
+        Ok(content) => Ok(Some(content.lines().map(PathBuf::from).collect())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn write_manifest(links_dir_path: &Path, entries: &HashSet<PathBuf>) -> anyhow::Result<()> {
+    let mut entries: Vec<_> = entries.iter().filter_map(|entry| entry.to_str()).collect();
+    entries.sort();
+    let mut content = entries.join("\n");
+    content.push('\n');
+    // Write and rename, so that a crash never leaves a partial manifest.
+    let manifest_path = links_dir_path.join(MANIFEST_FILE_NAME);
+    let temp_path = links_dir_path.join(MANIFEST_TEMP_FILE_NAME);
+    fs::write(&temp_path, content).await?;
+    fs::rename(&temp_path, &manifest_path).await?;
+    Ok(())
+}
+
 pub async fn refresh_links_directory(
     con: &mut crate::SqlitePoolConnection,
     links_dir_path: &Path,
-) -> anyhow::Result<()> {
+    filter: &LinksFilter,
+    link_mode: LinkMode,
+) -> anyhow::Result<LinksRefreshReport> {
+    let mut report = LinksRefreshReport::default();
     let media_type_to_adjacent_types = prepare_media_type_to_adjacent_types_mapping(con).await?;
     let series_to_adjacent_types =
         prepare_series_to_adjacent_types_mapping(con, &media_type_to_adjacent_types).await?;
 
     let query = sqlx::query_as(
         r#"
-        SELECT episodes.* FROM episodes
+        SELECT episodes.*
+            , serieses.media_type
+            , media_types.maintain_symlinks
+            , media_types.link_template
+            , serieses.name AS series_name
+            , media_types.name AS media_type_name
+            , media_types.base_dir
+        FROM episodes
         INNER JOIN serieses on episodes.series = serieses.id
         INNER JOIN media_types ON serieses.media_type = media_types.id
         WHERE episodes.date_of_read IS NULL
-        AND media_types.maintain_symlinks
+        ORDER BY episodes.series, episodes.volume, episodes.number, episodes.number_suffix
         "#,
     );
-    let unread_episodes: Vec<models::Episode> =
-        query.fetch(con.acquire().await?).try_collect().await?;
+    let unread_episodes: Vec<FromRowWithExtra<models::Episode, EpisodeLinkingSettings>> = query
+        .fetch(con.acquire().await?)
+        .map_ok(
+            |mut episode: FromRowWithExtra<models::Episode, EpisodeLinkingSettings>| {
+                episode.data.file =
+                    crate::library_root::resolve(&episode.extra.base_dir, &episode.data.file);
+                episode
+            },
+        )
+        .try_collect()
+        .await?;
+    let mut num_linked_per_series = HashMap::<i64, i64>::new();
+    let unread_episodes: Vec<FromRowWithExtra<models::Episode, EpisodeLinkingSettings>> =
+        unread_episodes
+            .into_iter()
+            .filter(|FromRowWithExtra { extra, .. }| {
+                if filter.media_types.is_empty() {
+                    extra.maintain_symlinks
+                } else {
+                    filter.media_types.contains(&extra.media_type)
+                }
+            })
+            .filter(|FromRowWithExtra { data, .. }| {
+                filter.serieses.is_empty() || filter.serieses.contains(&data.series)
+            })
+            .filter(|FromRowWithExtra { data, .. }| {
+                let Some(max_unread) = filter.max_unread_per_series else {
+                    return true;
+                };
+                let num_linked = num_linked_per_series.entry(data.series).or_default();
+                *num_linked += 1;
+                *num_linked <= max_unread
+            })
+            .collect();
 
-    // TODO: Generate names from scratch and get rid of this regex usage...
-    let chapter_pattern = regex::Regex::new(r#"c(\d+)$"#)?;
+    // Media types with an empty template, or with a template that fails to parse, get the names of
+    // their links from the names of the episodes.
+    let mut link_templates = HashMap::<i64, Option<LinkTemplate>>::new();
+    for FromRowWithExtra { extra, .. } in unread_episodes.iter() {
+        link_templates.entry(extra.media_type).or_insert_with(|| {
+            if extra.link_template.is_empty() {
+                return None;
+            }
+            match extra.link_template.parse() {
+                Ok(link_template) => Some(link_template),
+                Err(err) => {
+                    log::error!("Bad link template for {:?}: {}", extra.media_type_name, err);
+                    None
+                }
+            }
+        });
+    }
+
+    let chapter_pattern =
+        regex::Regex::new(r#"c(\d+)((?:\.\d+)?[a-z]?(?:-\d+(?:\.\d+)?[a-z]?)?)$"#)?;
 
     let mut pad_series_chapters_to = HashMap::<i64, usize>::new();
 
-    for episode in unread_episodes.iter() {
+    for FromRowWithExtra {
+        data: episode,
+        extra,
+    } in unread_episodes.iter()
+    {
+        if link_templates[&extra.media_type].is_some() {
+            continue;
+        }
         if let Some(chapter) = chapter_pattern
             .captures(&episode.name)
             .and_then(|m| m.get(1))
@@ -92,7 +316,7 @@ pub async fn refresh_links_directory(
     {
         let directories_with_unread_episodes: HashSet<_> = unread_episodes
             .iter()
-            .filter_map(|episode| Path::new(&episode.file).parent())
+            .filter_map(|episode| Path::new(&episode.data.file).parent())
             .collect();
         let all_potential_adjacent_suffixes: HashSet<_> = series_to_adjacent_types
             .values()
@@ -103,7 +327,9 @@ pub async fn refresh_links_directory(
             let mut reader = fs::read_dir(directory).await?;
             while let Some(dirent) = reader.next_entry().await? {
                 let file_path = dirent.path();
-                let Some(extension) = file_path.extension().and_then(|ext| ext.to_str()) else { continue };
+                let Some(extension) = file_path.extension().and_then(|ext| ext.to_str()) else {
+                    continue;
+                };
                 if let Some(extension) = all_potential_adjacent_suffixes.get(extension) {
                     let file_without_extension = file_path.with_extension("");
                     all_adjacent_files
@@ -116,29 +342,48 @@ pub async fn refresh_links_directory(
     }
 
     let mut desired_links = HashMap::new();
-    for episode in unread_episodes {
-        use std::fmt::Write;
-        let mut link_name = if let Some(pad_to) = pad_series_chapters_to.get(&episode.series) {
-            chapter_pattern
-                .replace(&episode.name, |captures: &regex::Captures| {
-                    let mut result = String::new();
-                    let chapter = captures.get(1).unwrap().as_str();
-                    for _ in chapter.len()..*pad_to {
-                        result.write_char('0').unwrap();
-                    }
-                    result.write_str(chapter).unwrap();
-                    result
-                })
-                .into_owned()
+    for FromRowWithExtra {
+        data: episode,
+        extra,
+    } in unread_episodes
+    {
+        let file_path = PathBuf::from(&episode.file);
+        let link_name = if let Some(link_template) = &link_templates[&extra.media_type] {
+            let link_name = link_template.render(&LinkTemplateValues {
+                series: &extra.series_name,
+                media_type: &extra.media_type_name,
+                volume: episode.volume,
+                chapter: &episode.number,
+                chapter_end: episode.number_end.as_ref(),
+                id: episode.id,
+                filename: file_path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .unwrap_or_default(),
+                ext: file_path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .unwrap_or_default(),
+            });
+            match link_name {
+                Ok(link_name) => link_name,
+                Err(err) => {
+                    log::warn!("Cannot link {:?}: {}", file_path, err);
+                    continue;
+                }
+            }
         } else {
-            episode.name
+            legacy_link_name(&episode, &chapter_pattern, &pad_series_chapters_to).into()
         };
-        write!(&mut link_name, " {}", episode.id)?;
-        let file_path = PathBuf::from(&episode.file);
-        if let Some(extension) = file_path.extension() {
-            write!(&mut link_name, ".{}", extension.to_str().unwrap()).unwrap();
-        }
         let link_path = links_dir_path.join(&link_name);
+        if desired_links.contains_key(&link_path) {
+            log::warn!(
+                "Cannot link {:?} to {:?} - another episode already has that link",
+                link_path,
+                file_path
+            );
+            continue;
+        }
         if let (Some(adjacents), Some(series_adjacents)) = (
             all_adjacent_files.get(&file_path.with_extension("")),
             series_to_adjacent_types.get(&episode.series),
@@ -155,20 +400,178 @@ pub async fn refresh_links_directory(
         desired_links.insert(link_path, file_path);
     }
 
-    let read_dir_result = ReadDirStream::new(fs::read_dir(links_dir_path).await?);
-    let existing_files: Vec<_> = read_dir_result.try_collect().await?;
+    let manifest = read_manifest(links_dir_path).await?;
+    // Directories without a manifest were created before it was introduced, when the only
+    // entries created were symlinks to episode files and their adjacent files.
+    let legacy_known_files: HashSet<PathBuf> = if manifest.is_none() {
+        let library_roots = crate::library_root::LibraryRoots::load(con).await?;
+        sqlx::query_as::<_, (i64, String)>("SELECT series, file FROM episodes")
+            .fetch(con.acquire().await?)
+            .map_ok(|(series, file)| {
+                Path::new(&library_roots.resolve(series, &file)).with_extension("")
+            })
+            .try_collect()
+            .await?
+    } else {
+        HashSet::new()
+    };
+    let mut new_manifest = HashSet::new();
+
+    // Links that are already up to date are left alone, so that changing the template of one
+    // media type does not touch the links of the others.
+    // Only directories that held removed entries are removed once empty, so that directories
+    // created by the user are left alone.
+    let mut dirs_of_removed_entries = HashSet::new();
+    let mut dirs_to_scan = vec![links_dir_path.to_owned()];
+    while let Some(dir) = dirs_to_scan.pop() {
+        let mut reader = fs::read_dir(&dir).await?;
+        while let Some(dirent) = reader.next_entry().await? {
+            let path = dirent.path();
+            if dirent.file_type().await?.is_dir() {
+                dirs_to_scan.push(path);
+                continue;
+            }
+            let Ok(relative_path) = path.strip_prefix(links_dir_path) else {
+                continue;
+            };
+            if relative_path == Path::new(MANIFEST_FILE_NAME)
+                || relative_path == Path::new(MANIFEST_TEMP_FILE_NAME)
+            {
+                continue;
+            }
+            let is_up_to_date = match desired_links.get(&path) {
+                Some(target) => link_mode.is_up_to_date(target, &path).await?,
+                None => false,
+            };
+            if is_up_to_date {
+                desired_links.remove(&path);
+                new_manifest.insert(relative_path.to_owned());
+                report.num_kept += 1;
+                continue;
+            }
+            let created_by_us = if let Some(manifest) = &manifest {
+                manifest.contains(relative_path)
+            } else if dirent.file_type().await?.is_symlink() {
+                fs::read_link(&path)
+                    .await
+                    .is_ok_and(|target| legacy_known_files.contains(&target.with_extension("")))
+            } else {
+                false
+            };
+            if !created_by_us {
+                if desired_links.remove(&path).is_some() {
+                    report.failures.push((
+                        path.clone(),
+                        "occupied by a file that was not created by chapter-tracker".to_owned(),
+                    ));
+                }
+                report.unknown_files.push(path);
+                continue;
+            }
+            log::debug!("Removing {:?}", path);
+            match fs::remove_file(&path).await {
+                Ok(()) => {
+                    report.num_removed += 1;
+                    dirs_of_removed_entries.extend(
+                        path.ancestors()
+                            .skip(1)
+                            .take_while(|dir| *dir != links_dir_path)
+                            .map(Path::to_owned),
+                    );
+                }
+                Err(err) => {
+                    // Keep it in the manifest, so that the next refresh tries again.
+                    new_manifest.insert(relative_path.to_owned());
+                    desired_links.remove(&path);
+                    report
+                        .failures
+                        .push((path, format!("cannot remove: {}", err)));
+                }
+            }
+        }
+    }
 
-    for file in existing_files {
-        let file_name = file.path();
-        if desired_links.remove(&file_name).is_none() {
-            log::debug!("Removing {:?}", file);
-            fs::remove_file(file.path()).await?;
+    // Deepest first, so that directories that only had empty directories in them get removed too.
+    let mut dirs_of_removed_entries = dirs_of_removed_entries.into_iter().collect::<Vec<_>>();
+    dirs_of_removed_entries.sort_by_key(|dir| core::cmp::Reverse(dir.components().count()));
+    for dir in dirs_of_removed_entries {
+        let is_empty = async {
+            Ok::<_, std::io::Error>(fs::read_dir(&dir).await?.next_entry().await?.is_none())
+        }
+        .await;
+        match is_empty {
+            Ok(true) => {
+                log::debug!("Removing empty directory {:?}", dir);
+                if let Err(err) = fs::remove_dir(&dir).await {
+                    log::debug!("Cannot remove empty directory {:?}: {}", dir, err);
+                }
+            }
+            Ok(false) => {}
+            Err(err) => report
+                .failures
+                .push((dir, format!("cannot check if empty: {}", err))),
         }
     }
 
     for (link_path, link_target) in desired_links {
-        log::debug!("Linking {:?} to {:?}", link_path, link_target);
-        fs::symlink(link_target, link_path).await.unwrap();
+        log::debug!(
+            "Linking {:?} to {:?} with {}",
+            link_path,
+            link_target,
+            link_mode
+        );
+        let mut result = async {
+            if let Some(parent) = link_path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            link_mode.materialize(&link_target, &link_path).await
+        }
+        .await;
+        if result.is_err() && link_mode.is_up_to_date(&link_target, &link_path).await? {
+            // Something else (e.g. another refresh) created the same link in the meantime.
+            result = Ok(());
+        }
+        match result {
+            Ok(()) => {
+                report.num_created += 1;
+                if let Ok(relative_path) = link_path.strip_prefix(links_dir_path) {
+                    new_manifest.insert(relative_path.to_owned());
+                }
+            }
+            Err(err) => report.failures.push((link_path, err.to_string())),
+        }
     }
-    Ok(())
+    write_manifest(links_dir_path, &new_manifest).await?;
+    Ok(report)
+}
+
+/// Name the link after the episode, with its chapter number padded to the longest chapter number
+/// in the series.
+fn legacy_link_name(
+    episode: &models::Episode,
+    chapter_pattern: &regex::Regex,
+    pad_series_chapters_to: &HashMap<i64, usize>,
+) -> String {
+    use std::fmt::Write;
+    let mut link_name = if let Some(pad_to) = pad_series_chapters_to.get(&episode.series) {
+        chapter_pattern
+            .replace(&episode.name, |captures: &regex::Captures| {
+                let mut result = String::new();
+                let chapter = captures.get(1).unwrap().as_str();
+                for _ in chapter.len()..*pad_to {
+                    result.write_char('0').unwrap();
+                }
+                result.write_str(chapter).unwrap();
+                result.write_str(&captures[2]).unwrap();
+                result
+            })
+            .into_owned()
+    } else {
+        episode.name.clone()
+    };
+    write!(&mut link_name, " {}", episode.id).unwrap();
+    if let Some(extension) = Path::new(&episode.file).extension() {
+        write!(&mut link_name, ".{}", extension.to_str().unwrap()).unwrap();
+    }
+    link_name
 }
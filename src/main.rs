@@ -1,5 +1,10 @@
 fn main() -> anyhow::Result<()> {
     flexi_logger::Logger::try_with_env_or_str("warn")?.start()?;
+    use structopt::StructOpt;
+    let cli_args = chapter_tracker::CliArgs::from_args();
+    if let Some(command) = &cli_args.command {
+        return chapter_tracker::cli::run_cli_command(&cli_args, command);
+    }
     let exit_status = chapter_tracker::start_gui()?;
     if exit_status != 0 {
         std::process::exit(exit_status);
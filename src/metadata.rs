@@ -0,0 +1,494 @@
+//! Metadata of episode files - titles, page counts, durations and release dates - read from the
+//! files themselves:
+//!
+//! * CBZ - `ComicInfo.xml`. Without a `PageCount`, the number of images is the page count.
+//! * CBR - the same, through the `unrar` program.
+//! * EPUB - the OPF package document.
+//! * PDF - the info dictionary, through the `pdfinfo` program.
+//! * Videos - the container's title, date and duration, through the `ffprobe` program.
+//!
+//! Extraction is opt-in per media type, and runs on newly registered episodes. Files that have no
+//! metadata, or whose metadata cannot be read, are still marked as extracted so that they are not
+//! retried on every scan.
+
+use std::io::Read;
+use std::path::Path;
+
+use futures::TryStreamExt;
+use hashbrown::HashMap;
+use sqlx::prelude::*;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct EpisodeMetadata {
+    pub title: Option<String>,
+    pub page_count: Option<i64>,
+    /// In seconds.
+    pub duration: Option<f64>,
+    /// `YYYY`, `YYYY-MM` or `YYYY-MM-DD`.
+    pub release_date: Option<String>,
+}
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp", "avif", "bmp", "jxl"];
+const VIDEO_EXTENSIONS: &[&str] = &[
+    "mkv", "mp4", "m4v", "avi", "webm", "mov", "wmv", "flv", "ogv", "ts",
+];
+
+pub async fn extract_metadata(path: &str) -> anyhow::Result<EpisodeMetadata> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "cbz" => {
+            let path = path.to_owned();
+            tokio::task::spawn_blocking(move || read_cbz(&path)).await?
+        }
+        "cbr" => read_cbr(path).await,
+        "epub" => {
+            let path = path.to_owned();
+            tokio::task::spawn_blocking(move || read_epub(&path)).await?
+        }
+        "pdf" => read_pdf(path).await,
+        extension if VIDEO_EXTENSIONS.contains(&extension) => read_video(path).await,
+        _ => Ok(Default::default()),
+    }
+}
+
+/// A duration in seconds as `H:MM:SS`, or `M:SS` when it is shorter than an hour.
+pub fn format_duration(duration: f64) -> String {
+    let seconds = duration.round() as i64;
+    if seconds < 3600 {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    } else {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct PendingEpisode {
+    id: i64,
+    file: String,
+    base_dir: String,
+}
+
+/// Extract the metadata of the episodes whose media types extract metadata. Unless `all` is set,
+/// only episodes that were not extracted yet are extracted. Returns the number of episodes
+/// extracted.
+pub async fn extract_pending_metadata(
+    con: &mut crate::SqlitePoolConnection,
+    series: Option<i64>,
+    all: bool,
+) -> anyhow::Result<usize> {
+    let pending = load_pending_episodes(con, series, None, all).await?;
+    store_metadata(con, &pending).await?;
+    Ok(pending.len())
+}
+
+/// Extract the metadata of the given episodes, if their media types extract metadata and they
+/// were not extracted yet. Returns the number of episodes extracted.
+pub async fn extract_episodes_metadata(
+    con: &mut crate::SqlitePoolConnection,
+    episode_ids: &[i64],
+) -> anyhow::Result<usize> {
+    let mut pending = Vec::new();
+    for episode_id in episode_ids {
+        pending.extend(load_pending_episodes(con, None, Some(*episode_id), false).await?);
+    }
+    store_metadata(con, &pending).await?;
+    Ok(pending.len())
+}
+
+async fn load_pending_episodes(
+    con: &mut crate::SqlitePoolConnection,
+    series: Option<i64>,
+    episode: Option<i64>,
+    all: bool,
+) -> anyhow::Result<Vec<PendingEpisode>> {
+    Ok(sqlx::query_as(
+        r#"
+        SELECT episodes.id, episodes.file, media_types.base_dir
+        FROM episodes
+        INNER JOIN serieses ON serieses.id = episodes.series
+        INNER JOIN media_types ON media_types.id = serieses.media_type
+        WHERE media_types.extract_metadata
+        AND (?1 OR episodes.date_of_metadata_extraction IS NULL)
+        AND (?2 IS NULL OR episodes.series = ?2)
+        AND (?3 IS NULL OR episodes.id = ?3)
+        "#,
+    )
+    .bind(all)
+    .bind(series)
+    .bind(episode)
+    .fetch(con.acquire().await?)
+    .try_collect()
+    .await?)
+}
+
+async fn store_metadata(
+    con: &mut crate::SqlitePoolConnection,
+    pending: &[PendingEpisode],
+) -> anyhow::Result<()> {
+    for episode in pending.iter() {
+        let file = crate::library_root::resolve(&episode.base_dir, &episode.file);
+        let metadata = match extract_metadata(&file).await {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                log::warn!("Cannot extract the metadata of {:?}: {}", file, err);
+                Default::default()
+            }
+        };
+        log::debug!("Metadata of {:?}: {:?}", file, metadata);
+        sqlx::query(
+            r#"
+            UPDATE episodes
+            SET title = ?
+              , page_count = ?
+              , duration = ?
+              , release_date = ?
+              , date_of_metadata_extraction = datetime()
+            WHERE id = ?
+            "#,
+        )
+        .bind(metadata.title)
+        .bind(metadata.page_count)
+        .bind(metadata.duration)
+        .bind(metadata.release_date)
+        .bind(episode.id)
+        .execute(con.acquire().await?)
+        .await?;
+    }
+    Ok(())
+}
+
+fn is_image(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+fn is_comic_info(name: &str) -> bool {
+    name.eq_ignore_ascii_case("ComicInfo.xml")
+}
+
+fn read_cbz(path: &str) -> anyhow::Result<EpisodeMetadata> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let num_images = archive.file_names().filter(|name| is_image(name)).count() as i64;
+    let comic_info_name = archive
+        .file_names()
+        .find(|name| is_comic_info(name))
+        .map(|name| name.to_owned());
+    let mut metadata = if let Some(comic_info_name) = comic_info_name {
+        let mut comic_info = String::new();
+        archive
+            .by_name(&comic_info_name)?
+            .read_to_string(&mut comic_info)?;
+        parse_comic_info(&comic_info)?
+    } else {
+        EpisodeMetadata::default()
+    };
+    if metadata.page_count.is_none() && 0 < num_images {
+        metadata.page_count = Some(num_images);
+    }
+    Ok(metadata)
+}
+
+async fn read_cbr(path: &str) -> anyhow::Result<EpisodeMetadata> {
+    let listing = run_tool("unrar", &["lb", "--", path]).await?;
+    let num_images = listing.lines().filter(|name| is_image(name)).count() as i64;
+    let mut metadata = if let Some(comic_info_name) =
+        listing.lines().find(|name| is_comic_info(name))
+    {
+        parse_comic_info(&run_tool("unrar", &["p", "-inul", "--", path, comic_info_name]).await?)?
+    } else {
+        EpisodeMetadata::default()
+    };
+    if metadata.page_count.is_none() && 0 < num_images {
+        metadata.page_count = Some(num_images);
+    }
+    Ok(metadata)
+}
+
+fn parse_comic_info(xml: &str) -> anyhow::Result<EpisodeMetadata> {
+    let fields = xml_element_texts(xml, &["Title", "PageCount", "Year", "Month", "Day"])?;
+    // ComicInfo uses -1 for unknown numbers.
+    let number = |name: &str| {
+        fields
+            .get(name)
+            .and_then(|text| text.parse::<i64>().ok())
+            .filter(|number| 0 < *number)
+    };
+    Ok(EpisodeMetadata {
+        title: fields.get("Title").cloned(),
+        page_count: number("PageCount"),
+        duration: None,
+        release_date: match (number("Year"), number("Month"), number("Day")) {
+            (Some(year), Some(month), Some(day)) => {
+                Some(format!("{:04}-{:02}-{:02}", year, month, day))
+            }
+            (Some(year), Some(month), None) => Some(format!("{:04}-{:02}", year, month)),
+            (Some(year), _, _) => Some(format!("{:04}", year)),
+            (None, _, _) => None,
+        },
+    })
+}
+
+fn read_epub(path: &str) -> anyhow::Result<EpisodeMetadata> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let mut container = String::new();
+    archive
+        .by_name("META-INF/container.xml")?
+        .read_to_string(&mut container)?;
+    let package_path = xml_element_attribute(&container, "rootfile", "full-path")?
+        .ok_or_else(|| anyhow::anyhow!("No rootfile in META-INF/container.xml"))?;
+    let mut package = String::new();
+    archive
+        .by_name(&package_path)?
+        .read_to_string(&mut package)?;
+    let fields = xml_element_texts(&package, &["title", "date"])?;
+    Ok(EpisodeMetadata {
+        title: fields.get("title").cloned(),
+        page_count: None,
+        duration: None,
+        release_date: fields.get("date").and_then(|date| normalize_date(date)),
+    })
+}
+
+async fn read_pdf(path: &str) -> anyhow::Result<EpisodeMetadata> {
+    let output = run_tool("pdfinfo", &["-isodates", "-enc", "UTF-8", "--", path]).await?;
+    let fields: HashMap<&str, &str> = output
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .filter(|(_, value)| !value.is_empty())
+        .collect();
+    Ok(EpisodeMetadata {
+        title: fields.get("Title").map(|title| title.to_string()),
+        page_count: fields.get("Pages").and_then(|pages| pages.parse().ok()),
+        duration: None,
+        release_date: fields
+            .get("CreationDate")
+            .and_then(|date| normalize_date(date)),
+    })
+}
+
+async fn read_video(path: &str) -> anyhow::Result<EpisodeMetadata> {
+    let output = run_tool(
+        "ffprobe",
+        &[
+            "-v",
+            "error",
+            "-print_format",
+            "json",
+            "-show_format",
+            "--",
+            path,
+        ],
+    )
+    .await?;
+    let output: serde_json::Value = serde_json::from_str(&output)?;
+    let format = &output["format"];
+    // Tag names differ in case between containers.
+    let tag = |name: &str| {
+        format["tags"].as_object().and_then(|tags| {
+            tags.iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .and_then(|(_, value)| value.as_str())
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+        })
+    };
+    Ok(EpisodeMetadata {
+        title: tag("title").map(|title| title.to_owned()),
+        page_count: None,
+        duration: format["duration"]
+            .as_str()
+            .and_then(|duration| duration.parse().ok()),
+        release_date: tag("date")
+            .or_else(|| tag("creation_time"))
+            .and_then(normalize_date),
+    })
+}
+
+async fn run_tool(program: &str, args: &[&str]) -> anyhow::Result<String> {
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .map_err(|err| anyhow::anyhow!("Cannot run {}: {}", program, err))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} exited with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The `YYYY`, `YYYY-MM` or `YYYY-MM-DD` prefix of a date, dropping the time.
+fn normalize_date(date: &str) -> Option<String> {
+    let date = date.trim();
+    let mut end = 0;
+    for (expected_length, part) in [4, 2, 2].iter().zip(date.split(['-', 'T', ' '])) {
+        if part.len() != *expected_length || !part.chars().all(|c| c.is_ascii_digit()) {
+            break;
+        }
+        if end != 0 {
+            // The separator
+            end += 1;
+        }
+        end += part.len();
+    }
+    if end == 0 {
+        None
+    } else {
+        Some(date[..end].to_owned())
+    }
+}
+
+/// The text of the first element with each of the `names`, ignoring namespace prefixes.
+fn xml_element_texts(xml: &str, names: &[&str]) -> anyhow::Result<HashMap<String, String>> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut result = HashMap::new();
+    let mut current = None;
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => {
+                let local_name = element.local_name();
+                current = names
+                    .iter()
+                    .find(|name| name.as_bytes() == local_name.as_ref())
+                    .filter(|name| !result.contains_key(**name));
+            }
+            Event::Text(text) => {
+                if let Some(name) = current {
+                    let text = text.unescape()?.trim().to_owned();
+                    if !text.is_empty() {
+                        result.insert(name.to_string(), text);
+                    }
+                }
+            }
+            Event::End(_) => current = None,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(result)
+}
+
+/// The value of an attribute of the first element named `element_name`.
+fn xml_element_attribute(
+    xml: &str,
+    element_name: &str,
+    attribute_name: &str,
+) -> anyhow::Result<Option<String>> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::Reader::from_str(xml);
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) | Event::Empty(element)
+                if element.local_name().as_ref() == element_name.as_bytes() =>
+            {
+                return Ok(match element.try_get_attribute(attribute_name)? {
+                    Some(attribute) => Some(attribute.unescape_value()?.into_owned()),
+                    None => None,
+                });
+            }
+            Event::Eof => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_date() {
+        assert_eq!(normalize_date("2021-03-15"), Some("2021-03-15".to_owned()));
+        assert_eq!(
+            normalize_date(" 2021-03-15T10:20:30Z "),
+            Some("2021-03-15".to_owned())
+        );
+        assert_eq!(
+            normalize_date("2021-03-15 10:20:30"),
+            Some("2021-03-15".to_owned())
+        );
+        assert_eq!(normalize_date("2021-03"), Some("2021-03".to_owned()));
+        assert_eq!(normalize_date("2021"), Some("2021".to_owned()));
+        assert_eq!(normalize_date("2021-3-5"), Some("2021".to_owned()));
+        assert_eq!(normalize_date("March 2021"), None);
+        assert_eq!(normalize_date(""), None);
+    }
+
+    #[test]
+    fn test_parse_comic_info() {
+        let metadata = parse_comic_info(
+            r#"<?xml version="1.0"?>
+            <ComicInfo xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+                <Series>Ignored</Series>
+                <Title> Cats &amp; Dogs </Title>
+                <Year>2021</Year>
+                <Month>3</Month>
+                <Day>5</Day>
+                <PageCount>24</PageCount>
+            </ComicInfo>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            metadata,
+            EpisodeMetadata {
+                title: Some("Cats & Dogs".to_owned()),
+                page_count: Some(24),
+                duration: None,
+                release_date: Some("2021-03-05".to_owned()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_comic_info_unknown_numbers() {
+        let metadata = parse_comic_info(
+            r#"<ComicInfo>
+                <Title></Title>
+                <Year>2021</Year>
+                <Month>-1</Month>
+                <Day>5</Day>
+                <PageCount>-1</PageCount>
+            </ComicInfo>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            metadata,
+            EpisodeMetadata {
+                title: None,
+                page_count: None,
+                duration: None,
+                release_date: Some("2021".to_owned()),
+            }
+        );
+        assert_eq!(
+            parse_comic_info("<ComicInfo/>").unwrap(),
+            EpisodeMetadata::default()
+        );
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(59.6), "1:00");
+        assert_eq!(format_duration(754.0), "12:34");
+        assert_eq!(format_duration(3725.0), "1:02:05");
+    }
+}
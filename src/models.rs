@@ -7,6 +7,10 @@ pub struct MediaType {
     pub adjacent_file_types: String,
     pub program: String,
     pub maintain_symlinks: bool,
+    pub mark_read_on_exit: bool,
+    pub link_template: String,
+    pub extract_metadata: bool,
+    pub download_command_template: String,
 }
 
 #[derive(PartialEq, Debug, Clone, sqlx::FromRow)]
@@ -17,6 +21,7 @@ pub struct Series {
     // pub numbers_repeat_each_volume: Option<bool>,
     pub download_command_dir: Option<String>,
     pub download_command: Option<String>,
+    pub source: Option<String>,
 }
 
 #[derive(PartialEq, Debug, sqlx::FromRow)]
@@ -35,13 +40,51 @@ pub struct Directory {
     pub recursive: bool,
 }
 
+#[derive(PartialEq, Debug, sqlx::FromRow)]
+pub struct EpisodeReadHistory {
+    pub num_reads: i32,
+    pub read_history: Option<String>,
+}
+
 #[derive(PartialEq, Debug, sqlx::FromRow)]
 pub struct Episode {
     pub id: i64,
     pub series: i64,
-    pub number: i64,
+    #[sqlx(flatten)]
+    pub number: crate::chapter_number::ChapterNumber,
+    /// The last chapter, for episodes that cover a range of chapters.
+    pub number_end: Option<crate::chapter_number::ChapterNumber>,
     pub name: String,
     pub file: String,
     pub volume: Option<i64>,
     pub date_of_read: Option<sqlx::types::chrono::NaiveDateTime>,
+    pub file_size: Option<i64>,
+    /// The title from the file's metadata, if it was extracted.
+    pub title: Option<String>,
+    pub page_count: Option<i64>,
+    /// In seconds.
+    pub duration: Option<f64>,
+    pub release_date: Option<String>,
+    /// The SHA-256 of the file, if it was ever hashed.
+    #[sqlx(default)]
+    pub content_hash: Option<Vec<u8>>,
+}
+
+#[derive(PartialEq, Debug, sqlx::FromRow)]
+pub struct LinksDirectory {
+    pub id: i64,
+    pub name: String,
+    pub dir: String,
+    pub max_unread_per_series: Option<i64>,
+    pub link_mode: crate::link_mode::LinkMode,
+}
+
+/// A links directory with the names of the media types and serieses it is limited to, separated by
+/// `;`.
+#[derive(PartialEq, Debug, sqlx::FromRow)]
+pub struct LinksDirectoryWithFilterNames {
+    #[sqlx(flatten)]
+    pub links_directory: LinksDirectory,
+    pub media_type_names: String,
+    pub series_names: String,
 }
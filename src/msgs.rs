@@ -1,10 +1,15 @@
+/// Discover new files and register them. Scans run one at a time, so that two scans won't register
+/// the same files. Returns the new episodes.
 #[derive(actix::Message)]
-#[rtype(result = "anyhow::Result<Vec<crate::files_discovery::FoundFile>>")]
-pub struct DiscoverFiles;
+#[rtype(result = "anyhow::Result<Vec<crate::files_discovery::NewEpisodes>>")]
+pub struct ScanFiles {
+    /// Only scan the directories of this series.
+    pub series: Option<i64>,
+}
 
 #[derive(actix::Message)]
-#[rtype(result = "anyhow::Result<()>")]
-pub struct FindAndRemoveDanglingFiles;
+#[rtype(result = "anyhow::Result<Vec<crate::files_discovery::DanglingFile>>")]
+pub struct FindDanglingFiles;
 
 pub struct RequestConnection;
 
@@ -27,3 +32,25 @@ where
 #[derive(actix::Message)]
 #[rtype(result = "()")]
 pub struct UpdateListRowData<T>(pub Vec<T>);
+
+/// Run the download command of a series and scan its directories. Returns the run and the episodes
+/// the scan registered.
+#[derive(actix::Message)]
+#[rtype(
+    result = "anyhow::Result<(crate::downloads::DownloadRun, Vec<crate::files_discovery::NewEpisodes>)>"
+)]
+pub struct RunDownloadCommand {
+    pub series: i64,
+    pub timeout: std::time::Duration,
+}
+
+/// Run the download commands of all the serieses, or of the serieses of one media type, and scan
+/// for new files once they all finish. Returns the episodes the scan registered.
+#[derive(actix::Message)]
+#[rtype(result = "anyhow::Result<Vec<crate::files_discovery::NewEpisodes>>")]
+pub struct RunAllDownloadCommands {
+    pub media_type: Option<i64>,
+    pub concurrency: usize,
+    pub timeout: std::time::Duration,
+    pub progress: tokio::sync::mpsc::UnboundedSender<crate::downloads::DownloadProgress>,
+}
@@ -0,0 +1,162 @@
+use sqlx::prelude::*;
+
+use crate::chapter_number::ChapterNumber;
+use crate::models;
+use crate::util::db::FromRowWithExtra;
+
+/// Record a read of the episode. This also updates the episode's `date_of_read`.
+pub async fn mark_read(
+    con: &mut sqlx::SqliteConnection,
+    episode_id: i64,
+    source: &str,
+) -> anyhow::Result<()> {
+    let query_result = sqlx::query(
+        r#"
+        INSERT INTO read_events(episode, timestamp, source)
+        SELECT id, datetime(), ? FROM episodes WHERE id = ?
+        "#,
+    )
+    .bind(source)
+    .bind(episode_id)
+    .execute(con.acquire().await?)
+    .await?;
+    if query_result.rows_affected() == 0 {
+        anyhow::bail!("No episode with id={}", episode_id);
+    }
+    Ok(())
+}
+
+/// Mark the episode as unread, keeping its read history.
+pub async fn mark_unread(con: &mut sqlx::SqliteConnection, episode_id: i64) -> anyhow::Result<()> {
+    sqlx::query("UPDATE episodes SET date_of_read = NULL WHERE id == ?")
+        .bind(episode_id)
+        .execute(con.acquire().await?)
+        .await?;
+    Ok(())
+}
+
+/// Episodes of a series to change the read state of together.
+#[derive(Debug, Clone)]
+pub enum BulkSelection {
+    /// All the episodes up to and including the given one, in the same order as they are listed.
+    UpTo {
+        volume: Option<i64>,
+        number: ChapterNumber,
+    },
+    Volume(Option<i64>),
+    All,
+}
+
+impl BulkSelection {
+    fn contains(&self, volume: Option<i64>, number: &ChapterNumber) -> bool {
+        match self {
+            BulkSelection::UpTo {
+                volume: up_to_volume,
+                number: up_to_number,
+            } => (volume, number) <= (*up_to_volume, up_to_number),
+            BulkSelection::Volume(selected_volume) => volume == *selected_volume,
+            BulkSelection::All => true,
+        }
+    }
+}
+
+/// Mark the selected episodes of the series as read or unread, in a single transaction. Returns the
+/// number of episodes whose read state was changed.
+pub async fn bulk_set_read_state(
+    con: &mut crate::SqlitePoolConnection,
+    series_id: i64,
+    selection: BulkSelection,
+    read: bool,
+    source: &str,
+) -> anyhow::Result<usize> {
+    let mut tx = con.begin().await?;
+    let episodes: Vec<models::Episode> = sqlx::query_as("SELECT * FROM episodes WHERE series = ?")
+        .bind(series_id)
+        .fetch_all(tx.acquire().await?)
+        .await?;
+    let mut num_changed = 0;
+    for episode in episodes {
+        if episode.date_of_read.is_some() == read
+            || !selection.contains(episode.volume, &episode.number)
+        {
+            continue;
+        }
+        if read {
+            mark_read(tx.acquire().await?, episode.id, source).await?;
+        } else {
+            mark_unread(tx.acquire().await?, episode.id).await?;
+        }
+        num_changed += 1;
+    }
+    tx.commit().await?;
+    Ok(num_changed)
+}
+
+#[derive(sqlx::FromRow)]
+pub struct LaunchSettings {
+    pub program: String,
+    pub mark_read_on_exit: bool,
+    pub base_dir: String,
+}
+
+/// Find the unread episode with the lowest volume and number in the series, with the settings for
+/// launching it. Episodes whose chapter is covered by a read episode of a chapter range are
+/// skipped. The file of the returned episode is already resolved.
+pub async fn find_next_unread(
+    con: &mut sqlx::SqliteConnection,
+    series_id: i64,
+) -> anyhow::Result<Option<FromRowWithExtra<models::Episode, LaunchSettings>>> {
+    let next_unread: Option<FromRowWithExtra<models::Episode, LaunchSettings>> = sqlx::query_as(
+        r#"
+        SELECT episodes.*
+            , media_types.program
+            , media_types.mark_read_on_exit
+            , media_types.base_dir
+        FROM episodes
+        INNER JOIN serieses ON serieses.id = episodes.series
+        INNER JOIN media_types ON media_types.id = serieses.media_type
+        WHERE episodes.series = ? AND episodes.date_of_read IS NULL
+        AND NOT EXISTS (
+            SELECT 1 FROM episodes AS ranges
+            WHERE ranges.series = episodes.series
+            AND ranges.volume IS episodes.volume
+            AND ranges.number_end IS NOT NULL
+            AND ranges.date_of_read IS NOT NULL
+            AND ranges.number <= episodes.number
+            AND episodes.number <= CAST(ranges.number_end AS REAL)
+        )
+        ORDER BY episodes.volume, episodes.number, episodes.number_suffix
+        LIMIT 1
+        "#,
+    )
+    .bind(series_id)
+    .fetch_optional(con.acquire().await?)
+    .await?;
+    Ok(next_unread.map(|mut next_unread| {
+        next_unread.data.file =
+            crate::library_root::resolve(&next_unread.extra.base_dir, &next_unread.data.file);
+        next_unread
+    }))
+}
+
+/// Find the most recently read series that still has unread episodes.
+pub async fn find_series_to_continue(
+    con: &mut sqlx::SqliteConnection,
+) -> anyhow::Result<Option<i64>> {
+    Ok(sqlx::query_as::<_, (i64,)>(
+        r#"
+        SELECT episodes.series
+        FROM read_events
+        INNER JOIN episodes ON episodes.id = read_events.episode
+        WHERE EXISTS (
+            SELECT 1 FROM episodes AS unread
+            WHERE unread.series = episodes.series AND unread.date_of_read IS NULL
+        )
+        ORDER BY read_events.timestamp DESC
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(con.acquire().await?)
+    .await?
+    .map(|(series_id,)| series_id))
+}
@@ -0,0 +1,190 @@
+//! Jobs the GUI runs periodically. Their intervals and the results of their last runs are kept in
+//! the `scheduled_jobs` table, which has one row per job.
+
+use core::fmt;
+use core::str::FromStr;
+
+use sqlx::prelude::*;
+use sqlx::sqlite::Sqlite;
+use sqlx::types::chrono::NaiveDateTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScheduledJob {
+    /// Discover and register new files.
+    Scan,
+    /// Look for files that were removed. Only reports them - deleting them is left for the user.
+    CleanDangling,
+    /// Refresh all the links directories.
+    RefreshLinks,
+    /// Run the download commands of all the serieses.
+    Download,
+}
+
+impl ScheduledJob {
+    pub const ALL: [ScheduledJob; 4] = [
+        ScheduledJob::Scan,
+        ScheduledJob::CleanDangling,
+        ScheduledJob::RefreshLinks,
+        ScheduledJob::Download,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduledJob::Scan => "scan",
+            ScheduledJob::CleanDangling => "clean_dangling",
+            ScheduledJob::RefreshLinks => "refresh_links",
+            ScheduledJob::Download => "download",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ScheduledJob::Scan => "Scan",
+            ScheduledJob::CleanDangling => "Find dangling files",
+            ScheduledJob::RefreshLinks => "Refresh links directories",
+            ScheduledJob::Download => "Download",
+        }
+    }
+}
+
+impl fmt::Display for ScheduledJob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ScheduledJob {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        ScheduledJob::ALL
+            .iter()
+            .copied()
+            .find(|job| job.as_str() == text)
+            .ok_or_else(|| anyhow::anyhow!("Unknown scheduled job {:?}", text))
+    }
+}
+
+impl sqlx::Type<Sqlite> for ScheduledJob {
+    fn type_info() -> sqlx::sqlite::SqliteTypeInfo {
+        <String as sqlx::Type<Sqlite>>::type_info()
+    }
+}
+
+impl<'q> sqlx::Encode<'q, Sqlite> for ScheduledJob {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        sqlx::Encode::<Sqlite>::encode(self.as_str(), buf)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Sqlite> for ScheduledJob {
+    fn decode(value: sqlx::sqlite::SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(<&str as sqlx::Decode<Sqlite>>::decode(value)?.parse()?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct ScheduledJobState {
+    pub id: i64,
+    pub job: ScheduledJob,
+    /// 0 means the job is not scheduled.
+    pub interval_minutes: i64,
+    pub last_run: Option<NaiveDateTime>,
+    pub last_result: Option<String>,
+    pub last_succeeded: Option<bool>,
+}
+
+impl ScheduledJobState {
+    pub fn describe_last_run(&self) -> String {
+        match (&self.last_run, &self.last_result) {
+            (Some(last_run), Some(last_result)) => format!(
+                "{} at {}: {}",
+                if self.last_succeeded == Some(true) {
+                    "✔"
+                } else {
+                    "✘"
+                },
+                last_run,
+                last_result
+            ),
+            _ => "Never ran".to_owned(),
+        }
+    }
+}
+
+pub async fn load_scheduled_jobs(
+    con: &mut sqlx::SqliteConnection,
+) -> anyhow::Result<Vec<ScheduledJobState>> {
+    Ok(sqlx::query_as("SELECT * FROM scheduled_jobs ORDER BY id")
+        .fetch_all(con.acquire().await?)
+        .await?)
+}
+
+pub async fn load_scheduled_job(
+    con: &mut sqlx::SqliteConnection,
+    job_id: i64,
+) -> anyhow::Result<ScheduledJobState> {
+    Ok(sqlx::query_as("SELECT * FROM scheduled_jobs WHERE id = ?")
+        .bind(job_id)
+        .fetch_one(con.acquire().await?)
+        .await?)
+}
+
+/// The scheduled job that is overdue the longest, if any job is due.
+pub async fn next_due_job(
+    con: &mut sqlx::SqliteConnection,
+) -> anyhow::Result<Option<ScheduledJobState>> {
+    Ok(sqlx::query_as(
+        r#"
+        SELECT * FROM scheduled_jobs
+        WHERE 0 < interval_minutes AND (
+            last_run IS NULL
+            OR datetime(last_run, '+' || interval_minutes || ' minutes') <= datetime()
+        )
+        ORDER BY datetime(last_run, '+' || interval_minutes || ' minutes'), id
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(con.acquire().await?)
+    .await?)
+}
+
+pub async fn set_job_interval(
+    con: &mut sqlx::SqliteConnection,
+    job_id: i64,
+    interval_minutes: i64,
+) -> anyhow::Result<()> {
+    sqlx::query("UPDATE scheduled_jobs SET interval_minutes = ? WHERE id = ?")
+        .bind(interval_minutes)
+        .bind(job_id)
+        .execute(con.acquire().await?)
+        .await?;
+    Ok(())
+}
+
+/// Record that the job finished now.
+pub async fn record_job_run(
+    con: &mut sqlx::SqliteConnection,
+    job_id: i64,
+    succeeded: bool,
+    result: &str,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE scheduled_jobs
+        SET last_run = datetime()
+          , last_result = ?
+          , last_succeeded = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(result)
+    .bind(succeeded)
+    .bind(job_id)
+    .execute(con.acquire().await?)
+    .await?;
+    Ok(())
+}
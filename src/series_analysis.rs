@@ -0,0 +1,357 @@
+//! Finding problems in the episodes of a series: missing chapters, chapters that have more than one
+//! file, and volumes whose chapters come before those of the previous volume.
+//!
+//! Chapters are compared by their whole part, so `12.5` and `12a` neither fill nor create a gap.
+//! Volumes are checked in order, with the episodes that have no volume last. A volume whose first
+//! chapter is 1 is assumed to restart the numbering, and is not compared with the previous one.
+//! Chapters missing between the end of a volume and the start of the next one are reported as
+//! missing between the two volumes.
+
+use core::fmt;
+
+use futures::TryStreamExt;
+use hashbrown::HashMap;
+use sqlx::prelude::*;
+
+use crate::chapter_number::ChapterNumber;
+use crate::models;
+
+#[derive(Debug, Default)]
+pub struct SeriesAnalysis {
+    pub gaps: Vec<ChapterGap>,
+    pub duplicates: Vec<DuplicateChapter>,
+    pub out_of_order_volumes: Vec<OutOfOrderVolume>,
+}
+
+/// Whole chapters that are missing between two existing ones.
+#[derive(Debug)]
+pub struct ChapterGap {
+    pub position: GapPosition,
+    pub first_missing: i64,
+    pub last_missing: i64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GapPosition {
+    /// Inside a volume, or among the episodes that have no volume.
+    InVolume(Option<i64>),
+    /// After the last chapter of a volume and before the first chapter of the next one, which may
+    /// be the episodes that have no volume.
+    BetweenVolumes(i64, Option<i64>),
+}
+
+/// A chapter that more than one episode was registered for.
+#[derive(Debug)]
+pub struct DuplicateChapter {
+    pub volume: Option<i64>,
+    pub number: ChapterNumber,
+    pub files: Vec<String>,
+}
+
+/// A volume that starts before the previous volume ends.
+#[derive(Debug)]
+pub struct OutOfOrderVolume {
+    pub volume: i64,
+    pub first_chapter: ChapterNumber,
+    pub previous_volume: i64,
+    pub previous_last_chapter: ChapterNumber,
+}
+
+impl SeriesAnalysis {
+    pub fn num_problems(&self) -> usize {
+        self.gaps.len() + self.duplicates.len() + self.out_of_order_volumes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_problems() == 0
+    }
+
+    /// Describe each problem in its own line.
+    pub fn problem_descriptions(&self) -> impl Iterator<Item = String> + '_ {
+        let gaps = self.gaps.iter().map(|gap| gap.to_string());
+        let duplicates = self.duplicates.iter().map(|dup| dup.to_string());
+        let out_of_order_volumes = self.out_of_order_volumes.iter().map(|ooo| ooo.to_string());
+        gaps.chain(duplicates).chain(out_of_order_volumes)
+    }
+}
+
+fn fmt_volume(f: &mut fmt::Formatter<'_>, volume: Option<i64>) -> fmt::Result {
+    if let Some(volume) = volume {
+        write!(f, " in volume {}", volume)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for ChapterGap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.first_missing == self.last_missing {
+            write!(f, "Missing chapter {}", self.first_missing)?;
+        } else {
+            write!(
+                f,
+                "Missing chapters {}-{}",
+                self.first_missing, self.last_missing
+            )?;
+        }
+        match self.position {
+            GapPosition::InVolume(volume) => fmt_volume(f, volume),
+            GapPosition::BetweenVolumes(previous_volume, Some(volume)) => write!(
+                f,
+                " between volume {} and volume {}",
+                previous_volume, volume
+            ),
+            GapPosition::BetweenVolumes(previous_volume, None) => write!(
+                f,
+                " between volume {} and the episodes without a volume",
+                previous_volume
+            ),
+        }
+    }
+}
+
+impl fmt::Display for DuplicateChapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Chapter {}", self.number)?;
+        fmt_volume(f, self.volume)?;
+        write!(
+            f,
+            " has {} files: {}",
+            self.files.len(),
+            self.files.join(", ")
+        )
+    }
+}
+
+impl fmt::Display for OutOfOrderVolume {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Volume {} starts at chapter {}, but volume {} ends at chapter {}",
+            self.volume, self.first_chapter, self.previous_volume, self.previous_last_chapter
+        )
+    }
+}
+
+/// Sort by volume and then by number, with the episodes that have no volume last.
+fn order_key(volume: Option<i64>, number: &ChapterNumber) -> (bool, Option<i64>, &ChapterNumber) {
+    (volume.is_none(), volume, number)
+}
+
+pub fn analyze(episodes: &[models::Episode]) -> SeriesAnalysis {
+    let mut analysis = SeriesAnalysis::default();
+
+    let mut files_by_chapter = HashMap::<(Option<i64>, &ChapterNumber), Vec<&str>>::new();
+    for episode in episodes.iter() {
+        files_by_chapter
+            .entry((episode.volume, &episode.number))
+            .or_default()
+            .push(&episode.file);
+    }
+    analysis.duplicates = files_by_chapter
+        .into_iter()
+        .filter(|(_, files)| 1 < files.len())
+        .map(|((volume, number), files)| DuplicateChapter {
+            volume,
+            number: number.clone(),
+            files: files.into_iter().map(|file| file.to_owned()).collect(),
+        })
+        .collect();
+    analysis
+        .duplicates
+        .sort_by(|a, b| order_key(a.volume, &a.number).cmp(&order_key(b.volume, &b.number)));
+
+    let mut sorted_episodes: Vec<&models::Episode> = episodes.iter().collect();
+    sorted_episodes
+        .sort_by(|a, b| order_key(a.volume, &a.number).cmp(&order_key(b.volume, &b.number)));
+
+    // The volume we are in, and the last chapter seen in it.
+    let mut previous: Option<(Option<i64>, &ChapterNumber)> = None;
+    for episode in sorted_episodes {
+        let last_chapter = episode.number_end.as_ref().unwrap_or(&episode.number);
+        let Some((previous_volume, previous_last_chapter)) = previous else {
+            previous = Some((episode.volume, last_chapter));
+            continue;
+        };
+        let is_new_volume = previous_volume != episode.volume;
+        if is_new_volume && episode.number.whole() <= 1 {
+            previous = Some((episode.volume, last_chapter));
+            continue;
+        }
+        if previous_last_chapter.whole() + 1 < episode.number.whole() {
+            let position = match previous_volume {
+                Some(previous_volume) if is_new_volume => {
+                    GapPosition::BetweenVolumes(previous_volume, episode.volume)
+                }
+                _ => GapPosition::InVolume(episode.volume),
+            };
+            analysis.gaps.push(ChapterGap {
+                position,
+                first_missing: previous_last_chapter.whole() + 1,
+                last_missing: episode.number.whole() - 1,
+            });
+        } else if is_new_volume && episode.number.whole() <= previous_last_chapter.whole() {
+            if let (Some(volume), Some(previous_volume)) = (episode.volume, previous_volume) {
+                analysis.out_of_order_volumes.push(OutOfOrderVolume {
+                    volume,
+                    first_chapter: episode.number.clone(),
+                    previous_volume,
+                    previous_last_chapter: previous_last_chapter.clone(),
+                });
+            }
+        }
+        // Within a volume, a range may already cover the chapters after this episode.
+        let last_chapter = if is_new_volume {
+            last_chapter
+        } else {
+            last_chapter.max(previous_last_chapter)
+        };
+        previous = Some((episode.volume, last_chapter));
+    }
+
+    analysis
+}
+
+pub async fn analyze_series(
+    con: &mut sqlx::SqliteConnection,
+    series_id: i64,
+) -> anyhow::Result<SeriesAnalysis> {
+    let episodes: Vec<models::Episode> = sqlx::query_as("SELECT * FROM episodes WHERE series = ?")
+        .bind(series_id)
+        .fetch_all(con.acquire().await?)
+        .await?;
+    Ok(analyze(&episodes))
+}
+
+/// Analyze all the serieses, and return the id, name and analysis of the ones with problems.
+pub async fn analyze_all_serieses(
+    con: &mut sqlx::SqliteConnection,
+) -> anyhow::Result<Vec<(i64, String, SeriesAnalysis)>> {
+    let serieses: Vec<(i64, String)> =
+        sqlx::query_as("SELECT id, name FROM serieses ORDER BY name")
+            .fetch(con.acquire().await?)
+            .try_collect()
+            .await?;
+    let mut result = Vec::new();
+    for (series_id, series_name) in serieses {
+        let analysis = analyze_series(con, series_id).await?;
+        if !analysis.is_empty() {
+            result.push((series_id, series_name, analysis));
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn episode(volume: Option<i64>, number: &str) -> models::Episode {
+        models::Episode {
+            id: 0,
+            series: 1,
+            number: number.parse().unwrap(),
+            number_end: None,
+            name: format!("c{}", number),
+            file: format!("c{}.cbz", number),
+            volume,
+            date_of_read: None,
+            file_size: None,
+            title: None,
+            page_count: None,
+            duration: None,
+            release_date: None,
+            content_hash: None,
+        }
+    }
+
+    fn descriptions(episodes: &[models::Episode]) -> Vec<String> {
+        analyze(episodes).problem_descriptions().collect()
+    }
+
+    #[test]
+    fn test_no_problems() {
+        assert!(analyze(&[]).is_empty());
+        assert!(analyze(&[
+            episode(None, "1"),
+            episode(None, "2"),
+            episode(None, "2.5"),
+            episode(None, "3"),
+        ])
+        .is_empty());
+        // A volume that starts at 1 restarts the numbering.
+        assert!(analyze(&[
+            episode(Some(1), "1"),
+            episode(Some(1), "2"),
+            episode(Some(2), "1"),
+        ])
+        .is_empty());
+    }
+
+    #[test]
+    fn test_gap_in_volume() {
+        assert_eq!(
+            descriptions(&[
+                episode(Some(1), "1"),
+                episode(Some(1), "4"),
+                episode(Some(1), "6"),
+            ]),
+            [
+                "Missing chapters 2-3 in volume 1",
+                "Missing chapter 5 in volume 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gap_covered_by_range() {
+        let mut range = episode(None, "2");
+        range.number_end = Some("4".parse().unwrap());
+        assert!(analyze(&[episode(None, "1"), range, episode(None, "5")]).is_empty());
+    }
+
+    #[test]
+    fn test_gap_between_volumes() {
+        let analysis = analyze(&[
+            episode(Some(1), "1"),
+            episode(Some(1), "2"),
+            episode(Some(2), "5"),
+            episode(Some(2), "6"),
+            episode(None, "8"),
+        ]);
+        assert_eq!(
+            analysis
+                .gaps
+                .iter()
+                .map(|gap| (&gap.position, gap.first_missing, gap.last_missing))
+                .collect::<Vec<_>>(),
+            [
+                (&GapPosition::BetweenVolumes(1, Some(2)), 3, 4),
+                (&GapPosition::BetweenVolumes(2, None), 7, 7),
+            ]
+        );
+        assert_eq!(
+            analysis.problem_descriptions().collect::<Vec<_>>(),
+            [
+                "Missing chapters 3-4 between volume 1 and volume 2",
+                "Missing chapter 7 between volume 2 and the episodes without a volume",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicates_and_out_of_order_volumes() {
+        assert_eq!(
+            descriptions(&[
+                episode(Some(1), "1"),
+                episode(Some(1), "2"),
+                episode(Some(1), "3"),
+                episode(Some(2), "3"),
+                episode(Some(2), "3"),
+            ]),
+            [
+                "Chapter 3 in volume 2 has 2 files: c3.cbz, c3.cbz",
+                "Volume 2 starts at chapter 3, but volume 1 ends at chapter 3",
+            ]
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use sqlx::prelude::*;
+use sqlx::types::chrono::{NaiveDate, Utc};
+
+const NUM_DAYS: usize = 14;
+const NUM_WEEKS: usize = 12;
+const NUM_MONTHS: usize = 12;
+const NUM_LONGEST_STREAKS: usize = 5;
+const BACKLOG_SIZE: usize = 20;
+
+#[derive(Debug)]
+pub struct Statistics {
+    pub total_reads: i64,
+    /// Like `total_reads`, but reads of episodes that cover a range of chapters count once per
+    /// chapter.
+    pub total_chapters_read: i64,
+    /// Pages and minutes read, counting only episodes whose metadata has them.
+    pub total_pages_read: i64,
+    pub total_minutes_read: i64,
+    pub reads_per_day: Vec<ReadsInPeriod>,
+    pub reads_per_week: Vec<ReadsInPeriod>,
+    pub reads_per_month: Vec<ReadsInPeriod>,
+    pub media_types: Vec<MediaTypeStatistics>,
+    pub current_streak: Option<ReadingStreak>,
+    pub longest_streaks: Vec<ReadingStreak>,
+    pub unread_backlog: Vec<SeriesBacklog>,
+    /// Average number of days from registering an episode's file to its first read. Only episodes
+    /// registered after discovery times started being recorded are counted.
+    pub average_days_to_read: Option<f64>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ReadsInPeriod {
+    pub period: String,
+    /// Reading a range of chapters counts as reading each of them.
+    pub num_reads: i64,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct MediaTypeStatistics {
+    pub name: String,
+    pub num_serieses: i64,
+    pub num_episodes: i64,
+    pub num_unread: i64,
+    /// Reading a range of chapters counts as reading each of them.
+    pub num_reads: i64,
+    pub pages_read: i64,
+    pub minutes_read: i64,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct SeriesBacklog {
+    pub series_name: String,
+    pub media_type_name: String,
+    pub num_unread: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReadingStreak {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl ReadingStreak {
+    pub fn num_days(&self) -> i64 {
+        (self.end - self.start).num_days() + 1
+    }
+}
+
+pub async fn gather_statistics(con: &mut sqlx::SqliteConnection) -> anyhow::Result<Statistics> {
+    let (total_reads,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM read_events")
+        .fetch_one(con.acquire().await?)
+        .await?;
+
+    // Fractional chapters and suffixes are part of the whole chapter they come after.
+    let (total_chapters_read,): (i64,) = sqlx::query_as(
+        r#"
+        SELECT COALESCE(SUM(
+            COALESCE(CAST(episodes.number_end AS INTEGER) - CAST(episodes.number AS INTEGER), 0) + 1
+        ), 0)
+        FROM read_events
+        INNER JOIN episodes ON episodes.id = read_events.episode
+        "#,
+    )
+    .fetch_one(con.acquire().await?)
+    .await?;
+
+    let (total_pages_read, total_minutes_read): (i64, i64) = sqlx::query_as(
+        r#"
+        SELECT COALESCE(SUM(episodes.page_count), 0)
+            , CAST(COALESCE(SUM(episodes.duration), 0) / 60 AS INTEGER)
+        FROM read_events
+        INNER JOIN episodes ON episodes.id = read_events.episode
+        "#,
+    )
+    .fetch_one(con.acquire().await?)
+    .await?;
+
+    let reads_per_day = reads_per_period(
+        con,
+        "date(timestamp)",
+        &format!("date('now', '-{} days')", NUM_DAYS - 1),
+    )
+    .await?;
+    // Weeks start on Monday, and are identified by that Monday's date.
+    let reads_per_week = reads_per_period(
+        con,
+        "date(timestamp, '-6 days', 'weekday 1')",
+        &format!(
+            "date('now', '-6 days', 'weekday 1', '-{} days')",
+            7 * (NUM_WEEKS - 1)
+        ),
+    )
+    .await?;
+    let reads_per_month = reads_per_period(
+        con,
+        "strftime('%Y-%m', timestamp)",
+        &format!(
+            "date('now', 'start of month', '-{} months')",
+            NUM_MONTHS - 1
+        ),
+    )
+    .await?;
+
+    let media_types = sqlx::query_as(
+        r#"
+        SELECT media_types.name AS name
+            , COUNT(DISTINCT serieses.id) AS num_serieses
+            , COUNT(episodes.id) AS num_episodes
+            , COALESCE(SUM(episodes.id IS NOT NULL AND episodes.date_of_read IS NULL), 0) AS num_unread
+            , (
+                SELECT COALESCE(SUM(
+                    COALESCE(CAST(read_episodes.number_end AS INTEGER) - CAST(read_episodes.number AS INTEGER), 0) + 1
+                ), 0)
+                FROM read_events
+                INNER JOIN episodes AS read_episodes ON read_episodes.id = read_events.episode
+                INNER JOIN serieses AS read_serieses ON read_serieses.id = read_episodes.series
+                WHERE read_serieses.media_type = media_types.id
+            ) AS num_reads
+            , (
+                SELECT COALESCE(SUM(read_episodes.page_count), 0)
+                FROM read_events
+                INNER JOIN episodes AS read_episodes ON read_episodes.id = read_events.episode
+                INNER JOIN serieses AS read_serieses ON read_serieses.id = read_episodes.series
+                WHERE read_serieses.media_type = media_types.id
+            ) AS pages_read
+            , (
+                SELECT CAST(COALESCE(SUM(read_episodes.duration), 0) / 60 AS INTEGER)
+                FROM read_events
+                INNER JOIN episodes AS read_episodes ON read_episodes.id = read_events.episode
+                INNER JOIN serieses AS read_serieses ON read_serieses.id = read_episodes.series
+                WHERE read_serieses.media_type = media_types.id
+            ) AS minutes_read
+        FROM media_types
+        LEFT JOIN serieses ON serieses.media_type = media_types.id
+        LEFT JOIN episodes ON episodes.series = serieses.id
+        GROUP BY media_types.id
+        ORDER BY media_types.name
+        "#,
+    )
+    .fetch_all(con.acquire().await?)
+    .await?;
+
+    let unread_backlog = sqlx::query_as(
+        r#"
+        SELECT serieses.name AS series_name
+            , media_types.name AS media_type_name
+            , COUNT(*) AS num_unread
+        FROM episodes
+        INNER JOIN serieses ON serieses.id = episodes.series
+        INNER JOIN media_types ON media_types.id = serieses.media_type
+        WHERE episodes.date_of_read IS NULL
+        GROUP BY serieses.id
+        ORDER BY num_unread DESC, serieses.name
+        LIMIT ?
+        "#,
+    )
+    .bind(BACKLOG_SIZE as i64)
+    .fetch_all(con.acquire().await?)
+    .await?;
+
+    let (average_days_to_read,): (Option<f64>,) = sqlx::query_as(
+        r#"
+        SELECT AVG(julianday(first_read) - julianday(date_of_discovery))
+        FROM (
+            SELECT episodes.date_of_discovery, MIN(read_events.timestamp) AS first_read
+            FROM episodes
+            INNER JOIN read_events ON read_events.episode = episodes.id
+            WHERE episodes.date_of_discovery IS NOT NULL
+            GROUP BY episodes.id
+        )
+        "#,
+    )
+    .fetch_one(con.acquire().await?)
+    .await?;
+
+    let read_dates = sqlx::query_as::<_, (String,)>(
+        "SELECT DISTINCT date(timestamp) FROM read_events WHERE timestamp IS NOT NULL ORDER BY 1",
+    )
+    .fetch_all(con.acquire().await?)
+    .await?
+    .into_iter()
+    .filter_map(|(date,)| NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok());
+    let streaks = split_to_streaks(read_dates);
+
+    let today = Utc::now().date_naive();
+    let current_streak = streaks
+        .last()
+        .filter(|streak| (today - streak.end).num_days() <= 1)
+        .copied();
+
+    let mut longest_streaks = streaks;
+    longest_streaks.sort_by(|a, b| {
+        b.num_days()
+            .cmp(&a.num_days())
+            .then_with(|| b.end.cmp(&a.end))
+    });
+    longest_streaks.truncate(NUM_LONGEST_STREAKS);
+
+    Ok(Statistics {
+        total_reads,
+        total_chapters_read,
+        total_pages_read,
+        total_minutes_read,
+        reads_per_day,
+        reads_per_week,
+        reads_per_month,
+        media_types,
+        current_streak,
+        longest_streaks,
+        unread_backlog,
+        average_days_to_read,
+    })
+}
+
+async fn reads_per_period(
+    con: &mut sqlx::SqliteConnection,
+    period_expr: &str,
+    since_expr: &str,
+) -> anyhow::Result<Vec<ReadsInPeriod>> {
+    Ok(sqlx::query_as(&format!(
+        r#"
+        SELECT {period_expr} AS period
+            , SUM(
+                COALESCE(CAST(episodes.number_end AS INTEGER) - CAST(episodes.number AS INTEGER), 0) + 1
+            ) AS num_reads
+        FROM read_events
+        INNER JOIN episodes ON episodes.id = read_events.episode
+        WHERE {since_expr} <= date(timestamp)
+        GROUP BY period
+        ORDER BY period DESC
+        "#,
+        period_expr = period_expr,
+        since_expr = since_expr,
+    ))
+    .fetch_all(con.acquire().await?)
+    .await?)
+}
+
+fn split_to_streaks(sorted_dates: impl Iterator<Item = NaiveDate>) -> Vec<ReadingStreak> {
+    let mut streaks = Vec::<ReadingStreak>::new();
+    for date in sorted_dates {
+        match streaks.last_mut() {
+            Some(streak) if (date - streak.end).num_days() == 1 => {
+                streak.end = date;
+            }
+            _ => {
+                streaks.push(ReadingStreak {
+                    start: date,
+                    end: date,
+                });
+            }
+        }
+    }
+    streaks
+}
@@ -16,7 +16,11 @@ pub async fn run_set_directory_dialog(widget: gtk::Entry, base_dir: Option<Strin
             dialog.set_current_folder(base_dir.as_str());
         }
     } else {
-        dialog.set_filename(current_choice.as_str());
+        // The current choice may be relative to the base directory
+        dialog.set_filename(crate::library_root::resolve(
+            base_dir.as_deref().unwrap_or(""),
+            current_choice.as_str(),
+        ));
     }
     let result = woab::run_dialog(&dialog, false).await;
     let filename = dialog.filename();
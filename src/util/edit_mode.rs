@@ -193,6 +193,21 @@ impl WidgetForEditMode<i64> for gtk::ComboBox {
     }
 }
 
+/// Uses the `id` of the active item.
+impl WidgetForEditMode<String> for gtk::ComboBoxText {
+    fn set_editability(&self, editability: bool) {
+        self.set_sensitive(editability);
+    }
+
+    fn get_value(&self) -> String {
+        self.active_id().map(|id| id.into()).unwrap_or_default()
+    }
+
+    fn set_value(&self, value: String) {
+        self.set_active_id(Some(&value));
+    }
+}
+
 impl WidgetForEditMode<bool> for gtk::ToggleButton {
     fn set_editability(&self, editability: bool) {
         self.set_sensitive(editability);